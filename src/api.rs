@@ -0,0 +1,260 @@
+use super::{control::Command, right::Right, watch::Event, Company, Config};
+use chrono::prelude::*;
+use serde_json::Value;
+#[cfg(unix)]
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    sync::mpsc::{self, Sender},
+    thread,
+};
+
+/// request on the control socket, one json object per line like `{"op": "pause", "company": "acme"}`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Request {
+    List,
+    /// state of one or all companies
+    Status(Option<String>),
+    SendNow(String),
+    /// send a request for another right once, with details for the letter
+    Once(String, Right, String),
+    Pause(String),
+    Resume(String),
+    /// read the companies from the config files again
+    Reload,
+    History(String),
+}
+
+impl Request {
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let json: Value =
+            serde_json::from_str(line).map_err(|err| format!("invalid json: {}", err))?;
+        let op = json["op"]
+            .as_str()
+            .ok_or_else(|| String::from("request needs an op"))?;
+        let name = json["company"].as_str().map(|v| v.to_string());
+        let company = || {
+            name.clone()
+                .ok_or_else(|| format!("{} needs a company", op))
+        };
+        match op {
+            "list" => Ok(Request::List),
+            "status" => Ok(Request::Status(name.clone())),
+            "send-now" => Ok(Request::SendNow(company()?)),
+            "request" => {
+                let right = json["type"]
+                    .as_str()
+                    .ok_or_else(|| String::from("request needs a type"))?;
+                let details = json["details"].as_str().unwrap_or_default().to_string();
+                Ok(Request::Once(company()?, Right::parse(right)?, details))
+            }
+            "pause" => Ok(Request::Pause(company()?)),
+            "resume" => Ok(Request::Resume(company()?)),
+            "reload" => Ok(Request::Reload),
+            "history" => Ok(Request::History(company()?)),
+            _ => Err(format!("unknown op '{}'", op)),
+        }
+    }
+
+    /// the request as sent over the socket
+    pub fn to_json(&self) -> Value {
+        match self {
+            Request::List => json!({"op": "list"}),
+            Request::Status(None) => json!({"op": "status"}),
+            Request::Status(Some(name)) => json!({"op": "status", "company": name}),
+            Request::SendNow(name) => json!({"op": "send-now", "company": name}),
+            Request::Once(name, right, details) => json!({
+                "op": "request",
+                "company": name,
+                "type": right.to_string(),
+                "details": details,
+            }),
+            Request::Pause(name) => json!({"op": "pause", "company": name}),
+            Request::Resume(name) => json!({"op": "resume", "company": name}),
+            Request::Reload => json!({"op": "reload"}),
+            Request::History(name) => json!({"op": "history", "company": name}),
+        }
+    }
+}
+
+/// answer to a request, `{"ok": true, "result": ...}` or `{"ok": false, "error": "..."}`
+pub fn response(result: Result<Value, String>) -> Value {
+    match result {
+        Ok(result) => json!({"ok": true, "result": result}),
+        Err(err) => json!({"ok": false, "error": err}),
+    }
+}
+
+/// socket used by the daemon and datenbriefctl if none is given, in the runtime directory
+pub fn default_socket() -> String {
+    let dir = std::env::var_os("XDG_RUNTIME_DIR")
+        .map(std::path::PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir);
+    dir.join("datenbriefd.sock").to_string_lossy().to_string()
+}
+
+/// listen on the unix socket `path` and pass the requests to the daemon through `tx`
+///
+/// The socket is only accessible by the user running the daemon, there is no other
+/// authentication.
+#[cfg(unix)]
+pub(crate) fn spawn_socket(path: &str, tx: Sender<Event>) -> io::Result<()> {
+    if UnixStream::connect(path).is_ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            "another daemon listens on the socket",
+        ));
+    }
+    // left over by a daemon which did not stop cleanly
+    let _ = fs::remove_file(path);
+    // created with the right mode, changing it after bind leaves a window to connect
+    let umask = unsafe { libc::umask(0o177) };
+    let listener = UnixListener::bind(path);
+    unsafe { libc::umask(umask) };
+    let listener = listener?;
+    info!("listening for api requests on {}", path);
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let tx = tx.clone();
+                    thread::spawn(move || {
+                        if let Err(err) = serve(stream, &tx) {
+                            debug!("api connection failed: {}", err);
+                        }
+                    });
+                }
+                Err(err) => warn!("could not accept api connection: {}", err),
+            }
+        }
+    });
+    Ok(())
+}
+
+/// answer the requests of one connection, line by line
+#[cfg(unix)]
+fn serve(stream: UnixStream, tx: &Sender<Event>) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match Request::parse(&line) {
+            Ok(request) => {
+                let (reply, answer) = mpsc::channel();
+                if tx.send(Event::Request(request, reply)).is_err() {
+                    return Ok(());
+                }
+                answer
+                    .recv()
+                    .unwrap_or_else(|_| response(Err(String::from("daemon stopped"))))
+            }
+            Err(err) => response(Err(err)),
+        };
+        writeln!(writer, "{}", response)?;
+    }
+    Ok(())
+}
+
+impl Config {
+    /// run the api `request`, returns the result as json
+    pub fn request(&mut self, request: &Request, now: DateTime<Utc>) -> Result<Value, String> {
+        match request {
+            Request::List => Ok(self
+                .companies
+                .iter()
+                .map(|v| json!({"name": v.name, "mail": v.mail, "next": v.next_hit.to_rfc3339()}))
+                .collect()),
+            Request::Status(None) => Ok(self
+                .companies
+                .iter()
+                .map(|v| self.company_json(v, now))
+                .collect()),
+            Request::Status(Some(name)) => {
+                let i = self.company_index(name)?;
+                Ok(self.company_json(&self.companies[i], now))
+            }
+            Request::SendNow(name) => self
+                .execute(&Command::Send(name.clone()), now)
+                .map(Value::from),
+            Request::Once(name, right, details) => self
+                .execute(
+                    &Command::Request(name.clone(), *right, details.clone()),
+                    now,
+                )
+                .map(Value::from),
+            Request::Pause(name) => self
+                .execute(&Command::Pause(name.clone()), now)
+                .map(Value::from),
+            Request::Resume(name) => self
+                .execute(&Command::Resume(name.clone()), now)
+                .map(Value::from),
+            Request::Reload => self.reload(now).map(Value::from),
+            Request::History(name) => {
+                let v: &Company = &self.companies[self.company_index(name)?];
+                let mut replies = self
+                    .archive
+                    .records(v)
+                    .map_err(|err| format!("could not read archive of {}: {}", v.name, err))?;
+                // the http api has no login, the passwords stay in the archive
+                for meta in replies.iter_mut() {
+                    if let Some(meta) = meta.as_object_mut() {
+                        meta.remove("passwords");
+                    }
+                    for attachment in meta["attachments"].as_array_mut().into_iter().flatten() {
+                        if let Some(attachment) = attachment.as_object_mut() {
+                            attachment.remove("password");
+                        }
+                    }
+                }
+                let events: Vec<Value> = v
+                    .history
+                    .iter()
+                    .map(|(date, event)| json!({"date": date.to_rfc3339(), "event": event}))
+                    .collect();
+                Ok(json!({"company": v.name, "events": events, "replies": replies}))
+            }
+        }
+    }
+
+    /// configuration and state of `v` at `now` as json
+    pub fn company_json(&self, v: &Company, now: DateTime<Utc>) -> Value {
+        let date = |v: Option<DateTime<Utc>>| v.map(|v| v.to_rfc3339());
+        json!({
+            "name": v.name,
+            "mail": v.mail,
+            "alias": v.alias,
+            "own-name": v.onw_name,
+            "interval": v.interval,
+            "language": v.language.unwrap_or(self.language).to_string(),
+            "next": v.next_hit.to_rfc3339(),
+            "last": date(v.last_hit),
+            "reference": v.reference,
+            "reminder": v.reminder,
+            "next-reminder": date(self.scheduler.reminder_at(v)),
+            "right": v.right.to_string(),
+            "pending": v.pending.as_ref().map(|(right, _)| right.to_string()),
+            "deadline": date(v.deadline()),
+            "extended": date(v.extended),
+            "verifying": date(v.verifying),
+            "escalation": date(self.scheduler.escalation_at(v)),
+            "complained": date(v.complained),
+            "authority": v.authority.as_ref().or(self.complaint.authority.as_ref()),
+            "overdue": v.is_overdue(now),
+            "paused": v.paused,
+            "invalid": v.invalid.as_ref().map(|(mail, reason, date)| {
+                json!({"mail": mail, "reason": reason, "date": date.to_rfc3339()})
+            }),
+            "answered": date(v.answered),
+            "ticket": v.ticket,
+            "outcome": v.outcome.map(|(kind, date)| {
+                json!({"kind": kind.to_string(), "date": date.to_rfc3339()})
+            }),
+            "last-reply": date(v.last_reply),
+            "fulfilled": date(v.fulfilled),
+            "response-days": v.response_days,
+        })
+    }
+}
@@ -0,0 +1,298 @@
+use super::{mime::Part, sha256, slug, unpack, Company};
+use chrono::prelude::*;
+use serde_json::Value;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// per company store of replies and their attachments
+#[derive(Debug, Clone)]
+pub struct Archive {
+    pub dir: String,
+}
+
+impl Archive {
+    pub fn new() -> Self {
+        Self {
+            dir: String::from("archive"),
+        }
+    }
+
+    /// directory of `company`
+    pub fn company_dir(&self, company: &Company) -> PathBuf {
+        PathBuf::from(&self.dir).join(slug(&company.name))
+    }
+
+    /// store the raw `message`, its attachments and a `meta.json` sidecar
+    ///
+    /// Returns the directory of the stored message, storing the same message again is a no-op.
+    pub fn store(
+        &self,
+        company: &Company,
+        raw: &[u8],
+        message: &Part,
+        now: DateTime<Utc>,
+    ) -> io::Result<PathBuf> {
+        let hash = sha256(raw);
+        let date = message.date().unwrap_or(now);
+        let dir = self.company_dir(company).join(format!(
+            "{}-{}",
+            date.format("%Y%m%dT%H%M%S"),
+            &hash[..12]
+        ));
+        if dir.join("meta.json").exists() {
+            debug!("{} is already archived", dir.display());
+            return Ok(dir);
+        }
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join("message.eml"), raw)?;
+
+        let mut attachments = Vec::new();
+        for part in message.leaves().into_iter().filter(|v| v.is_attachment()) {
+            let name = unique_name(&dir, &file_name(part, attachments.len()));
+            fs::write(dir.join(&name), &part.body)?;
+            let mut attachment = json!({
+                "name": name,
+                "type": part.content_type(),
+                "size": part.body.len(),
+                "sha256": sha256(&part.body),
+            });
+            let dest = dir.join(format!("{}-unpacked", name));
+            // office documents are zip files too, but only useful as a whole
+            let unpacked = if unpack::is_document(&name, &part.content_type()) {
+                Ok(None)
+            } else {
+                unpack::unpack(&part.body, &dest, None)
+            };
+            match unpacked {
+                Ok(Some(unpacked)) => {
+                    attachment["unpacked"] = json!(unpacked.files);
+                    attachment["encrypted"] = json!(unpacked.encrypted);
+                }
+                Ok(None) => attachment["encrypted"] = json!(unpack::is_encrypted_pdf(&part.body)),
+                Err(err) => warn!("could not unpack {}: {}", name, err),
+            }
+            attachments.push(attachment);
+        }
+        let passwords = unpack::passwords(&message.text());
+
+        let meta = json!({
+            "company": company.name,
+            "date": date.to_rfc3339(),
+            "received": now.to_rfc3339(),
+            "from": message.decoded("From"),
+            "subject": message.decoded("Subject"),
+            "message-id": message.header("Message-ID"),
+            "reference": company.reference,
+            "sha256": hash,
+            "attachments": attachments,
+            "passwords": passwords,
+        });
+        fs::write(
+            dir.join("meta.json"),
+            serde_json::to_string_pretty(&meta).unwrap(),
+        )?;
+        info!(
+            "archived reply of {} with {} attachments in {}",
+            company.name,
+            attachments.len(),
+            dir.display()
+        );
+        if let Err(err) = self.pair(company) {
+            warn!("could not pair passwords of {}: {}", company.name, err);
+        }
+        Ok(dir)
+    }
+
+    /// try the passwords sent in separate mails on encrypted attachments
+    ///
+    /// Zip archives are unpacked with the first password that fits, other files get the newest
+    /// password noted in their metadata.
+    pub fn pair(&self, company: &Company) -> io::Result<()> {
+        let records = self.entries(company)?;
+        let mut passwords: Vec<(String, String)> = Vec::new();
+        for (dir, meta) in records.iter().rev() {
+            let from = dir_name(dir);
+            for v in meta["passwords"].as_array().into_iter().flatten() {
+                if let Some(v) = v.as_str() {
+                    passwords.push((v.to_string(), from.clone()));
+                }
+            }
+        }
+        if passwords.is_empty() {
+            return Ok(());
+        }
+
+        for (dir, mut meta) in records {
+            let mut changed = false;
+            for attachment in meta["attachments"].as_array_mut().into_iter().flatten() {
+                if attachment["encrypted"] != true || attachment.get("password").is_some() {
+                    continue;
+                }
+                let name = attachment["name"].as_str().unwrap_or_default().to_string();
+                let data = fs::read(dir.join(&name))?;
+                let dest = dir.join(format!("{}-unpacked", name));
+                for (password, from) in passwords.iter() {
+                    match unpack::unpack(&data, &dest, Some(password)) {
+                        Ok(Some(unpacked)) if unpacked.encrypted => continue,
+                        Ok(Some(unpacked)) => attachment["unpacked"] = json!(unpacked.files),
+                        // the password of other files can not be checked
+                        Ok(None) => (),
+                        Err(err) => {
+                            debug!("password from {} does not fit {}: {}", from, name, err);
+                            continue;
+                        }
+                    }
+                    info!(
+                        "paired {} of {} with password from {}",
+                        name, company.name, from
+                    );
+                    attachment["password"] = json!(password);
+                    attachment["password-mail"] = json!(from);
+                    changed = true;
+                    break;
+                }
+            }
+            if changed {
+                fs::write(
+                    dir.join("meta.json"),
+                    serde_json::to_string_pretty(&meta).unwrap(),
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// metadata of all archived replies of `company`, oldest first
+    pub fn records(&self, company: &Company) -> io::Result<Vec<Value>> {
+        Ok(self
+            .entries(company)?
+            .into_iter()
+            .map(|(_, meta)| meta)
+            .collect())
+    }
+
+    /// keep a copy of the letter `id` to `company` accepted at `now`
+    pub fn store_letter(
+        &self,
+        company: &Company,
+        id: &str,
+        message: &[u8],
+        now: DateTime<Utc>,
+    ) -> io::Result<()> {
+        let dir = self.company_dir(company).join("letters");
+        fs::create_dir_all(&dir)?;
+        let name = format!("{}-{}.eml", now.format("%Y%m%dT%H%M%S"), id);
+        fs::write(dir.join(name), message)
+    }
+
+    /// names and raw copies of the letters to `company` sent since `since`, oldest first
+    pub fn letters(
+        &self,
+        company: &Company,
+        since: DateTime<Utc>,
+    ) -> io::Result<Vec<(String, Vec<u8>)>> {
+        let dir = match fs::read_dir(self.company_dir(company).join("letters")) {
+            Ok(dir) => dir,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+        // names start with the time the letter was accepted
+        let since = since.format("%Y%m%dT%H%M%S").to_string();
+        let mut paths: Vec<PathBuf> = dir
+            .filter_map(|v| v.ok().map(|v| v.path()))
+            .filter(|v| {
+                dir_name(v)
+                    .get(..since.len())
+                    .is_some_and(|v| v >= since.as_str())
+            })
+            .collect();
+        paths.sort();
+        paths
+            .into_iter()
+            .map(|v| Ok((dir_name(&v), fs::read(&v)?)))
+            .collect()
+    }
+
+    /// names and raw messages of the replies of `company` received since `since`, oldest first
+    pub fn replies(
+        &self,
+        company: &Company,
+        since: DateTime<Utc>,
+    ) -> io::Result<Vec<(String, Vec<u8>)>> {
+        let mut replies = Vec::new();
+        for (dir, meta) in self.entries(company)? {
+            let received = meta["received"]
+                .as_str()
+                .and_then(|v| v.parse::<DateTime<Utc>>().ok());
+            if received.is_some_and(|v| v >= since) {
+                let message = fs::read(dir.join("message.eml"))?;
+                replies.push((format!("{}.eml", dir_name(&dir)), message));
+            }
+        }
+        Ok(replies)
+    }
+
+    /// directories and metadata of all archived replies of `company`, oldest first
+    fn entries(&self, company: &Company) -> io::Result<Vec<(PathBuf, Value)>> {
+        let dir = match fs::read_dir(self.company_dir(company)) {
+            Ok(dir) => dir,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+        let mut paths: Vec<PathBuf> = dir
+            .filter_map(|v| v.ok())
+            .map(|v| v.path().join("meta.json"))
+            .filter(|v| v.exists())
+            .collect();
+        paths.sort();
+        let mut records = Vec::new();
+        for path in paths {
+            match serde_json::from_str(&fs::read_to_string(&path)?) {
+                Ok(value) => records.push((path.parent().unwrap().to_path_buf(), value)),
+                Err(err) => warn!("could not parse {}: {}", path.display(), err),
+            }
+        }
+        Ok(records)
+    }
+}
+
+impl Default for Archive {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// safe file name of an attachment
+fn file_name(part: &Part, index: usize) -> String {
+    let name = part.filename().unwrap_or_default();
+    // drop any directory the sender put into the name
+    let name = name.rsplit(['/', '\\']).next().unwrap_or_default();
+    let name: String = name
+        .chars()
+        .map(|v| if v.is_control() { '_' } else { v })
+        .collect();
+    let name = name.trim().trim_start_matches('.');
+    if name.is_empty() || name == "message.eml" || name == "meta.json" {
+        return format!("attachment-{}", index + 1);
+    }
+    name.to_string()
+}
+
+fn dir_name(dir: &Path) -> String {
+    dir.file_name()
+        .map(|v| v.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// `name`, prefixed with a number if it already exists in `dir`
+fn unique_name(dir: &Path, name: &str) -> String {
+    if !dir.join(name).exists() {
+        return name.to_string();
+    }
+    (2..)
+        .map(|v| format!("{}-{}", v, name))
+        .find(|v| !dir.join(v).exists())
+        .unwrap()
+}
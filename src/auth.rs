@@ -0,0 +1,286 @@
+use super::{
+    mime::{self, Part},
+    sha256,
+};
+use chrono::{prelude::*, Duration};
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// who may send commands to the control mailbox
+#[derive(Debug, Clone)]
+pub struct ControlAuth {
+    /// allowed sender addresses, lower case
+    pub senders: Vec<String>,
+    /// shared secret which has to appear as `token <secret>` line in the mail
+    ///
+    /// Token mails are told apart by their Message-ID and Date headers only, anyone who
+    /// reads one on its way can send it again with new ones. Replay protection for tokens
+    /// is only as good as the transport, prefer signatures.
+    pub token: Option<String>,
+    /// keyring with the public keys allowed to sign commands
+    pub keyring: Option<String>,
+    pub gpgv: String,
+    /// file every accepted and rejected control mail is recorded in
+    pub audit_log: String,
+    /// control mails and signatures dated further from now are rejected
+    pub max_age: Duration,
+}
+
+/// how a control mail proved where it came from
+#[derive(Debug, Clone, PartialEq)]
+pub enum Proof {
+    Token,
+    /// valid signature, only the signed text may be trusted
+    Signature {
+        text: String,
+        /// hash of the key, time and text of the signature, the same for a replayed mail
+        id: String,
+    },
+}
+
+impl ControlAuth {
+    pub fn new() -> Self {
+        Self {
+            senders: Vec::new(),
+            token: None,
+            keyring: None,
+            gpgv: String::from("gpgv"),
+            audit_log: String::from("audit.log"),
+            max_age: Duration::hours(24),
+        }
+    }
+
+    /// check sender, date and token or signature of the control mail `message`
+    ///
+    /// The sender alone is never enough, it is trivial to forge.
+    pub fn check(&self, message: &Part, now: DateTime<Utc>) -> Result<Proof, String> {
+        let from = message
+            .header("From")
+            .map(mime::address)
+            .unwrap_or_default();
+        if !self.senders.iter().any(|v| v.eq_ignore_ascii_case(&from)) {
+            return Err(format!("sender '{}' is not allowed", from));
+        }
+        if self.token.is_none() && self.keyring.is_none() {
+            return Err(String::from("neither token nor keyring configured"));
+        }
+        let date = message
+            .date()
+            .ok_or_else(|| String::from("missing or invalid date"))?;
+        if date < now - self.max_age || date > now + self.max_age {
+            return Err(format!("date {} is too far from now", date.to_rfc3339()));
+        }
+
+        if let Some(keyring) = &self.keyring {
+            match self.verify(keyring, message) {
+                Ok(Some((text, signed))) => {
+                    // unlike the date header the signature time can not be changed
+                    if signed.created < now - self.max_age || signed.created > now + self.max_age {
+                        return Err(format!(
+                            "signature from {} is too far from now",
+                            signed.created.to_rfc3339()
+                        ));
+                    }
+                    let id = format!(
+                        "sig:{}",
+                        sha256(format!("{} {} {}", signed.key, signed.created, text).as_bytes())
+                    );
+                    return Ok(Proof::Signature { text, id });
+                }
+                Ok(None) => (),
+                Err(err) => return Err(format!("invalid signature: {}", err)),
+            }
+        }
+        if let Some(token) = &self.token {
+            let text = message.text();
+            let given = text.lines().find_map(token_value).unwrap_or_default();
+            if !given.is_empty() && same(given.as_bytes(), token.as_bytes()) {
+                return Ok(Proof::Token);
+            }
+            return Err(String::from("missing or wrong token"));
+        }
+        Err(String::from("mail is not signed"))
+    }
+
+    /// write `line` to the audit log
+    pub fn audit(&self, now: DateTime<Utc>, line: &str) {
+        let result = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.audit_log)
+            .and_then(|mut v| writeln!(v, "{} {}", now.to_rfc3339(), line));
+        if let Err(err) = result {
+            error!("could not write audit log {}: {}", self.audit_log, err);
+        }
+    }
+
+    /// verify a pgp/mime or inline signature, returns the signed text or `None` if unsigned
+    fn verify(&self, keyring: &str, message: &Part) -> io::Result<Option<(String, Signed)>> {
+        if message.content_type() == "multipart/signed" {
+            let boundary = message
+                .param("Content-Type", "boundary")
+                .unwrap_or_default();
+            let parts = mime::split_multipart(&message.body, &boundary);
+            if parts.len() != 2 {
+                return Err(io::Error::other("multipart/signed needs two parts"));
+            }
+            let signature = Part::parse(parts[1]);
+            // the signature covers the part exactly as sent, with canonical line endings
+            let data = canonical(parts[0]);
+            let signed = self.gpgv(keyring, &data, Some(&signature.body))?;
+            return Ok(Some((Part::parse(&data).text(), signed)));
+        }
+
+        let text = message.text();
+        let start = match text.find("-----BEGIN PGP SIGNED MESSAGE-----") {
+            Some(start) => start,
+            None => return Ok(None),
+        };
+        let end = text[start..]
+            .find("-----END PGP SIGNATURE-----")
+            .map(|v| start + v + 27)
+            .ok_or_else(|| io::Error::other("incomplete signature"))?;
+        let signed = self.gpgv(keyring, &text.as_bytes()[start..end], None)?;
+        Ok(Some((clearsigned_text(&text[start..end]), signed)))
+    }
+
+    fn gpgv(&self, keyring: &str, data: &[u8], signature: Option<&[u8]>) -> io::Result<Signed> {
+        let dir = private_dir()?;
+        let data_file = dir.join("data.txt");
+        let signature_file = dir.join("data.asc");
+        let output = write_new(&data_file, data).and_then(|_| {
+            let mut command = Command::new(&self.gpgv);
+            command.arg("--status-fd").arg("1");
+            command.arg("--keyring").arg(keyring);
+            if let Some(signature) = signature {
+                write_new(&signature_file, signature)?;
+                command.arg(&signature_file);
+            }
+            command.arg(&data_file).output()
+        });
+        if let Err(err) = fs::remove_dir_all(&dir) {
+            warn!("could not remove {}: {}", dir.display(), err);
+        }
+        let output = output?;
+        if !output.status.success() {
+            let err = String::from_utf8_lossy(&output.stderr);
+            return Err(io::Error::other(
+                err.lines().last().unwrap_or_default().to_string(),
+            ));
+        }
+        trace!("{}", String::from_utf8_lossy(&output.stderr));
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .find_map(|v| v.strip_prefix("[GNUPG:] VALIDSIG "))
+            .and_then(Signed::parse)
+            .ok_or_else(|| io::Error::other("gpgv reported no valid signature"))
+    }
+}
+
+/// who signed a control mail and when
+#[derive(Debug, Clone, PartialEq)]
+struct Signed {
+    /// fingerprint of the signing key
+    key: String,
+    created: DateTime<Utc>,
+}
+
+impl Signed {
+    /// parse the arguments of a `VALIDSIG` status line
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.split_whitespace();
+        let key = fields.next()?.to_string();
+        // the time follows the creation day, in seconds or as iso 8601 with `fixed-list-mode`
+        let time = fields.nth(1)?;
+        let created = match time.parse::<i64>() {
+            Ok(seconds) => Utc.timestamp_opt(seconds, 0).single()?,
+            Err(_) => Utc.datetime_from_str(time, "%Y%m%dT%H%M%S").ok()?,
+        };
+        Some(Self { key, created })
+    }
+}
+
+/// new directory in the temp dir only this user may enter
+fn private_dir() -> io::Result<PathBuf> {
+    let mut builder = fs::DirBuilder::new();
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    for attempt in 0..10 {
+        let dir = std::env::temp_dir().join(format!(
+            "datenbriefd-{}-{}-{}",
+            std::process::id(),
+            Utc::now().timestamp_nanos(),
+            attempt
+        ));
+        // fails instead of following a link planted under the same name
+        match builder.create(&dir) {
+            Ok(()) => return Ok(dir),
+            Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err),
+        }
+    }
+    Err(io::Error::other(
+        "could not create a private temporary directory",
+    ))
+}
+
+/// write `data` to the file `path` which must not exist yet
+fn write_new(path: &Path, data: &[u8]) -> io::Result<()> {
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)?
+        .write_all(data)
+}
+
+impl Default for ControlAuth {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// value of a `token <secret>` or `token: <secret>` line
+pub fn token_value(line: &str) -> Option<&str> {
+    let line = line.trim();
+    let rest = match line.get(..5) {
+        Some(word) if word.eq_ignore_ascii_case("token") => &line[5..],
+        _ => return None,
+    };
+    if !rest.starts_with(':') && !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    Some(rest.trim_start_matches(':').trim()).filter(|v| !v.is_empty())
+}
+
+/// compare without leaking the position of the first difference
+fn same(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b.iter()).fold(0, |v, (a, b)| v | (a ^ b)) == 0
+}
+
+/// `data` with every line ending as CRLF
+fn canonical(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    for (i, v) in data.iter().enumerate() {
+        if *v == b'\n' && (i == 0 || data[i - 1] != b'\r') {
+            out.push(b'\r');
+        }
+        out.push(*v);
+    }
+    out
+}
+
+/// text of a clear signed message without armor and dash escaping
+fn clearsigned_text(armored: &str) -> String {
+    armored
+        .lines()
+        .skip_while(|v| !v.trim().is_empty())
+        .skip(1)
+        .take_while(|v| !v.starts_with("-----BEGIN PGP SIGNATURE-----"))
+        .map(|v| v.strip_prefix("- ").unwrap_or(v))
+        .collect::<Vec<&str>>()
+        .join("\n")
+}
@@ -0,0 +1,95 @@
+use super::mime::Part;
+use regex::Regex;
+
+/// subjects and texts of automatic replies and acknowledgements
+const PATTERNS: &[&str] = &[
+    r"(?i)automatic reply|auto-?reply|out of office",
+    r"(?i)automatische antwort|abwesenheitsnotiz|eingangsbest[äa]tigung",
+    r"(?i)we (have )?received your (request|inquiry|message|e-?mail)",
+    r"(?i)wir haben ihre (anfrage|nachricht|e-?mail) erhalten",
+    r"(?i)ihre (anfrage|nachricht|e-?mail) ist (bei uns )?eingegangen",
+    r"(?i)(this is an|dies ist eine) automatisch",
+];
+
+/// ticket numbers in acknowledgements, the first group is the number
+const TICKETS: &[&str] = &[
+    r"(?i)\b(?:ticket|case|vorgang|anfrage|request)-?(?:nummer|number|nr\.?|no\.?|id)?\s*[:#]\s*#?([a-z0-9][a-z0-9-]{2,}[0-9])",
+    r"(?i)\b(?:ticket|case|vorgang)-?(?:nummer|number|nr\.?|no\.?|id)?\s+#?([0-9][a-z0-9-]{2,})",
+];
+
+/// detection of automatically generated replies like ticket acknowledgements
+#[derive(Debug, Clone)]
+pub struct AutoReply {
+    /// subject or text patterns marking a reply without attachments as automatic
+    pub patterns: Vec<Regex>,
+    /// patterns of ticket numbers, the first group is the number
+    pub tickets: Vec<Regex>,
+}
+
+impl AutoReply {
+    pub fn new() -> Self {
+        Self {
+            patterns: PATTERNS.iter().map(|v| Regex::new(v).unwrap()).collect(),
+            tickets: TICKETS.iter().map(|v| Regex::new(v).unwrap()).collect(),
+        }
+    }
+
+    /// whether `message` was generated automatically instead of answering the request
+    pub fn is_auto(&self, message: &Part) -> bool {
+        // rfc 3834
+        if let Some(value) = message.header("Auto-Submitted") {
+            if !value.trim().eq_ignore_ascii_case("no") {
+                return true;
+            }
+        }
+        if message.header("X-Autoreply").is_some()
+            || message.header("X-Autorespond").is_some()
+            || message.header("X-Autogenerated").is_some()
+        {
+            return true;
+        }
+        if let Some(value) = message.header("Precedence") {
+            let value = value.trim().to_lowercase();
+            if value == "auto_reply" || value == "bulk" || value == "junk" {
+                return true;
+            }
+        }
+
+        // a mail with attachments is most likely the answer itself
+        if message.leaves().iter().any(|v| v.is_attachment()) {
+            return false;
+        }
+        let subject = message.decoded("Subject").unwrap_or_default();
+        let text = message.text();
+        self.patterns
+            .iter()
+            .any(|v| v.is_match(&subject) || v.is_match(&text))
+    }
+
+    /// ticket number in the subject or text of `message`, ignoring our own `reference`
+    pub fn ticket(&self, message: &Part, reference: Option<&str>) -> Option<String> {
+        let subject = message.decoded("Subject").unwrap_or_default();
+        let text = message.text();
+        for haystack in [subject.as_str(), text.as_str()].iter() {
+            for pattern in self.tickets.iter() {
+                for captures in pattern.captures_iter(haystack) {
+                    let ticket = match captures.get(1) {
+                        Some(ticket) => ticket.as_str(),
+                        None => continue,
+                    };
+                    if reference.is_some_and(|v| v.eq_ignore_ascii_case(ticket)) {
+                        continue;
+                    }
+                    return Some(ticket.to_string());
+                }
+            }
+        }
+        None
+    }
+}
+
+impl Default for AutoReply {
+    fn default() -> Self {
+        Self::new()
+    }
+}
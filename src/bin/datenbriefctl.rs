@@ -0,0 +1,150 @@
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use datenbriefd::{
+    api::{self, Request},
+    right::Right,
+};
+use serde_json::Value;
+#[cfg(unix)]
+use std::{
+    io::{BufRead, BufReader, Write},
+    os::unix::net::UnixStream,
+};
+
+fn main() {
+    let default_socket = api::default_socket();
+    let company = || {
+        Arg::with_name("company")
+            .value_name("COMPANY")
+            .help("name of the company")
+            .required(true)
+    };
+    let matches = App::new("datenbriefctl")
+        .version(env!("CARGO_PKG_VERSION"))
+        .author("Kloenk <me@kloenk.de>")
+        .about("control a running datenbriefd over its api socket")
+        .setting(AppSettings::ColorAuto)
+        .setting(AppSettings::ColoredHelp)
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .arg(
+            Arg::with_name("socket")
+                .short("s")
+                .long("socket")
+                .value_name("FILE")
+                .help("unix socket of the daemon")
+                .takes_value(true)
+                .default_value(&default_socket),
+        )
+        .subcommand(SubCommand::with_name("list").about("list all companies"))
+        .subcommand(
+            SubCommand::with_name("status")
+                .about("show the state of one or all companies")
+                .arg(company().required(false)),
+        )
+        .subcommand(
+            SubCommand::with_name("send-now")
+                .about("send a request right now")
+                .arg(company()),
+        )
+        .subcommand(
+            SubCommand::with_name("request")
+                .about("send a request for another right once")
+                .arg(company())
+                .arg(
+                    Arg::with_name("type")
+                        .value_name("TYPE")
+                        .help("rectification, erasure, restriction, portability or objection")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("details")
+                        .short("d")
+                        .long("details")
+                        .value_name("TEXT")
+                        .help("added to the letter, required for rectification")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("pause")
+                .about("stop requests and reminders")
+                .arg(company()),
+        )
+        .subcommand(
+            SubCommand::with_name("resume")
+                .about("send requests and reminders again")
+                .arg(company()),
+        )
+        .subcommand(
+            SubCommand::with_name("reload").about("read the companies from the config again"),
+        )
+        .subcommand(
+            SubCommand::with_name("history")
+                .about("show the events and archived replies of a company")
+                .arg(company()),
+        )
+        .get_matches();
+
+    let request = request(&matches);
+    let socket = matches.value_of("socket").unwrap_or_default();
+    match send(socket, &request) {
+        Ok(response) if response["ok"] == Value::Bool(true) => match &response["result"] {
+            Value::String(result) => println!("{}", result),
+            result => println!("{}", serde_json::to_string_pretty(result).unwrap()),
+        },
+        Ok(response) => {
+            eprintln!("{}", response["error"].as_str().unwrap_or("request failed"));
+            std::process::exit(1);
+        }
+        Err(err) => {
+            eprintln!("could not talk to datenbriefd on {}: {}", socket, err);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// api request for the sub command in `matches`
+fn request(matches: &ArgMatches) -> Request {
+    let (command, args) = matches.subcommand();
+    let company = args
+        .and_then(|v| v.value_of("company"))
+        .map(|v| v.to_string());
+    match command {
+        "list" => Request::List,
+        "status" => Request::Status(company),
+        "send-now" => Request::SendNow(company.unwrap_or_default()),
+        "request" => {
+            let args = args.unwrap();
+            let right = match Right::parse(args.value_of("type").unwrap_or_default()) {
+                Ok(right) => right,
+                Err(err) => {
+                    eprintln!("{}", err);
+                    std::process::exit(1);
+                }
+            };
+            let details = args.value_of("details").unwrap_or_default().to_string();
+            Request::Once(company.unwrap_or_default(), right, details)
+        }
+        "pause" => Request::Pause(company.unwrap_or_default()),
+        "resume" => Request::Resume(company.unwrap_or_default()),
+        "reload" => Request::Reload,
+        _ => Request::History(company.unwrap_or_default()),
+    }
+}
+
+/// send `request` to the daemon listening on `socket` and wait for the response
+#[cfg(unix)]
+fn send(socket: &str, request: &Request) -> std::io::Result<Value> {
+    let mut stream = UnixStream::connect(socket)?;
+    writeln!(stream, "{}", request.to_json())?;
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line)?;
+    serde_json::from_str(&line).map_err(std::io::Error::other)
+}
+
+#[cfg(not(unix))]
+fn send(_: &str, _: &Request) -> std::io::Result<Value> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "unix sockets are not supported on this platform",
+    ))
+}
@@ -0,0 +1,189 @@
+use super::mime::{self, Part};
+
+/// permanent delivery failure reported by a mail server
+#[derive(Debug, Clone)]
+pub struct Bounce {
+    /// message id, in reply to and references of the bounced message
+    pub message_ids: Vec<String>,
+    /// failed recipients
+    pub recipients: Vec<String>,
+    /// enhanced status code like `5.1.1`
+    pub status: Option<String>,
+    pub reason: String,
+}
+
+/// subjects used by mail servers which do not send rfc 3464 reports
+const SUBJECTS: &[&str] = &[
+    "undeliverable",
+    "undelivered mail",
+    "delivery status notification (failure)",
+    "delivery failure",
+    "mail delivery failed",
+    "failure notice",
+    "returned mail",
+    "unzustellbar",
+    "nicht zustellbar",
+];
+
+/// parse `message` as bounce, `None` if it is no permanent failure
+pub fn parse(message: &Part) -> Option<Bounce> {
+    if message.content_type() == "multipart/report" {
+        let report_type = message
+            .param("Content-Type", "report-type")
+            .unwrap_or_default()
+            .to_lowercase();
+        if report_type == "delivery-status" || report_type == "global-delivery-status" {
+            return parse_dsn(message);
+        }
+    }
+    parse_heuristic(message)
+}
+
+/// rfc 3464 delivery status notification
+fn parse_dsn(message: &Part) -> Option<Bounce> {
+    let status = message.walk().into_iter().find(|v| {
+        let kind = v.content_type();
+        kind == "message/delivery-status" || kind == "message/global-delivery-status"
+    })?;
+    let status = status.body_text().replace("\r\n", "\n");
+
+    let mut bounce = Bounce {
+        message_ids: original_ids(message),
+        recipients: Vec::new(),
+        status: None,
+        reason: String::new(),
+    };
+    // the first group describes the message, every further group one recipient
+    for group in status.split("\n\n").skip(1) {
+        let fields = mime::parse_headers(group);
+        let field = |name: &str| {
+            fields
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(name))
+                .map(|(_, v)| v.trim().to_string())
+        };
+        let code = field("Status").unwrap_or_default();
+        let failed = field("Action")
+            .map(|v| v.eq_ignore_ascii_case("failed"))
+            .unwrap_or_else(|| code.starts_with('5'));
+        if !failed {
+            continue;
+        }
+        if let Some(recipient) = field("Final-Recipient").or_else(|| field("Original-Recipient")) {
+            // strip the address type like `rfc822;`
+            let recipient = recipient.rsplit(';').next().unwrap_or_default();
+            bounce.recipients.push(mime::address(recipient));
+        }
+        if bounce.status.is_none() && !code.is_empty() {
+            bounce.status = Some(code.clone());
+        }
+        if bounce.reason.is_empty() {
+            bounce.reason = field("Diagnostic-Code").unwrap_or(code);
+        }
+    }
+
+    if bounce.recipients.is_empty() && bounce.status.is_none() {
+        return None;
+    }
+    if bounce.reason.is_empty() {
+        bounce.reason = String::from("delivery failed");
+    }
+    Some(bounce)
+}
+
+/// bounces of servers which do not send delivery status notifications
+fn parse_heuristic(message: &Part) -> Option<Bounce> {
+    let from = message
+        .header("From")
+        .map(mime::address)
+        .unwrap_or_default();
+    let subject = message
+        .decoded("Subject")
+        .unwrap_or_default()
+        .to_lowercase();
+    let daemon = from.starts_with("mailer-daemon@") || from.starts_with("postmaster@");
+    if !daemon && !SUBJECTS.iter().any(|v| subject.contains(v)) {
+        return None;
+    }
+    // temporary problems are retried by the server
+    if subject.contains("delay") || subject.contains("warning") || subject.contains("verzögert") {
+        return None;
+    }
+
+    let text: String = message
+        .walk()
+        .into_iter()
+        .filter(|v| v.content_type().starts_with("text/"))
+        .map(|v| v.body_text())
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    let mut message_ids = original_ids(message);
+    for line in text.lines() {
+        let line = line.trim();
+        if line
+            .get(..11)
+            .is_some_and(|v| v.eq_ignore_ascii_case("message-id:"))
+        {
+            message_ids.extend(mime::message_ids(line));
+        }
+    }
+
+    let status = text
+        .split(|v: char| !(v.is_ascii_digit() || v == '.'))
+        .find(|v| is_status(v))
+        .map(|v| v.to_string());
+    let recipients: Vec<String> = text
+        .split(|v: char| v.is_whitespace() || "<>()[];,:\"'".contains(v))
+        .filter(|v| v.contains('@') && !v.starts_with('@') && !v.ends_with('@'))
+        .map(|v| v.trim_matches('.').to_lowercase())
+        .filter(|v| *v != from && !message_ids.iter().any(|id| id.eq_ignore_ascii_case(v)))
+        .collect();
+
+    let reason = text
+        .lines()
+        .map(|v| v.trim())
+        .find(|v| v.starts_with("5") || v.to_lowercase().contains("user unknown"))
+        .unwrap_or("delivery failed")
+        .to_string();
+
+    Some(Bounce {
+        message_ids,
+        recipients,
+        status,
+        reason,
+    })
+}
+
+/// message ids of the returned original message or its headers
+fn original_ids(message: &Part) -> Vec<String> {
+    let mut ids = Vec::new();
+    for part in message.walk().into_iter().skip(1) {
+        let headers = match part.content_type().as_str() {
+            "text/rfc822-headers" | "message/rfc822-headers" => {
+                mime::parse_headers(&part.body_text())
+            }
+            _ if part.header("Message-ID").is_some() => part.headers.clone(),
+            _ => continue,
+        };
+        for (k, v) in headers.iter() {
+            if k.eq_ignore_ascii_case("Message-ID")
+                || k.eq_ignore_ascii_case("In-Reply-To")
+                || k.eq_ignore_ascii_case("References")
+            {
+                ids.extend(mime::message_ids(v));
+            }
+        }
+    }
+    ids
+}
+
+/// permanent enhanced status code like `5.1.1`
+fn is_status(value: &str) -> bool {
+    let parts: Vec<&str> = value.split('.').collect();
+    parts.len() == 3
+        && parts[0] == "5"
+        && parts[1..]
+            .iter()
+            .all(|v| !v.is_empty() && v.len() <= 3 && v.chars().all(|v| v.is_ascii_digit()))
+}
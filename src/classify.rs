@@ -0,0 +1,165 @@
+use super::mime::Part;
+use regex::Regex;
+
+/// what a company answered to a request
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    /// the data was sent or made available
+    Provided,
+    /// the company asks for proof of identity
+    Verification,
+    /// the company stores no data about us
+    NoData,
+    /// the company points to a self service portal instead of answering
+    Portal,
+    /// the deadline was extended under Art. 12(3)
+    Extension,
+    /// the company refuses to answer
+    Refused,
+}
+
+/// what to do with a company after an outcome
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    /// the request is fulfilled, no more reminders
+    Done,
+    /// stop the clock until the next answer or until the request is resumed
+    Pause,
+    /// keep sending reminders
+    Remind,
+    /// stop reminding, a complaint is the next step
+    Escalate,
+}
+
+/// all outcomes, in the order they are tested
+pub const OUTCOMES: &[Outcome] = &[
+    Outcome::Verification,
+    Outcome::Extension,
+    Outcome::Refused,
+    Outcome::NoData,
+    Outcome::Portal,
+    Outcome::Provided,
+];
+
+impl Outcome {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.to_lowercase().as_str() {
+            "provided" => Ok(Outcome::Provided),
+            "verification" => Ok(Outcome::Verification),
+            "no-data" => Ok(Outcome::NoData),
+            "portal" => Ok(Outcome::Portal),
+            "extension" => Ok(Outcome::Extension),
+            "refused" => Ok(Outcome::Refused),
+            _ => Err(format!("unknown outcome '{}'", value)),
+        }
+    }
+
+    pub fn action(self) -> Action {
+        match self {
+            Outcome::Provided | Outcome::NoData => Action::Done,
+            Outcome::Verification => Action::Pause,
+            Outcome::Portal | Outcome::Extension => Action::Remind,
+            Outcome::Refused => Action::Escalate,
+        }
+    }
+
+    /// default rules, matched against subject and text
+    fn patterns(self) -> &'static [&'static str] {
+        match self {
+            Outcome::Provided => &[
+                r"(?i)\b(anbei|beigefügt|im anhang|anhängend)\b",
+                r"(?i)\b(attached|enclosed|please find)\b",
+            ],
+            Outcome::Verification => &[
+                r"(?i)identität|identifizierung|ausweis|personalausweis",
+                r"(?i)verify your identity|proof of identity|identification document",
+            ],
+            Outcome::NoData => &[
+                r"(?i)keine (personenbezogenen )?daten (zu ihrer person )?(gespeichert|vorliegen|verarbeitet)",
+                r"(?i)liegen (uns )?keine (personenbezogenen )?daten",
+                r"(?i)(do not|don't) (hold|store|have) any (personal )?(data|information)",
+                r"(?i)no (personal )?data (is |was )?(stored|held|found)",
+            ],
+            Outcome::Portal => &[
+                r"(?i)self-?service|datenschutz-?center|privacy (center|portal)",
+                r"(?i)\bportal\b|download (your|ihre) (data|daten)",
+            ],
+            Outcome::Extension => &[
+                r"(?i)frist (um .* )?verlänger|verlängerung der frist",
+                r"(?i)extend(ed)? the (deadline|period|time limit)",
+            ],
+            Outcome::Refused => &[
+                r"(?i)offenkundig unbegründet|exzessiv|lehnen .* ab|ablehnen",
+                r"(?i)manifestly unfounded|excessive|(refuse|decline) (to|your request)",
+            ],
+        }
+    }
+}
+
+impl std::fmt::Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Outcome::Provided => write!(f, "provided"),
+            Outcome::Verification => write!(f, "verification"),
+            Outcome::NoData => write!(f, "no-data"),
+            Outcome::Portal => write!(f, "portal"),
+            Outcome::Extension => write!(f, "extension"),
+            Outcome::Refused => write!(f, "refused"),
+        }
+    }
+}
+
+/// keyword rules mapping replies to outcomes
+#[derive(Debug, Clone)]
+pub struct Classifier {
+    /// tested in order, the first match wins
+    pub rules: Vec<(Outcome, Regex)>,
+}
+
+impl Classifier {
+    pub fn new() -> Self {
+        let mut rules = Vec::new();
+        for outcome in OUTCOMES.iter() {
+            for pattern in outcome.patterns() {
+                rules.push((*outcome, Regex::new(pattern).unwrap()));
+            }
+        }
+        Self { rules }
+    }
+
+    /// outcome of the reply `message`, `None` if no rule matches
+    pub fn classify(&self, message: &Part) -> Option<Outcome> {
+        let subject = message.decoded("Subject").unwrap_or_default();
+        let text = own_text(&message.text());
+        let rule = self
+            .rules
+            .iter()
+            .find(|(_, v)| v.is_match(&subject) || v.is_match(&text));
+        if let Some((outcome, _)) = rule {
+            return Some(*outcome);
+        }
+        // an answer with attachments most likely contains the data
+        if message.leaves().iter().any(|v| v.is_attachment()) {
+            return Some(Outcome::Provided);
+        }
+        None
+    }
+}
+
+impl Default for Classifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// `text` without the quoted request, which would match some rules itself
+fn own_text(text: &str) -> String {
+    text.lines()
+        .take_while(|v| {
+            let v = v.trim_start();
+            !v.starts_with("-----") && !v.starts_with("Von:") && !v.starts_with("From:")
+        })
+        .filter(|v| !v.trim_start().starts_with('>'))
+        .collect::<Vec<&str>>()
+        .join("\n")
+}
@@ -0,0 +1,161 @@
+use super::{mail, slug, Company, Config};
+use chrono::prelude::*;
+use std::fs;
+
+/// drafts of complaints to the supervisory authority about requests ignored after all reminders
+#[derive(Debug, Clone)]
+pub struct Complaint {
+    /// send the complaint to the authority instead of mailing the draft to the operator
+    pub send: bool,
+    /// address of the supervisory authority of companies which do not set their own
+    pub authority: Option<String>,
+    /// operator reviewing the drafts, defaults to the first allowed control sender
+    pub to: Option<String>,
+    /// sender of the drafts, defaults to the control address
+    pub from: Option<String>,
+}
+
+impl Complaint {
+    pub fn new() -> Self {
+        Self {
+            send: false,
+            authority: None,
+            to: None,
+            from: None,
+        }
+    }
+}
+
+impl Default for Complaint {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Config {
+    /// draft complaints about open requests which are unanswered after the last reminder
+    pub(crate) fn file_complaints(&mut self, now: DateTime<Utc>) {
+        for i in 0..self.companies.len() {
+            let v: &Company = &self.companies[i];
+            let due = self.scheduler.escalation_at(v).is_some_and(|v| v <= now);
+            if !due || v.complained.is_some() {
+                continue;
+            }
+            let name = v.name.clone();
+            match self.complain(i, now) {
+                Ok(result) => info!("{}", result),
+                Err(err) => error!("could not file complaint about {}: {}", name, err),
+            }
+        }
+    }
+
+    /// draft the complaint about the open request to the company at `index`, store it in
+    /// the archive and mail it to the operator or the authority
+    pub(crate) fn complain(&mut self, index: usize, now: DateTime<Utc>) -> Result<String, String> {
+        let v: &Company = &self.companies[index];
+        let language = v.language.unwrap_or(self.language);
+        let since = v
+            .last_hit
+            .ok_or_else(|| format!("no request was sent to {}", v.name))?;
+        let timeline: Vec<String> = v
+            .history
+            .iter()
+            .map(|(date, event)| format!(" {}  {}", date.format("%Y-%m-%d"), event))
+            .collect();
+        let text = language.complaint(v, &timeline.join("\n"));
+        let subject = language.complaint_subject(v);
+
+        let mut attachments = Vec::new();
+        for files in [
+            self.archive.letters(v, since),
+            self.archive.replies(v, since),
+        ] {
+            match files {
+                Ok(files) => attachments.extend(files),
+                Err(err) => warn!("could not read the archive of {}: {}", v.name, err),
+            }
+        }
+
+        let authority = v
+            .authority
+            .clone()
+            .or_else(|| self.complaint.authority.clone());
+        let (from, sender, to, text) = match authority {
+            Some(authority) if self.complaint.send => {
+                let from = format!("{} <{}>", mail::encode_word(&v.onw_name), v.alias);
+                (from, v.alias.clone(), Some(authority), text)
+            }
+            authority => {
+                let sender = self.complaint.from.clone().or_else(|| self.own_address());
+                let to = self
+                    .complaint
+                    .to
+                    .clone()
+                    .or_else(|| self.control_auth.senders.first().cloned());
+                let note = format!(
+                    "Draft of a complaint to {}, please review and send it yourself.\n\n{}",
+                    authority.as_deref().unwrap_or("the supervisory authority"),
+                    text
+                );
+                let sender = sender.unwrap_or_default();
+                (format!("datenbriefd <{}>", sender), sender, to, note)
+            }
+        };
+        let to = to.filter(|_| !sender.is_empty());
+        let message = mail::complaint(
+            &from,
+            to.as_deref().unwrap_or_default(),
+            &subject,
+            &text,
+            &attachments,
+            now,
+        )
+        .render();
+
+        let reference = slug(v.reference.as_deref().unwrap_or_default());
+        let file = self
+            .archive
+            .company_dir(v)
+            .join(format!("complaint-{}.eml", reference));
+        fs::create_dir_all(self.archive.company_dir(v))
+            .and_then(|_| fs::write(&file, &message))
+            .map_err(|err| format!("could not write {}: {}", file.display(), err))?;
+
+        let result = match to {
+            Some(to) => self
+                .transport
+                .connect(&self.Smtp)
+                .and_then(|mut client| {
+                    client.send(&sender, &[&to], message.as_bytes())?;
+                    client.close()
+                })
+                .map(|_| {
+                    if self.complaint.send {
+                        format!("sent complaint about {} to {}", v.name, to)
+                    } else {
+                        format!("mailed complaint draft about {} to {}", v.name, to)
+                    }
+                })
+                .map_err(|err| {
+                    format!(
+                        "could not mail complaint about {} to {}: {}, stored the draft in {}",
+                        v.name,
+                        to,
+                        err,
+                        file.display()
+                    )
+                }),
+            None => Ok(format!(
+                "stored complaint draft about {} in {}",
+                v.name,
+                file.display()
+            )),
+        };
+        // not retried on failure, the operator finds the draft in the archive
+        let v: &mut Company = &mut self.companies[index];
+        v.complained = Some(now);
+        v.record(now, result.clone().unwrap_or_else(|err| err));
+        self.save_time();
+        result
+    }
+}
@@ -0,0 +1,154 @@
+use super::{Company, Config};
+use chrono::prelude::*;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// directory next to the config `file` with additional config files
+pub fn conf_dir(file: &Path) -> PathBuf {
+    file.parent()
+        .unwrap_or_else(|| Path::new(""))
+        .join("conf.d")
+}
+
+/// merge the companies of all `*.toml` files in `dir` into `config`, field by field
+///
+/// Files which can not be read are skipped with a warning.
+pub fn merge_conf_dir(config: &mut toml::Value, dir: &Path) -> Result<(), String> {
+    let mut files: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|v| v.ok().map(|v| v.path()))
+            .filter(|v| v.extension().is_some_and(|v| v == "toml"))
+            .collect(),
+        Err(_) => return Ok(()),
+    };
+    files.sort();
+    for file in files {
+        let value: Result<toml::Value, String> = fs::read_to_string(&file)
+            .map_err(|v| v.to_string())
+            .and_then(|v| toml::from_str(&v).map_err(|v| v.to_string()));
+        let value = match value {
+            Ok(value) => value,
+            Err(err) => {
+                warn!("ignoring {}: {}", file.display(), err);
+                continue;
+            }
+        };
+        debug!("read {} as config", file.display());
+        let companies = match value.get("companies").and_then(|v| v.as_table()) {
+            Some(companies) => companies,
+            None => continue,
+        };
+        let stored = config
+            .as_table_mut()
+            .ok_or_else(|| String::from("config is not a table"))?
+            .entry("companies")
+            .or_insert_with(|| toml::Value::Table(Default::default()));
+        for (name, fields) in companies.iter() {
+            let entry = stored.as_table_mut().map(|v| {
+                v.entry(name.as_str())
+                    .or_insert_with(|| toml::Value::Table(Default::default()))
+            });
+            if let (Some(toml::Value::Table(entry)), Some(fields)) = (entry, fields.as_table()) {
+                entry.extend(fields.clone());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// companies of the `[companies]` section of `config`, `interval` is used where none is set
+///
+/// Unlike control mails a config file is not rejected as a whole, invalid fields and
+/// companies are skipped with a warning.
+pub fn companies(config: &toml::Value, interval: Option<usize>) -> Vec<Company> {
+    let mut companies = Vec::new();
+    let table = match config.get("companies").and_then(|v| v.as_table()) {
+        Some(table) => table,
+        None => return companies,
+    };
+    for (k, v) in table.iter() {
+        let fields = match v.as_table() {
+            Some(fields) => fields,
+            None => {
+                warn!("ignoring company {}, it is not a table", k);
+                continue;
+            }
+        };
+        let mut company = Company::new();
+        company.name = k.to_string();
+        trace!("set companie name to '{}'", k);
+        if let Some(interval) = interval {
+            trace!("set interval to global default ({})", interval);
+            company.interval = interval;
+        }
+        for (key, value) in fields.iter() {
+            let mut field = toml::value::Table::new();
+            field.insert(key.clone(), value.clone());
+            if let Err(err) = company.apply(&toml::Value::Table(field)) {
+                warn!("{}, ignoring it", err);
+            }
+        }
+        companies.push(company);
+    }
+    companies
+}
+
+impl Config {
+    /// read the companies from the config files again, keeping the state of known ones
+    ///
+    /// A missing config file only leaves conf.d, a broken one keeps all companies as they are.
+    pub fn reload(&mut self, now: DateTime<Utc>) -> Result<String, String> {
+        let file = self
+            .config_file
+            .clone()
+            .ok_or_else(|| String::from("no config file loaded"))?;
+        let mut config: toml::Value = match fs::read_to_string(&file) {
+            Ok(text) => toml::from_str(&text).map_err(|v| v.to_string()),
+            Err(ref err) if err.kind() == std::io::ErrorKind::NotFound => {
+                debug!("{} does not exist, only reading conf.d", file);
+                Ok(toml::Value::Table(Default::default()))
+            }
+            Err(err) => Err(err.to_string()),
+        }
+        .map_err(|err| format!("could not read {}: {}", file, err))?;
+        merge_conf_dir(&mut config, &conf_dir(Path::new(&file)))?;
+        let mut companies = self.command_line.clone();
+        companies.extend(self::companies(&config, self.interval));
+
+        let (mut added, mut removed) = (0, self.companies.len());
+        let mut known = Vec::new();
+        for new in companies {
+            let mut v = match self.companies.iter().find(|v| v.name == new.name) {
+                Some(old) => old.clone(),
+                None => {
+                    added += 1;
+                    known.push(new);
+                    continue;
+                }
+            };
+            removed -= 1;
+            if v.invalid.as_ref().is_some_and(|v| v.0 != new.mail) {
+                info!("address of {} changed, sending requests again", v.name);
+                v.invalid = None;
+            }
+            v.mail = new.mail;
+            v.alias = new.alias;
+            v.onw_name = new.onw_name;
+            v.interval = new.interval;
+            v.language = new.language;
+            v.authority = new.authority;
+            known.push(v);
+        }
+        self.companies = known;
+        self.scheduler.stagger(&mut self.companies, now);
+        self.save_time();
+        Ok(format!(
+            "loaded {} companies, {} added, {} removed",
+            self.companies.len(),
+            added,
+            removed
+        ))
+    }
+}
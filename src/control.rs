@@ -0,0 +1,448 @@
+use super::{
+    auth::{token_value, Proof},
+    classify::Outcome,
+    imap, mail,
+    mime::{self, Part},
+    right::Right,
+    watch, Company, Config,
+};
+use chrono::prelude::*;
+use std::{fs, io, path::Path};
+use toml::value::Table;
+
+/// command received on the control mailbox
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Status,
+    /// send a request right now
+    Send(String),
+    Pause(String),
+    Resume(String),
+    /// override the outcome of the last answer of a company
+    Classify(String, Outcome),
+    /// send a request for another right once, with details from the mail body
+    Request(String, Right, String),
+    /// add companies or change their fields, by name
+    Company(Table),
+}
+
+impl Command {
+    /// parse a command line like `send acme`
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or_default().to_lowercase();
+        let company = words.collect::<Vec<&str>>().join(" ");
+        let company = || {
+            if company.is_empty() {
+                Err(format!("{} needs a company", command))
+            } else {
+                Ok(company.clone())
+            }
+        };
+        match command.as_str() {
+            "status" => Ok(Command::Status),
+            "send" | "send-now" => Ok(Command::Send(company()?)),
+            "pause" => Ok(Command::Pause(company()?)),
+            "resume" => Ok(Command::Resume(company()?)),
+            "classify" => {
+                let company = company()?;
+                // the outcome is the last word, company names may contain spaces
+                let (name, outcome) = company
+                    .rsplit_once(' ')
+                    .ok_or_else(|| String::from("classify needs a company and an outcome"))?;
+                Ok(Command::Classify(
+                    name.to_string(),
+                    Outcome::parse(outcome)?,
+                ))
+            }
+            "request" => {
+                let company = company()?;
+                let (name, right) = company
+                    .rsplit_once(' ')
+                    .ok_or_else(|| String::from("request needs a company and a type"))?;
+                Ok(Command::Request(
+                    name.to_string(),
+                    Right::parse(right)?,
+                    String::new(),
+                ))
+            }
+            "company" => Err(String::from("company needs a toml snippet in the body")),
+            "" => Err(String::from("empty command")),
+            _ => Err(format!("unknown command '{}'", command)),
+        }
+    }
+
+    /// command of a control mail, taken from the subject or the first line of the body
+    pub fn from_message(message: &Part) -> Result<Self, String> {
+        let subject = message.decoded("Subject").unwrap_or_default();
+        let subject = subject.trim();
+        if !subject.is_empty() {
+            return Self::with_body(subject, &message.text());
+        }
+        Self::from_text(&message.text())
+    }
+
+    /// command in the first line of `text`, skipping the token
+    pub fn from_text(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines();
+        let line = lines
+            .by_ref()
+            .map(|v| v.trim())
+            .find(|v| !v.is_empty() && token_value(v).is_none())
+            .unwrap_or_default();
+        Self::with_body(line, &lines.collect::<Vec<&str>>().join("\n"))
+    }
+
+    /// parse the command `line`, `body` holds a toml snippet or the details of a request
+    fn with_body(line: &str, body: &str) -> Result<Self, String> {
+        let word = line.split_whitespace().next().unwrap_or_default();
+        // neither the token nor the mail signature belong to the body
+        let body = body
+            .lines()
+            .take_while(|v| *v != "-- ")
+            .filter(|v| token_value(v).is_none())
+            .collect::<Vec<&str>>()
+            .join("\n");
+        if word.eq_ignore_ascii_case("company") {
+            return companies(line[word.len()..].trim(), &body).map(Command::Company);
+        }
+        match Self::parse(line)? {
+            Command::Request(name, right, _) => {
+                Ok(Command::Request(name, right, body.trim().to_string()))
+            }
+            command => Ok(command),
+        }
+    }
+}
+
+/// the command as it would be written in a control mail, without the body
+impl std::fmt::Display for Command {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Command::Status => write!(f, "status"),
+            Command::Send(name) => write!(f, "send {}", name),
+            Command::Pause(name) => write!(f, "pause {}", name),
+            Command::Resume(name) => write!(f, "resume {}", name),
+            Command::Classify(name, outcome) => write!(f, "classify {} {}", name, outcome),
+            Command::Request(name, right, _) => write!(f, "request {} {}", name, right),
+            Command::Company(companies) => {
+                let names: Vec<&str> = companies.keys().map(|v| v.as_str()).collect();
+                write!(f, "company {}", names.join(", "))
+            }
+        }
+    }
+}
+
+/// companies in a toml snippet, either `[companies.<name>]` sections or the fields of `name`
+fn companies(name: &str, snippet: &str) -> Result<Table, String> {
+    let value: toml::Value =
+        toml::from_str(snippet).map_err(|err| format!("invalid toml snippet: {}", err))?;
+    let mut table = match value {
+        toml::Value::Table(table) => table,
+        _ => return Err(String::from("toml snippet is not a table")),
+    };
+    let companies = match table.remove("companies") {
+        Some(toml::Value::Table(companies)) if name.is_empty() && table.is_empty() => companies,
+        Some(_) => return Err(String::from("expected only [companies.<name>] sections")),
+        None if name.is_empty() => return Err(String::from("company needs a name")),
+        None => {
+            let mut companies = Table::new();
+            companies.insert(name.to_string(), toml::Value::Table(table));
+            companies
+        }
+    };
+    if companies.is_empty() {
+        return Err(String::from("toml snippet has no companies"));
+    }
+    Ok(companies)
+}
+
+impl Config {
+    /// fetch unseen mails from `ImapControl` and run the commands in them
+    pub(crate) fn watch_control(&mut self) {
+        if self.ImapControl.host.is_empty() {
+            return;
+        }
+        let mut client = match imap::Client::connect(&self.ImapControl) {
+            Ok(client) => client,
+            Err(err) => {
+                warn!(
+                    "could not connect to imap control server {}: {}",
+                    self.ImapControl.host, err
+                );
+                return;
+            }
+        };
+        if let Err(err) = self.fetch_control(&mut client) {
+            warn!("could not fetch control mails: {}", err);
+        }
+        if let Err(err) = client.logout() {
+            debug!("error closing imap connection: {}", err);
+        }
+    }
+
+    fn fetch_control(&mut self, client: &mut imap::Client) -> std::io::Result<()> {
+        client.select("INBOX")?;
+        for uid in client.search("UNSEEN")? {
+            for (uid, raw) in client.fetch(&uid.to_string())? {
+                self.control_mail(&raw, Utc::now());
+                client.add_flags(&uid.to_string(), "\\Seen")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// authenticate and run the command in the raw control mail `raw`
+    pub(crate) fn control_mail(&mut self, raw: &[u8], now: DateTime<Utc>) {
+        let message = Part::parse(raw);
+        let from = message
+            .header("From")
+            .map(mime::address)
+            .unwrap_or_default();
+        let id = message.header("Message-ID").unwrap_or("-").to_string();
+        // a mail with a valid token or signature may be sent again by anyone who intercepted it,
+        // only the signature id survives changing the headers
+        let check = self.control_auth.check(&message, now).and_then(|proof| {
+            let key = match &proof {
+                Proof::Signature { id, .. } => id.clone(),
+                Proof::Token => watch::message_id(raw),
+            };
+            if self.is_processed(&key) {
+                Err(String::from("mail was already handled"))
+            } else {
+                Ok((proof, key))
+            }
+        });
+        let (command, key) = match check {
+            // the subject is not covered by the signature
+            Ok((Proof::Signature { text, .. }, key)) => (Command::from_text(&text), key),
+            Ok((Proof::Token, key)) => (Command::from_message(&message), key),
+            Err(err) => {
+                warn!("rejected control mail from {}: {}", from, err);
+                self.control_auth
+                    .audit(now, &format!("rejected {} from {}: {}", id, from, err));
+                return;
+            }
+        };
+        self.remember(key);
+        self.save_time();
+        let command = match command {
+            Ok(command) => command,
+            Err(err) => {
+                warn!("invalid control mail: {}", err);
+                self.control_auth
+                    .audit(now, &format!("invalid {} from {}: {}", id, from, err));
+                self.reply_control(&message, &format!("failed: {}\n", err), now);
+                return;
+            }
+        };
+        let result = self.execute(&command, now);
+        match &result {
+            Ok(result) => info!("control command {}: {}", command, result),
+            Err(err) => warn!("control command {} failed: {}", command, err),
+        }
+        self.control_auth.audit(
+            now,
+            &format!(
+                "accepted {} from {}: {} {}",
+                id,
+                from,
+                command,
+                if result.is_ok() { "ok" } else { "failed" }
+            ),
+        );
+        let text = match result {
+            Ok(result) => format!("ok: {}\n\n{}\n", command, result),
+            Err(err) => format!("failed: {}\n\n{}\n", command, err),
+        };
+        self.reply_control(&message, &text, now);
+    }
+
+    /// mail `text` back to the sender of the control mail `command`
+    fn reply_control(&mut self, command: &Part, text: &str, now: DateTime<Utc>) {
+        let from = match self.own_address() {
+            Some(from) => from,
+            None => {
+                warn!("no control address configured, not answering control mail");
+                return;
+            }
+        };
+        let to = command
+            .header("From")
+            .map(mime::address)
+            .unwrap_or_default();
+        let message = mail::control_reply(&from, command, text, now).render();
+        let result = self.transport.connect(&self.Smtp).and_then(|mut client| {
+            client.send(&from, &[&to], message.as_bytes())?;
+            client.close()
+        });
+        match result {
+            Ok(()) => debug!("answered control mail of {}", to),
+            Err(err) => error!("could not answer control mail of {}: {}", to, err),
+        }
+    }
+
+    /// run `command`, returns a description of the result
+    pub fn execute(&mut self, command: &Command, now: DateTime<Utc>) -> Result<String, String> {
+        match command {
+            Command::Status => Ok(self.status()),
+            Command::Send(name) => {
+                let i = self.company_index(name)?;
+                self.hit(i, now, false);
+                Ok(format!("queued request to {}", self.companies[i].name))
+            }
+            Command::Pause(name) => {
+                let i = self.company_index(name)?;
+                self.companies[i].paused = true;
+                self.companies[i].record(now, String::from("paused"));
+                self.save_time();
+                Ok(format!("paused {}", self.companies[i].name))
+            }
+            Command::Resume(name) => {
+                let i = self.company_index(name)?;
+                self.companies[i].paused = false;
+                self.companies[i].record(now, String::from("resumed"));
+                self.companies[i].resume_clock(now);
+                self.save_time();
+                Ok(format!("resumed {}", self.companies[i].name))
+            }
+            Command::Classify(name, outcome) => {
+                let i = self.company_index(name)?;
+                self.set_outcome(i, *outcome, now);
+                Ok(format!(
+                    "classified {} as {}",
+                    self.companies[i].name, outcome
+                ))
+            }
+            Command::Request(name, right, details) => {
+                let i = self.company_index(name)?;
+                self.companies[i].request_once(*right, details)?;
+                self.companies[i].record(now, format!("queued {} request", right));
+                self.save_time();
+                if self.companies[i].is_open() {
+                    return Ok(format!(
+                        "queued {} request to {} until the open request is answered",
+                        right, self.companies[i].name
+                    ));
+                }
+                self.hit(i, now, false);
+                Ok(format!(
+                    "queued {} request to {}",
+                    right, self.companies[i].name
+                ))
+            }
+            Command::Company(companies) => self.change_companies(companies, now),
+        }
+    }
+
+    /// add or change `companies` and store them in `company_file`
+    ///
+    /// Nothing is changed unless all companies are valid. Changed companies keep their
+    /// schedule, new ones are scheduled like companies added to the config file.
+    fn change_companies(
+        &mut self,
+        companies: &Table,
+        now: DateTime<Utc>,
+    ) -> Result<String, String> {
+        let mut changed: Vec<(Option<usize>, Company)> = Vec::new();
+        for (name, fields) in companies.iter() {
+            let index = self.company_index(name).ok();
+            let mut company = match index {
+                Some(i) => self.companies[i].clone(),
+                None => {
+                    let mut company = Company::new();
+                    company.name = name.to_string();
+                    if let Some(interval) = self.interval {
+                        company.interval = interval;
+                    }
+                    company
+                }
+            };
+            company.apply(fields)?;
+            if company.mail.is_empty() {
+                return Err(format!("{} needs a mail address", company.name));
+            }
+            changed.push((index, company));
+        }
+
+        let mut result = String::new();
+        match &self.company_file {
+            Some(file) => {
+                // the config file knows the companies by the name they were added with
+                let mut stored = Table::new();
+                for ((_, company), fields) in changed.iter().zip(companies.values()) {
+                    stored.insert(company.name.clone(), fields.clone());
+                }
+                store_companies(Path::new(file), &stored)
+                    .map_err(|err| format!("could not write {}: {}", file, err))?;
+            }
+            None => result.push_str("no company file configured, changes are lost on restart\n"),
+        }
+        for (index, company) in changed {
+            match index {
+                Some(i) => {
+                    result.push_str(&format!("changed {}\n", company.name));
+                    self.companies[i] = company;
+                }
+                None => {
+                    result.push_str(&format!("added {}\n", company.name));
+                    self.companies.push(company);
+                }
+            }
+        }
+        self.scheduler.stagger(&mut self.companies, now);
+        self.save_time();
+        Ok(result.trim_end().to_string())
+    }
+
+    /// address mails to the operator are sent from, the control address or the `ImapControl` user
+    pub(crate) fn own_address(&self) -> Option<String> {
+        match &self.control_address {
+            Some(from) => Some(from.clone()),
+            None if self.ImapControl.user.contains('@') => Some(self.ImapControl.user.clone()),
+            None => None,
+        }
+    }
+
+    /// index of the company called `name`, ignoring case
+    pub fn company_index(&self, name: &str) -> Result<usize, String> {
+        self.companies
+            .iter()
+            .position(|v: &Company| v.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| format!("unknown company '{}'", name))
+    }
+}
+
+/// merge the fields of `companies` into the `[companies]` of the toml file `file`
+fn store_companies(file: &Path, companies: &Table) -> io::Result<()> {
+    let mut config: Table = match fs::read_to_string(file) {
+        Ok(data) => toml::from_str(&data).map_err(io::Error::other)?,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Table::new(),
+        Err(err) => return Err(err),
+    };
+    let stored = config
+        .entry("companies")
+        .or_insert_with(|| toml::Value::Table(Table::new()))
+        .as_table_mut()
+        .ok_or_else(|| io::Error::other("companies is not a table"))?;
+    for (name, fields) in companies.iter() {
+        let entry = stored
+            .entry(name.as_str())
+            .or_insert_with(|| toml::Value::Table(Table::new()));
+        if let (Some(entry), Some(fields)) = (entry.as_table_mut(), fields.as_table()) {
+            entry.extend(fields.clone());
+        }
+    }
+
+    let data = toml::to_string(&config).map_err(io::Error::other)?;
+    if let Some(dir) = file.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let tmp = file.with_extension("tmp");
+    let data = format!(
+        "# managed by datenbriefd, changed by control mails\n\n{}",
+        data
+    );
+    fs::write(&tmp, data)?;
+    fs::rename(&tmp, file)
+}
@@ -0,0 +1,223 @@
+use super::{mail, schedule, Company, Config};
+use chrono::{prelude::*, Duration};
+use std::fmt::Write;
+
+/// how often the digest is sent
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Period {
+    Weekly,
+    Monthly,
+}
+
+impl Period {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.to_lowercase().as_str() {
+            "weekly" | "week" => Ok(Period::Weekly),
+            "monthly" | "month" => Ok(Period::Monthly),
+            _ => Err(format!("unknown digest period '{}'", value)),
+        }
+    }
+
+    /// end of the period starting at `date`
+    pub fn after(self, date: DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            Period::Weekly => date + Duration::weeks(1),
+            Period::Monthly => schedule::add_months(date, 1),
+        }
+    }
+}
+
+/// summary of the past and the coming period mailed to the operator
+#[derive(Debug, Clone)]
+pub struct Digest {
+    /// no digest is sent if unset
+    pub period: Option<Period>,
+    /// defaults to the first allowed control sender
+    pub to: Option<String>,
+    /// defaults to the control address
+    pub from: Option<String>,
+    /// when the last digest was sent
+    pub(crate) last: Option<DateTime<Utc>>,
+}
+
+impl Digest {
+    pub fn new() -> Self {
+        Self {
+            period: None,
+            to: None,
+            from: None,
+            last: None,
+        }
+    }
+}
+
+impl Default for Digest {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Config {
+    /// send the digest if a period passed since the last one
+    pub(crate) fn send_digest(&mut self, now: DateTime<Utc>) {
+        let period = match self.digest.period {
+            Some(period) => period,
+            None => return,
+        };
+        let last = match self.digest.last {
+            Some(last) => last,
+            None => {
+                // the first digest covers a full period
+                self.digest.last = Some(now);
+                self.save_time();
+                return;
+            }
+        };
+        if period.after(last) > now {
+            return;
+        }
+
+        let to = self
+            .digest
+            .to
+            .clone()
+            .or_else(|| self.control_auth.senders.first().cloned());
+        let from = self.digest.from.clone().or_else(|| self.own_address());
+        let (from, to) = match (from, to) {
+            (Some(from), Some(to)) => (from, to),
+            _ => {
+                warn!("digest needs a sender and a recipient, not sending it");
+                return;
+            }
+        };
+        let text = self.digest_text(last, now, period.after(now));
+        let message = mail::digest(&from, &to, last, now, &text).render();
+        let result = self.transport.connect(&self.Smtp).and_then(|mut client| {
+            client.send(&from, &[&to], message.as_bytes())?;
+            client.close()
+        });
+        match result {
+            Ok(()) => {
+                info!("sent digest to {}", to);
+                self.digest.last = Some(now);
+                self.save_time();
+            }
+            Err(err) => error!("could not send digest to {}: {}", to, err),
+        }
+    }
+
+    /// text of the digest covering `since` to `now` and the sends until `until`
+    pub fn digest_text(
+        &self,
+        since: DateTime<Utc>,
+        now: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> String {
+        let date = |v: DateTime<Utc>| v.format("%Y-%m-%d").to_string();
+        let mut out = format!("datenbriefd digest {} to {}\n", date(since), date(now));
+
+        let mut upcoming: Vec<(DateTime<Utc>, &Company, String)> = Vec::new();
+        for v in self.companies.iter() {
+            if v.invalid.is_some() || v.paused {
+                continue;
+            }
+            if v.next_hit <= until {
+                upcoming.push((v.next_hit, v, String::from("request")));
+            }
+            if let Some(at) = self.scheduler.reminder_at(v).filter(|v| *v <= until) {
+                upcoming.push((at, v, format!("reminder {}", v.reminder + 1)));
+            }
+        }
+        upcoming.sort_by_key(|v| v.0);
+        section(
+            &mut out,
+            &format!("sends until {}", date(until)),
+            upcoming
+                .iter()
+                .map(|(at, v, kind)| format!("{}  {:<20} {}", date(*at), v.name, kind)),
+        );
+
+        let overdue = self.companies.iter().filter_map(|v| {
+            let deadline = v.deadline().filter(|_| v.is_overdue(now))?;
+            Some(format!(
+                "{}  {:<20} sent {}, {} days overdue",
+                date(deadline),
+                v.name,
+                date(v.last_hit?),
+                (now - deadline).num_days()
+            ))
+        });
+        section(&mut out, "past the legal deadline", overdue);
+
+        let mut replies: Vec<&Company> = self
+            .companies
+            .iter()
+            .filter(|v| v.last_reply.is_some_and(|v| v >= since))
+            .collect();
+        replies.sort_by_key(|v| v.last_reply);
+        section(
+            &mut out,
+            "replies",
+            replies.iter().map(|v| {
+                let kind = match v.outcome {
+                    Some((outcome, at)) if at >= since => outcome.to_string(),
+                    _ => String::from("reply"),
+                };
+                format!("{}  {:<20} {}", date(v.last_reply.unwrap()), v.name, kind)
+            }),
+        );
+
+        let mut failures: Vec<String> = self
+            .companies
+            .iter()
+            .filter_map(|v| {
+                let (mail, reason, at) = v.invalid.as_ref()?;
+                Some(format!(
+                    "{}  {:<20} {} bounced: {}",
+                    date(*at),
+                    v.name,
+                    mail,
+                    reason
+                ))
+            })
+            .collect();
+        let spool = self.spool.entries().and_then(|mut entries| {
+            entries.extend(self.spool.failed()?);
+            Ok(entries)
+        });
+        match spool {
+            Ok(entries) => failures.extend(entries.iter().filter(|v| v.error.is_some()).map(|v| {
+                let state = if v.attempts >= self.spool.max_attempts {
+                    String::from("given up")
+                } else {
+                    format!("retry {}", date(v.next_attempt))
+                };
+                format!(
+                    "{}  {:<20} {} after {} attempts ({}): {}",
+                    date(v.created),
+                    v.company,
+                    v.id,
+                    v.attempts,
+                    state,
+                    v.error.as_deref().unwrap_or_default()
+                )
+            })),
+            Err(err) => failures.push(format!("could not read spool {}: {}", self.spool.dir, err)),
+        }
+        section(&mut out, "failures", failures.into_iter());
+        out
+    }
+}
+
+/// append a titled list of `lines` to `out`
+fn section<I: Iterator<Item = String>>(out: &mut String, title: &str, lines: I) {
+    let _ = write!(out, "\n{}:\n", title);
+    let mut empty = true;
+    for line in lines {
+        let _ = writeln!(out, "  {}", line);
+        empty = false;
+    }
+    if empty {
+        out.push_str("  none\n");
+    }
+}
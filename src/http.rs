@@ -0,0 +1,173 @@
+use super::{
+    api::{self, Request},
+    watch::Event,
+};
+use serde_json::Value;
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{IpAddr, SocketAddr, TcpListener, TcpStream},
+    sync::mpsc::{self, Sender},
+    thread,
+    time::Duration,
+};
+
+/// single page showing the state and a timeline of every company
+const DASHBOARD: &str = include_str!("dashboard.html");
+
+/// address to listen on for `value`, a bare port only listens on localhost
+pub fn bind_address(value: &str) -> String {
+    if value.contains(':') {
+        value.to_string()
+    } else {
+        format!("127.0.0.1:{}", value)
+    }
+}
+
+/// serve the read only json api and the dashboard on `address`, requests go to the daemon through `tx`
+///
+/// Returns the address actually listened on.
+pub(crate) fn spawn(address: &str, tx: Sender<Event>) -> io::Result<SocketAddr> {
+    let listener = TcpListener::bind(bind_address(address))?;
+    let local = listener.local_addr()?;
+    if !local.ip().is_loopback() {
+        warn!("http api on {} is reachable from other hosts", local);
+    }
+    info!("serving the dashboard on http://{}/", local);
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let tx = tx.clone();
+                    thread::spawn(move || {
+                        if let Err(err) = serve(stream, &tx) {
+                            debug!("http connection failed: {}", err);
+                        }
+                    });
+                }
+                Err(err) => warn!("could not accept http connection: {}", err),
+            }
+        }
+    });
+    Ok(local)
+}
+
+/// answer one request and close the connection
+fn serve(stream: TcpStream, tx: &Sender<Event>) -> io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut words = line.split_whitespace();
+    let method = words.next().unwrap_or_default().to_string();
+    let target = words.next().unwrap_or_default().to_string();
+    let mut host = String::new();
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Host") {
+                host = value.trim().to_string();
+            }
+        }
+    }
+
+    let (status, content_type, body) = if method != "GET" && method != "HEAD" {
+        (405, "text/plain", String::from("method not allowed\n"))
+    } else if !is_local(&host) {
+        (403, "text/plain", String::from("unknown host\n"))
+    } else {
+        route(&target, tx)
+    };
+    debug!("http {} {} {}", method, target, status);
+    let reason = match status {
+        200 => "OK",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Service Unavailable",
+    };
+    write!(
+        writer,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+        status,
+        reason,
+        content_type,
+        body.len()
+    )?;
+    if method != "HEAD" {
+        writer.write_all(body.as_bytes())?;
+    }
+    writer.flush()
+}
+
+/// status, content type and body for the path `target`
+fn route(target: &str, tx: &Sender<Event>) -> (u16, &'static str, String) {
+    let path = target.split('?').next().unwrap_or_default();
+    let segments: Vec<String> = path.trim_matches('/').split('/').map(decode).collect();
+    let segments: Vec<&str> = segments.iter().map(|v| v.as_str()).collect();
+    // only requests which do not change anything are routed
+    let request = match segments.as_slice() {
+        [""] => return (200, "text/html; charset=utf-8", DASHBOARD.to_string()),
+        ["api", "status"] => Request::Status(None),
+        ["api", "companies", name] => Request::Status(Some(name.to_string())),
+        ["api", "companies", name, "history"] => Request::History(name.to_string()),
+        _ => {
+            let body = api::response(Err(format!("no such path {}", path)));
+            return (404, "application/json", body.to_string());
+        }
+    };
+    let (reply, answer) = mpsc::channel();
+    let response = match tx.send(Event::Request(request, reply)) {
+        Ok(()) => answer.recv().ok(),
+        Err(_) => None,
+    };
+    match response {
+        Some(response) if response["ok"] == Value::Bool(true) => {
+            (200, "application/json", response.to_string())
+        }
+        Some(response) => (404, "application/json", response.to_string()),
+        None => {
+            let body = api::response(Err(String::from("daemon stopped")));
+            (503, "application/json", body.to_string())
+        }
+    }
+}
+
+/// whether the `Host` header names this machine
+///
+/// Other names can point to localhost through dns rebinding, which would let any web
+/// page read the api.
+fn is_local(host: &str) -> bool {
+    let name = match host.strip_prefix('[') {
+        Some(rest) => rest.split(']').next().unwrap_or_default(),
+        None => host.rsplit_once(':').map(|v| v.0).unwrap_or(host),
+    };
+    name.eq_ignore_ascii_case("localhost") || name.parse::<IpAddr>().is_ok()
+}
+
+/// percent decoded path segment
+fn decode(segment: &str) -> String {
+    let bytes = segment.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|v| std::str::from_utf8(v).ok())
+            .and_then(|v| u8::from_str_radix(v, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
@@ -0,0 +1,388 @@
+use super::{
+    net::{self, other, Connection, Stream, READ_TIMEOUT},
+    Encryption, ServerConfig,
+};
+use std::{io, time::Duration};
+
+/// untagged server response, literals are cut out of `text` and kept in `literals`
+#[derive(Debug, Clone)]
+pub struct Response {
+    pub text: String,
+    pub literals: Vec<Vec<u8>>,
+}
+
+/// state of a selected mailbox
+#[derive(Debug, Clone, Default)]
+pub struct Mailbox {
+    pub exists: u32,
+    pub uid_validity: u32,
+    pub uid_next: u32,
+}
+
+/// minimal imap client
+pub struct Client {
+    conn: Connection,
+    tag: u32,
+    /// capabilities after login, asked for once
+    capabilities: Option<Vec<String>>,
+}
+
+impl Client {
+    /// connect, start tls if requested and log in
+    pub fn connect(config: &ServerConfig) -> io::Result<Self> {
+        let mut client = Self {
+            conn: Connection::new(Stream::connect(config)?),
+            tag: 0,
+            capabilities: None,
+        };
+        let greeting = client.conn.read_line()?;
+        if !greeting.starts_with("* OK") && !greeting.starts_with("* PREAUTH") {
+            return Err(other(format!("unexpected imap greeting: {}", greeting)));
+        }
+
+        if let Encryption::starttls = config.encryption {
+            client.run("STARTTLS")?;
+            client.conn = client.conn.upgrade(config)?;
+        }
+
+        if !greeting.starts_with("* PREAUTH") {
+            net::check_cleartext(config)?;
+            let login = format!("LOGIN {} {}", quote(&config.user), quote(&config.password));
+            client.run_secret(&login)?;
+        }
+
+        Ok(client)
+    }
+
+    /// run `command` and return all untagged responses
+    pub fn run(&mut self, command: &str) -> io::Result<Vec<Response>> {
+        let tag = self.next_tag();
+        self.conn.write_line(&format!("{} {}", tag, command))?;
+        self.responses(&tag)
+    }
+
+    /// like `run`, but keeps the command out of the trace log
+    fn run_secret(&mut self, command: &str) -> io::Result<Vec<Response>> {
+        let tag = self.next_tag();
+        self.conn
+            .write_all(format!("{} {}\r\n", tag, command).as_bytes())?;
+        self.responses(&tag)
+    }
+
+    fn next_tag(&mut self) -> String {
+        self.tag += 1;
+        format!("A{:04}", self.tag)
+    }
+
+    /// read one response line including all literals
+    fn response(&mut self) -> io::Result<Response> {
+        let mut response = Response {
+            text: String::new(),
+            literals: Vec::new(),
+        };
+        loop {
+            let line = self.conn.read_line()?;
+            response.text.push_str(&line);
+            match literal_len(&line) {
+                Some(len) => response.literals.push(self.conn.read_exact(len)?),
+                None => return Ok(response),
+            }
+        }
+    }
+
+    /// collect untagged responses until the tagged reply for `tag`
+    fn responses(&mut self, tag: &str) -> io::Result<Vec<Response>> {
+        let mut responses = Vec::new();
+        loop {
+            let response = self.response()?;
+            if let Some(status) = response.text.strip_prefix(tag) {
+                let status = status.trim_start();
+                if status.starts_with("OK") {
+                    return Ok(responses);
+                }
+                return Err(other(format!("imap server replied: {}", status)));
+            }
+            responses.push(response);
+        }
+    }
+
+    /// select `mailbox` for reading and writing
+    pub fn select(&mut self, mailbox: &str) -> io::Result<Mailbox> {
+        let mut state = Mailbox::default();
+        for response in self.run(&format!("SELECT {}", quote_mailbox(mailbox)))? {
+            let text = response.text.as_str();
+            if let Some(value) = text.strip_suffix(" EXISTS") {
+                state.exists = value.trim_start_matches("* ").parse().unwrap_or(0);
+            } else if let Some(value) = code_value(text, "UIDVALIDITY") {
+                state.uid_validity = value;
+            } else if let Some(value) = code_value(text, "UIDNEXT") {
+                state.uid_next = value;
+            }
+        }
+        Ok(state)
+    }
+
+    /// full raw messages with a uid in `range`, as `(uid, message)`
+    pub fn fetch(&mut self, range: &str) -> io::Result<Vec<(u32, Vec<u8>)>> {
+        let mut messages = Vec::new();
+        for mut response in self.run(&format!("UID FETCH {} (UID BODY.PEEK[])", range))? {
+            if !response.text.contains(" FETCH ") || response.literals.is_empty() {
+                continue;
+            }
+            let uid = response
+                .text
+                .split("UID ")
+                .nth(1)
+                .and_then(|v| v.split(|v: char| !v.is_ascii_digit()).next())
+                .and_then(|v| v.parse().ok());
+            if let Some(uid) = uid {
+                messages.push((uid, response.literals.remove(0)));
+            }
+        }
+        Ok(messages)
+    }
+
+    /// uids of the messages matching `query`
+    pub fn search(&mut self, query: &str) -> io::Result<Vec<u32>> {
+        let mut uids = Vec::new();
+        for response in self.run(&format!("UID SEARCH {}", query))? {
+            if let Some(list) = response.text.strip_prefix("* SEARCH") {
+                uids.extend(
+                    list.split_whitespace()
+                        .filter_map(|v| v.parse::<u32>().ok()),
+                );
+            }
+        }
+        Ok(uids)
+    }
+
+    /// whether the server announces `capability`
+    pub fn has_capability(&mut self, capability: &str) -> io::Result<bool> {
+        if self.capabilities.is_none() {
+            let mut capabilities = Vec::new();
+            for response in self.run("CAPABILITY")? {
+                if let Some(list) = response.text.strip_prefix("* CAPABILITY") {
+                    capabilities.extend(list.split_whitespace().map(String::from));
+                }
+            }
+            self.capabilities = Some(capabilities);
+        }
+        let capabilities = self.capabilities.as_deref().unwrap_or_default();
+        Ok(capabilities
+            .iter()
+            .any(|v| v.eq_ignore_ascii_case(capability)))
+    }
+
+    /// wait up to `timeout` for the server to announce new messages, returns `true` if it did
+    pub fn idle(&mut self, timeout: Duration) -> io::Result<bool> {
+        let tag = self.next_tag();
+        self.conn.write_line(&format!("{} IDLE", tag))?;
+        let line = self.conn.read_line()?;
+        if !line.starts_with('+') {
+            return Err(other(format!("imap server refused idle: {}", line)));
+        }
+
+        self.conn.get_ref().set_read_timeout(Some(timeout))?;
+        let mut new = false;
+        loop {
+            match self.conn.read_line() {
+                Ok(line) => {
+                    if line.ends_with(" EXISTS") || line.ends_with(" RECENT") {
+                        new = true;
+                        break;
+                    }
+                }
+                Err(ref err)
+                    if err.kind() == io::ErrorKind::WouldBlock
+                        || err.kind() == io::ErrorKind::TimedOut =>
+                {
+                    break
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        self.conn.get_ref().set_read_timeout(Some(READ_TIMEOUT))?;
+
+        self.conn.write_line("DONE")?;
+        self.responses(&tag)?;
+        Ok(new)
+    }
+
+    /// add `flags` to the messages with a uid in `range`
+    pub fn add_flags(&mut self, range: &str, flags: &str) -> io::Result<()> {
+        self.run(&format!("UID STORE {} +FLAGS.SILENT ({})", range, flags))?;
+        Ok(())
+    }
+
+    /// create `mailbox`, an already existing mailbox is not an error
+    pub fn create(&mut self, mailbox: &str) -> io::Result<()> {
+        match self.run(&format!("CREATE {}", quote_mailbox(mailbox))) {
+            Ok(_) => {
+                info!("created imap folder {}", mailbox);
+                Ok(())
+            }
+            Err(err) if err.to_string().contains("ALREADYEXISTS") => Ok(()),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// store `message` in `mailbox` with `flags`, creating the mailbox if needed
+    pub fn append(&mut self, mailbox: &str, flags: &str, message: &[u8]) -> io::Result<()> {
+        match self.append_once(mailbox, flags, message) {
+            Err(ref err) if err.to_string().contains("TRYCREATE") => {
+                self.create(mailbox)?;
+                self.append_once(mailbox, flags, message)
+            }
+            result => result,
+        }
+    }
+
+    fn append_once(&mut self, mailbox: &str, flags: &str, message: &[u8]) -> io::Result<()> {
+        let tag = self.next_tag();
+        self.conn.write_line(&format!(
+            "{} APPEND {} ({}) {{{}}}",
+            tag,
+            quote_mailbox(mailbox),
+            flags,
+            message.len()
+        ))?;
+        let line = self.conn.read_line()?;
+        if !line.starts_with('+') {
+            return Err(other(format!("imap server replied: {}", line)));
+        }
+        self.conn.write_all(message)?;
+        self.conn.write_line("")?;
+        self.responses(&tag)?;
+        Ok(())
+    }
+
+    /// hierarchy delimiter of the server, `/` if it has none
+    pub fn delimiter(&mut self) -> io::Result<char> {
+        for response in self.run("LIST \"\" \"\"")? {
+            if let Some(rest) = response.text.strip_prefix("* LIST ") {
+                // * LIST (\Noselect) "/" ""
+                let rest = rest.split_once(") ").map(|v| v.1).unwrap_or_default();
+                let mut chars = rest.chars();
+                if let (Some('"'), Some(v)) = (chars.next(), chars.next()) {
+                    return Ok(if v == '\\' {
+                        chars.next().unwrap_or('/')
+                    } else {
+                        v
+                    });
+                }
+            }
+        }
+        Ok('/')
+    }
+
+    /// move the messages with a uid in `range` to `mailbox`, creating the mailbox if needed
+    ///
+    /// Servers without MOVE get a copy, the originals are flagged as deleted and only expunged
+    /// if UIDPLUS allows to do so without touching other messages. Without it they stay in
+    /// the source mailbox until a mail client expunges it.
+    pub fn move_to(&mut self, range: &str, mailbox: &str) -> io::Result<()> {
+        let (command, fallback) = if self.has_capability("MOVE")? {
+            ("MOVE", false)
+        } else {
+            ("COPY", true)
+        };
+        let command = format!("UID {} {} {}", command, range, quote_mailbox(mailbox));
+        match self.run(&command) {
+            Err(ref err) if err.to_string().contains("TRYCREATE") => {
+                self.create(mailbox)?;
+                self.run(&command)?;
+            }
+            result => {
+                result?;
+            }
+        }
+        if fallback {
+            self.add_flags(range, "\\Deleted")?;
+            if self.has_capability("UIDPLUS")? {
+                self.run(&format!("UID EXPUNGE {}", range))?;
+            } else {
+                warn!(
+                    "imap server supports neither MOVE nor UIDPLUS, {} stays flagged as deleted",
+                    range
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// log out and close the connection
+    pub fn logout(mut self) -> io::Result<()> {
+        match self.run("LOGOUT") {
+            Ok(_) => Ok(()),
+            // some servers close the connection before the tagged reply
+            Err(ref err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(()),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+/// value of a response code like `[UIDNEXT 42]`
+fn code_value(text: &str, code: &str) -> Option<u32> {
+    let start = text.find(&format!("[{} ", code))? + code.len() + 2;
+    let end = text[start..].find(']')? + start;
+    text[start..end].trim().parse().ok()
+}
+
+/// length of the literal announced at the end of `line`
+fn literal_len(line: &str) -> Option<usize> {
+    let line = line.strip_suffix('}')?;
+    let start = line.rfind('{')?;
+    line[start + 1..].trim_end_matches('+').parse().ok()
+}
+
+/// quote the mailbox `name` encoded in modified utf-7
+fn quote_mailbox(name: &str) -> String {
+    quote(&encode_mailbox(name))
+}
+
+/// encode a mailbox name in modified utf-7 as defined in rfc 3501
+pub fn encode_mailbox(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    let mut pending: Vec<u16> = Vec::new();
+    let flush = |pending: &mut Vec<u16>, out: &mut String| {
+        if pending.is_empty() {
+            return;
+        }
+        let bytes: Vec<u8> = pending
+            .iter()
+            .flat_map(|v| v.to_be_bytes().to_vec())
+            .collect();
+        out.push('&');
+        out.push_str(&base64::encode_config(&bytes, base64::STANDARD_NO_PAD).replace('/', ","));
+        out.push('-');
+        pending.clear();
+    };
+    for v in name.chars() {
+        if (' '..='~').contains(&v) {
+            flush(&mut pending, &mut out);
+            out.push(v);
+            if v == '&' {
+                out.push('-');
+            }
+        } else {
+            let mut buf = [0; 2];
+            pending.extend_from_slice(v.encode_utf16(&mut buf));
+        }
+    }
+    flush(&mut pending, &mut out);
+    out
+}
+
+/// quote `value` as imap string
+pub fn quote(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for v in value.chars() {
+        if v == '"' || v == '\\' {
+            out.push('\\');
+        }
+        out.push(v);
+    }
+    out.push('"');
+    out
+}
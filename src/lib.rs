@@ -8,30 +8,124 @@ extern crate serde_json;
 #[cfg(test)]
 mod tests;
 
-use chrono::prelude::*;
+pub mod api;
+pub mod archive;
+pub mod auth;
+pub mod autoreply;
+pub mod bounce;
+pub mod classify;
+pub mod complaint;
+pub mod conf;
+pub mod control;
+pub mod digest;
+#[cfg(feature = "http")]
+pub mod http;
+pub mod imap;
+pub mod mail;
+pub mod mime;
+pub mod net;
+pub mod right;
+pub mod schedule;
+pub mod smtp;
+pub mod spool;
+pub mod template;
+pub mod transport;
+pub mod unpack;
+mod watch;
+
+use archive::Archive;
+use auth::ControlAuth;
+use autoreply::AutoReply;
+use chrono::{prelude::*, Duration};
+use classify::{Classifier, Outcome};
+use complaint::Complaint;
+use digest::Digest;
+use right::Right;
+use schedule::Scheduler;
 use serde_json::Value;
+use spool::{Entry, Spool};
 use std::{
-    fs::{File, OpenOptions},
+    collections::HashMap,
+    fs::File,
     io::{Read, Write},
 };
+use template::Language;
+use transport::Backend;
+use watch::{Checkpoint, Event};
+
+/// key of the imap sync state in the time file, next to the companies
+const IMAP_STATE: &str = "@imap";
+/// key of the digest state in the time file
+const DIGEST_STATE: &str = "@digest";
+/// events kept in the history of a company
+const MAX_HISTORY: usize = 200;
 
 #[derive(Debug)]
+#[allow(non_snake_case)]
 pub struct Config {
     pub ImapControl: ServerConfig,
     pub Imap: ServerConfig,
     pub Smtp: ServerConfig,
+    pub control_auth: ControlAuth,
+    /// sender of the answers to control mails, defaults to the `ImapControl` user
+    pub control_address: Option<String>,
     pub companies: Vec<Company>,
+    /// interval of companies which do not set their own
+    pub interval: Option<usize>,
+    /// language of the letters to companies which do not set their own
+    pub language: Language,
+    /// managed conf.d file companies added or changed by control mails are stored in
+    pub company_file: Option<String>,
+    /// config file the companies are reloaded from, along with conf.d next to it
+    pub config_file: Option<String>,
+    /// companies given on the command line, kept on reload
+    pub command_line: Vec<Company>,
+    /// unix socket the json control api listens on
+    pub socket: Option<String>,
+    /// address or port of the read only http api and dashboard
+    pub http: Option<String>,
+    pub scheduler: Scheduler,
+    pub spool: Spool,
+    pub transport: Backend,
+    /// folder on `Imap` to file a copy of every accepted request in
+    pub sent_folder: Option<String>,
+    /// folder on `Imap` replies arrive in
+    pub inbox: String,
+    /// parent folder on `Imap` handled replies are moved to, one sub folder per company
+    pub reply_folder: Option<String>,
+    /// tag handled replies with a keyword showing their classification
+    pub reply_keywords: bool,
+    pub archive: Archive,
+    pub autoreply: AutoReply,
+    pub classifier: Classifier,
+    pub digest: Digest,
+    pub complaint: Complaint,
+    /// how often mailboxes are checked if the server does not support IDLE
+    pub poll: Duration,
     pub dry_run: bool,
     pub time_file: String,
+    /// position of the reply watcher per `user@host/folder`
+    checkpoints: HashMap<String, Checkpoint>,
+    /// message ids of all handled replies, oldest first
+    processed: Vec<String>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ServerConfig {
     pub port: u16,
     pub host: String,
     pub encryption: Encryption,
     pub user: String,
     pub password: String,
+    /// pem bundle of additional trusted certificates
+    pub ca_file: Option<String>,
+    /// pem certificate for client authentication
+    pub client_cert: Option<String>,
+    /// pem pkcs8 key belonging to `client_cert`
+    pub client_key: Option<String>,
+    pub min_tls: Option<native_tls::Protocol>,
+    /// sha256 fingerprint of the server certificate, replaces the ca validation
+    pub fingerprint: Option<String>,
 }
 
 impl ServerConfig {
@@ -42,10 +136,21 @@ impl ServerConfig {
             encryption: Encryption::starttls,
             user: String::new(),
             password: String::new(),
+            ca_file: None,
+            client_cert: None,
+            client_key: None,
+            min_tls: None,
+            fingerprint: None,
         }
     }
 }
 
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Company {
     pub name: String,
@@ -53,8 +158,48 @@ pub struct Company {
     pub alias: String,
     pub onw_name: String,
     pub interval: usize,
+    /// language of the letters, the global one if unset
+    pub language: Option<Language>,
     reminder: u8,
     next_hit: DateTime<Utc>,
+    last_hit: Option<DateTime<Utc>>,
+    known: bool,
+    /// message id of the open request
+    message_id: Option<String>,
+    /// reference number of the last request
+    reference: Option<String>,
+    /// address which bounced, the reason and when
+    invalid: Option<(String, String, DateTime<Utc>)>,
+    /// no requests or reminders are sent while paused
+    paused: bool,
+    /// when the open request was answered, stops the reminders
+    answered: Option<DateTime<Utc>>,
+    /// ticket number from an acknowledgement of the open request
+    ticket: Option<String>,
+    /// classified answer to the open request and when it arrived
+    outcome: Option<(Outcome, DateTime<Utc>)>,
+    /// when the company last replied in any way
+    last_reply: Option<DateTime<Utc>>,
+    /// when the last request was answered with the data
+    fulfilled: Option<DateTime<Utc>>,
+    /// days each fulfilled request took to be answered, oldest first
+    response_days: Vec<u32>,
+    /// what happened to the requests, oldest first
+    history: Vec<(DateTime<Utc>, String)>,
+    /// right the open request exercises
+    right: Right,
+    /// request for another right to send once, with details from the operator
+    pending: Option<(Right, String)>,
+    /// when the company gave notice of extending the deadline by two months
+    extended: Option<DateTime<Utc>>,
+    /// since when the company verifies our identity, the deadline is stopped meanwhile
+    verifying: Option<DateTime<Utc>>,
+    /// time the deadline of the open request was stopped for
+    suspended: Duration,
+    /// address of the supervisory authority responsible for the company, the global one if unset
+    pub authority: Option<String>,
+    /// when the complaint about the open request was drafted
+    complained: Option<DateTime<Utc>>,
 }
 
 impl Company {
@@ -65,28 +210,285 @@ impl Company {
             alias: String::new(),
             onw_name: String::new(),
             interval: 365,
+            language: None,
             reminder: 0,
             next_hit: Utc::now(),
+            last_hit: None,
+            known: false,
+            message_id: None,
+            reference: None,
+            invalid: None,
+            paused: false,
+            answered: None,
+            ticket: None,
+            outcome: None,
+            last_reply: None,
+            fulfilled: None,
+            response_days: Vec::new(),
+            history: Vec::new(),
+            right: Right::Access,
+            pending: None,
+            extended: None,
+            verifying: None,
+            suspended: Duration::zero(),
+            authority: None,
+            complained: None,
+        }
+    }
+
+    /// whether the request last sent was answered with the data
+    pub fn is_fulfilled(&self) -> bool {
+        match (self.last_hit, self.fulfilled) {
+            (Some(sent), Some(fulfilled)) => fulfilled >= sent,
+            _ => false,
+        }
+    }
+
+    /// legal deadline of the request last sent (Art. 12(3) GDPR), `None` while it is stopped
+    ///
+    /// The request is received when it is sent. The company has one month, three if it
+    /// gave notice of an extension, plus the time spent verifying our identity.
+    pub fn deadline(&self) -> Option<DateTime<Utc>> {
+        if self.verifying.is_some() {
+            return None;
+        }
+        let months = if self.extended.is_some() { 3 } else { 1 };
+        Some(schedule::deadline(self.last_hit? + self.suspended, months))
+    }
+
+    /// extend the deadline by two months, the notice has to arrive before the first month ends
+    pub(crate) fn extend(&mut self, now: DateTime<Utc>) {
+        if self.extended.is_some() {
+            return;
+        }
+        match self.deadline() {
+            Some(deadline) if now <= deadline => {
+                self.extended = Some(now);
+                let deadline = self.deadline().unwrap().format("%Y-%m-%d");
+                info!("deadline of {} extended to {}", self.name, deadline);
+                self.record(now, format!("deadline extended to {}", deadline));
+            }
+            _ => {
+                warn!("late extension notice of {}", self.name);
+                self.record(now, String::from("late extension notice ignored"));
+            }
+        }
+    }
+
+    /// stop the deadline while the company verifies our identity
+    pub(crate) fn stop_clock(&mut self, now: DateTime<Utc>) {
+        if self.verifying.is_none() && self.last_hit.is_some() {
+            self.verifying = Some(now);
+            let event = "deadline stopped for identity verification";
+            self.record(now, event.to_string());
+        }
+    }
+
+    /// let the deadline run again after the identity verification, moving it by the pause
+    pub(crate) fn resume_clock(&mut self, now: DateTime<Utc>) {
+        if let Some(since) = self.verifying.take() {
+            self.suspended = self.suspended + (now - since).max(Duration::zero());
+            if let Some(deadline) = self.deadline() {
+                let deadline = deadline.format("%Y-%m-%d");
+                self.record(now, format!("deadline moved to {}", deadline));
+            }
+        }
+    }
+
+    /// right exercised by the open request
+    pub fn right(&self) -> Right {
+        self.right
+    }
+
+    /// queue a request for `right` to be sent once, besides the periodic access requests
+    ///
+    /// It waits for the open request to be answered, both would share one deadline.
+    pub fn request_once(&mut self, right: Right, details: &str) -> Result<(), String> {
+        if right.needs_details() && details.trim().is_empty() {
+            return Err(format!("{} request to {} needs details", right, self.name));
+        }
+        self.pending = Some((right, details.trim().to_string()));
+        Ok(())
+    }
+
+    /// whether the request last sent is still waiting for an answer
+    pub fn is_open(&self) -> bool {
+        self.message_id.is_some() && self.answered.is_none() && !self.is_fulfilled()
+    }
+
+    /// whether the request last sent is unanswered past its legal deadline
+    pub fn is_overdue(&self, now: DateTime<Utc>) -> bool {
+        self.is_open()
+            && self.invalid.is_none()
+            && !self.paused
+            && self.deadline().is_some_and(|v| v < now)
+    }
+
+    /// days the companies took to fulfil requests, oldest first
+    pub fn response_days(&self) -> &[u32] {
+        &self.response_days
+    }
+
+    /// events of the requests to this company, oldest first
+    pub fn history(&self) -> &[(DateTime<Utc>, String)] {
+        &self.history
+    }
+
+    /// add `event` to the history, dropping the oldest events
+    pub(crate) fn record(&mut self, now: DateTime<Utc>, event: String) {
+        self.history.push((now, event));
+        if self.history.len() > MAX_HISTORY {
+            self.history.remove(0);
         }
     }
+
+    /// set the fields given in the toml table `value` of a `[companies.<name>]` section
+    pub fn apply(&mut self, value: &toml::Value) -> Result<(), String> {
+        let table = value
+            .as_table()
+            .ok_or_else(|| format!("{} is not a table", self.name))?;
+        for (k, v) in table.iter() {
+            let wrong = || format!("{} of {} has the wrong type", k, self.name);
+            match k.as_str() {
+                "alias" => {
+                    self.alias = v.as_str().ok_or_else(wrong)?.to_string();
+                    trace!("set alias to '{}'", self.alias);
+                }
+                "mail" => {
+                    let mail = v.as_str().ok_or_else(wrong)?;
+                    if self.invalid.as_ref().is_some_and(|v| v.0 != mail) {
+                        info!("address of {} changed, sending requests again", self.name);
+                        self.invalid = None;
+                    }
+                    self.mail = mail.to_string();
+                    trace!("set mail to '{}'", self.mail);
+                }
+                "name" => {
+                    self.onw_name = v.as_str().ok_or_else(wrong)?.to_string();
+                    trace!("set own name to '{}'", self.onw_name);
+                }
+                "interval" => {
+                    let interval = v.as_integer().filter(|v| *v > 0).ok_or_else(|| {
+                        format!(
+                            "interval of {} must be a positive number of days",
+                            self.name
+                        )
+                    })?;
+                    self.interval = interval as usize;
+                    trace!("set interval to {} days", self.interval);
+                }
+                "language" => {
+                    let language = Language::parse(v.as_str().ok_or_else(wrong)?)?;
+                    trace!("set language to {}", language);
+                    self.language = Some(language);
+                }
+                "authority" => {
+                    let authority = v.as_str().ok_or_else(wrong)?;
+                    trace!("set supervisory authority to '{}'", authority);
+                    self.authority = Some(authority.to_string());
+                }
+                _ => return Err(format!("unknown field {} for {}", k, self.name)),
+            }
+        }
+        Ok(())
+    }
 }
 
-#[derive(Debug)]
+impl Default for Company {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+#[allow(non_camel_case_types)]
 pub enum Encryption {
+    none,
     tls,
     starttls,
 }
 
 impl Encryption {
-    pub fn parse(value: &str) -> Self {
+    pub fn parse(value: &str) -> Result<Self, String> {
         match value.to_lowercase().as_str() {
-            "tls" => Encryption::tls,
-            "starttls" => Encryption::starttls,
-            _ => Encryption::starttls,
+            "none" => Ok(Encryption::none),
+            "tls" => Ok(Encryption::tls),
+            "starttls" => Ok(Encryption::starttls),
+            _ => Err(format!("unknown encryption '{}'", value)),
         }
     }
 }
 
+impl std::fmt::Display for Encryption {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Encryption::none => write!(f, "none"),
+            Encryption::tls => write!(f, "tls"),
+            Encryption::starttls => write!(f, "starttls"),
+        }
+    }
+}
+
+/// file system safe version of `name`
+pub(crate) fn slug(name: &str) -> String {
+    name.chars()
+        .map(|v| if v.is_ascii_alphanumeric() { v } else { '_' })
+        .collect()
+}
+
+/// serve the http api on `address`
+#[cfg(feature = "http")]
+fn spawn_http(address: &str, tx: std::sync::mpsc::Sender<Event>) {
+    if let Err(err) = http::spawn(address, tx) {
+        error!("could not listen on {}: {}", address, err);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(not(feature = "http"))]
+fn spawn_http(_: &str, _: std::sync::mpsc::Sender<Event>) {
+    warn!("http api configured but not included in features");
+}
+
+/// serve the json api on the unix socket `path`
+#[cfg(unix)]
+fn spawn_socket(path: &str, tx: std::sync::mpsc::Sender<Event>) {
+    if let Err(err) = api::spawn_socket(path, tx) {
+        error!("could not listen on {}: {}", path, err);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(not(unix))]
+fn spawn_socket(_: &str, _: std::sync::mpsc::Sender<Event>) {
+    warn!("api socket configured but unix sockets are not supported on this platform");
+}
+
+/// hex encoded sha256 of `data`
+pub fn sha256(data: &[u8]) -> String {
+    use sha2::{Digest, Sha256};
+    Sha256::digest(data)
+        .iter()
+        .map(|v| format!("{:02x}", v))
+        .collect()
+}
+
+/// parse a minimum tls version like `1.2`
+pub fn parse_tls_version(value: &str) -> Result<native_tls::Protocol, String> {
+    use native_tls::Protocol;
+    match value
+        .to_lowercase()
+        .trim_start_matches("tls")
+        .trim_start_matches('v')
+    {
+        "1.0" | "10" => Ok(Protocol::Tlsv10),
+        "1.1" | "11" => Ok(Protocol::Tlsv11),
+        "1.2" | "12" => Ok(Protocol::Tlsv12),
+        "1.3" | "13" => Ok(Protocol::Tlsv13),
+        _ => Err(format!("unknown tls version '{}'", value)),
+    }
+}
+
 impl Config {
     pub fn new() -> Self {
         Default::default()
@@ -94,15 +496,114 @@ impl Config {
 
     /// run main logic
     pub fn run(mut self) {
-        //self.write_time().unwrap();
         info!(
             "startind datenbriefd version: {}",
             env!("CARGO_PKG_VERSION")
         );
         info!("loaded {} companies", &self.companies.len());
-        trace!("load companies time table");
+        self.load();
+
+        let now = Utc::now();
+        self.scheduler.stagger(&mut self.companies, now);
+        self.scheduler.spread(&mut self.companies);
+        self.save_time();
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        if !self.dry_run {
+            if !self.Imap.host.is_empty() {
+                let server = self.Imap.clone();
+                watch::spawn_watcher(
+                    server,
+                    self.inbox.clone(),
+                    self.poll,
+                    Event::Replies,
+                    tx.clone(),
+                );
+                self.watch_replies();
+            }
+            if !self.ImapControl.host.is_empty() {
+                let server = self.ImapControl.clone();
+                watch::spawn_watcher(
+                    server,
+                    String::from("INBOX"),
+                    self.poll,
+                    Event::Control,
+                    tx.clone(),
+                );
+                self.watch_control();
+            }
+            if let Some(address) = &self.http {
+                spawn_http(address, tx.clone());
+            }
+            if let Some(socket) = &self.socket {
+                spawn_socket(socket, tx);
+            }
+        }
+
+        loop {
+            let now = Utc::now();
+            for i in self.scheduler.due(&self.companies, now) {
+                self.hit(i, now, false);
+            }
+            for i in self.scheduler.reminders_due(&self.companies, now) {
+                self.hit(i, now, true);
+            }
+            if self.dry_run {
+                info!("dry run finished");
+                return;
+            }
+            self.flush_spool(now);
+            self.file_sent();
+            self.send_digest(now);
+            self.file_complaints(now);
+            self.scheduler.spread(&mut self.companies);
+            self.save_time();
+
+            let mut next = self
+                .companies
+                .iter()
+                .flat_map(|v| {
+                    let escalation = self.scheduler.escalation_at(v);
+                    let complaint = escalation.filter(|_| v.complained.is_none());
+                    vec![Some(v.next_hit), self.scheduler.reminder_at(v), complaint]
+                })
+                .flatten()
+                .min();
+            if let Ok(entries) = self.spool.entries() {
+                let retry = entries.iter().map(|v| v.next_attempt).min();
+                next = next.min(retry).or(next).or(retry);
+            }
+            let wait = match next {
+                Some(next) => (next - Utc::now())
+                    .max(Duration::minutes(1))
+                    .min(Duration::hours(1)),
+                None => Duration::hours(1),
+            };
+            debug!("sleeping for {} seconds", wait.num_seconds());
+            let mut events = Vec::new();
+            if let Ok(event) = rx.recv_timeout(wait.to_std().unwrap()) {
+                events.push(event);
+                events.extend(rx.try_iter());
+            }
+            for event in events.iter() {
+                if let Event::Request(request, reply) = event {
+                    let result = self.request(request, Utc::now());
+                    // the client may have gone away already
+                    let _ = reply.send(api::response(result));
+                }
+            }
+            if events.iter().any(|v| matches!(v, Event::Replies)) {
+                self.watch_replies();
+            }
+            if events.iter().any(|v| matches!(v, Event::Control)) {
+                self.watch_control();
+            }
+        }
+    }
 
-        // parse time file
+    /// load the time table
+    pub fn load(&mut self) {
+        trace!("load companies time table");
         match self.parse_time_file() {
             Ok(data) => {
                 self.parse_time(&data);
@@ -115,8 +616,347 @@ impl Config {
                 }
             }
         }
+    }
+
+    /// table of all companies and their state
+    pub fn status(&self) -> String {
+        let mut out = format!(
+            "{:<20} {:<30} {:<12} {:<22} {:<9} {:<12} {}\n",
+            "company", "mail", "next", "reference", "reminder", "last reply", "state"
+        );
+        for v in self.companies.iter() {
+            let state = match (&v.invalid, &v.message_id) {
+                (Some((_, reason, date)), _) => format!(
+                    "address invalid since {}: {}",
+                    date.format("%Y-%m-%d"),
+                    reason
+                ),
+                _ if v.verifying.is_some() => format!(
+                    "verifying identity since {}",
+                    v.verifying.unwrap().format("%Y-%m-%d")
+                ),
+                _ if v.paused => match v.outcome {
+                    Some((outcome, date)) => {
+                        format!("paused, {} on {}", outcome, date.format("%Y-%m-%d"))
+                    }
+                    None => String::from("paused"),
+                },
+                (None, Some(_)) if v.outcome.is_some() => {
+                    let (outcome, date) = v.outcome.unwrap();
+                    let mut state = format!("{} on {}", outcome, date.format("%Y-%m-%d"));
+                    if let (true, Some(days)) = (v.is_fulfilled(), v.response_days.last()) {
+                        state.push_str(&format!(" after {} days", days));
+                    }
+                    state
+                }
+                (None, Some(_)) if v.answered.is_some() => {
+                    let answered = v.answered.unwrap().format("%Y-%m-%d");
+                    format!("answered on {}, needs review", answered)
+                }
+                (None, Some(_)) => {
+                    let mut state = match v.right {
+                        Right::Access => String::from("open"),
+                        right => format!("open {} request", right),
+                    };
+                    if let Some(ticket) = &v.ticket {
+                        state.push_str(&format!(", ticket {}", ticket));
+                    }
+                    state
+                }
+                (None, None) => String::from("idle"),
+            };
+            let state = match &v.pending {
+                Some((right, _)) => format!("{}, {} request queued", state, right),
+                None => state,
+            };
+            out.push_str(&format!(
+                "{:<20} {:<30} {:<12} {:<22} {:<9} {:<12} {}\n",
+                v.name,
+                v.mail,
+                v.next_hit.format("%Y-%m-%d").to_string(),
+                v.reference.as_deref().unwrap_or("-"),
+                v.reminder,
+                v.last_reply
+                    .map(|v| v.format("%Y-%m-%d").to_string())
+                    .unwrap_or_else(|| String::from("-")),
+                state
+            ));
+        }
+        out
+    }
+
+    /// render the request or reminder of the company at `index` and put it into the spool
+    pub(crate) fn hit(&mut self, index: usize, now: DateTime<Utc>, reminder: bool) {
+        let v: &Company = &self.companies[index];
+        let id = if reminder {
+            Spool::reminder_id(v)
+        } else {
+            Spool::id(v)
+        };
+        if self.spool.contains(&id) {
+            if self.spool.has_failed(&id) {
+                trace!("{} for {} failed before, not sending again", id, v.name);
+            }
+            return;
+        }
+
+        let language = v.language.unwrap_or(self.language);
+        let (reference, message) = if reminder {
+            let reference = v.reference.clone().unwrap_or_default();
+            let message_id = mail::message_id(&reference, &v.alias, now);
+            (reference, mail::reminder(v, language, &message_id, now))
+        } else {
+            let (right, details) = v.pending.clone().unwrap_or_default();
+            let reference = mail::reference(v, now);
+            let message_id = mail::message_id(&reference, &v.alias, now);
+            let message = mail::request(v, language, right, &details, &reference, &message_id, now);
+            (reference.clone(), message)
+        };
+        let message_id = message.get("Message-ID").unwrap_or_default().to_string();
+        let message = message.render();
+        if self.dry_run {
+            info!(
+                "dry run: would send {} to {} <{}>",
+                reference, v.name, v.mail
+            );
+            debug!("{}:\n{}", id, message);
+            return;
+        }
+        info!("queueing {} ({}) to {} <{}>", id, reference, v.name, v.mail);
+        let entry = Entry {
+            id,
+            company: v.name.clone(),
+            from: v.alias.clone(),
+            to: vec![v.mail.clone()],
+            message_id,
+            reference,
+            reminder: if reminder { v.reminder + 1 } else { 0 },
+            created: now,
+            attempts: 0,
+            next_attempt: now,
+            error: None,
+        };
+        if let Err(err) = self.spool.push(&entry, message.as_bytes()) {
+            error!("could not spool {} for {}: {}", entry.id, v.name, err);
+        }
+    }
+
+    /// submit all spooled messages which are due
+    fn flush_spool(&mut self, now: DateTime<Utc>) {
+        let entries = match self.spool.entries() {
+            Ok(entries) => entries,
+            Err(err) => {
+                error!("could not read spool {}: {}", self.spool.dir, err);
+                return;
+            }
+        };
+        let mut entries: Vec<Entry> = entries
+            .into_iter()
+            .filter(|v| v.next_attempt <= now)
+            .collect();
+        if entries.is_empty() {
+            return;
+        }
+
+        let mut client = match self.transport.connect(&self.Smtp) {
+            Ok(client) => Some(client),
+            Err(err) => {
+                warn!("could not open transport {:?}: {}", self.transport, err);
+                for entry in entries.iter_mut() {
+                    self.failed(entry, err.to_string(), now);
+                }
+                return;
+            }
+        };
+
+        for mut entry in entries {
+            let index = self.companies.iter().position(|v| v.name == entry.company);
+            // a spool id only changes once the message was accepted
+            let current = |v: &Company| match entry.reminder {
+                0 => Spool::id(v),
+                _ => Spool::reminder_id(v),
+            };
+            if index.is_none_or(|i| current(&self.companies[i]) != entry.id) {
+                info!("dropping stale spool entry {}", entry.id);
+                if let Err(err) = self.spool.remove(&entry.id) {
+                    error!("could not remove spool entry {}: {}", entry.id, err);
+                }
+                continue;
+            }
+            let index = index.unwrap();
+
+            let result = self.spool.message(&entry.id).and_then(|message| {
+                let to: Vec<&str> = entry.to.iter().map(|v| v.as_str()).collect();
+                match client.as_mut() {
+                    Some(client) => client.send(&entry.from, &to, &message),
+                    None => Err(net::other("transport connection lost")),
+                }
+            });
+            match result {
+                Ok(()) => {
+                    info!("{} to {} accepted", entry.id, entry.company);
+                    let v: &mut Company = &mut self.companies[index];
+                    if entry.reminder == 0 {
+                        // requests for other rights leave the periodic schedule alone
+                        let right = match v.pending.take() {
+                            Some((right, _)) => right,
+                            None => {
+                                v.next_hit = now + Duration::days(v.interval as i64);
+                                Right::Access
+                            }
+                        };
+                        v.last_hit = Some(now);
+                        v.right = right;
+                        v.reminder = 0;
+                        v.extended = None;
+                        v.verifying = None;
+                        v.suspended = Duration::zero();
+                        v.complained = None;
+                        v.message_id = Some(entry.message_id.clone());
+                        v.reference = Some(entry.reference.clone());
+                        v.answered = None;
+                        v.ticket = None;
+                        v.outcome = None;
+                        let event = match right {
+                            Right::Access => format!("sent request {}", entry.reference),
+                            right => format!("sent {} request {}", right, entry.reference),
+                        };
+                        v.record(now, event);
+                    } else {
+                        v.reminder = entry.reminder;
+                        v.record(now, format!("sent reminder {}", entry.reminder));
+                    }
+                    // persist first so the request is never sent twice
+                    self.save_time();
+                    let v = &self.companies[index];
+                    let copy = self
+                        .spool
+                        .message(&entry.id)
+                        .and_then(|message| self.archive.store_letter(v, &entry.id, &message, now));
+                    if let Err(err) = copy {
+                        warn!(
+                            "could not archive {} to {}: {}",
+                            entry.id, entry.company, err
+                        );
+                    }
+                    let result = match self.sent_folder {
+                        Some(_) => self.spool.keep_sent(&entry.id),
+                        None => self.spool.remove(&entry.id),
+                    };
+                    if let Err(err) = result {
+                        error!("could not remove spool entry {}: {}", entry.id, err);
+                    }
+                }
+                Err(err) => {
+                    warn!("could not send {} to {}: {}", entry.id, entry.company, err);
+                    if err.kind() != std::io::ErrorKind::Other {
+                        client = None;
+                    }
+                    self.failed(&mut entry, err.to_string(), now);
+                }
+            }
+        }
+
+        if let Some(client) = client {
+            if let Err(err) = client.close() {
+                debug!("error closing transport: {}", err);
+            }
+        }
+    }
+
+    /// file copies of accepted requests in the sent folder
+    fn file_sent(&self) {
+        let folder = match &self.sent_folder {
+            Some(folder) => folder,
+            None => return,
+        };
+        let ids = match self.spool.sent() {
+            Ok(ids) if ids.is_empty() => return,
+            Ok(ids) => ids,
+            Err(err) => {
+                error!("could not read sent messages from spool: {}", err);
+                return;
+            }
+        };
+
+        let mut client = match imap::Client::connect(&self.Imap) {
+            Ok(client) => client,
+            Err(err) => {
+                warn!(
+                    "could not connect to imap server {}: {}",
+                    self.Imap.host, err
+                );
+                return;
+            }
+        };
+        for id in ids {
+            let result = self
+                .spool
+                .sent_message(&id)
+                .and_then(|message| client.append(folder, "\\Seen", &message))
+                .and_then(|_| self.spool.remove_sent(&id));
+            match result {
+                Ok(()) => debug!("filed {} in {}", id, folder),
+                Err(err) => {
+                    warn!("could not file {} in {}: {}", id, folder, err);
+                    break;
+                }
+            }
+        }
+        if let Err(err) = client.logout() {
+            debug!("error closing imap connection: {}", err);
+        }
+    }
+
+    /// record a failed submission and report if the spool gave up
+    fn failed(&mut self, entry: &mut Entry, err: String, now: DateTime<Utc>) {
+        match self.spool.retry(entry, err, now) {
+            Ok(true) => {
+                error!(
+                    "giving up on request {} to {} after {} attempts: {}",
+                    entry.id,
+                    entry.company,
+                    entry.attempts,
+                    entry.error.as_deref().unwrap_or_default()
+                );
+                self.gave_up(entry, now);
+            }
+            Ok(false) => debug!(
+                "retrying request {} in {} minutes",
+                entry.id,
+                self.spool.delay(entry.attempts).num_minutes()
+            ),
+            Err(err) => error!("could not update spool entry {}: {}", entry.id, err),
+        }
+    }
 
-        error!("create a run function to run:\n{:?}", self);
+    /// move the schedule of the company of `entry` past the message which was given up on,
+    /// otherwise it stays due and keeps its slot of the send quota
+    fn gave_up(&mut self, entry: &Entry, now: DateTime<Utc>) {
+        let v = match self.companies.iter_mut().find(|v| v.name == entry.company) {
+            Some(v) => v,
+            None => return,
+        };
+        if entry.reminder > 0 {
+            v.reminder = entry.reminder;
+            v.record(now, format!("gave up on reminder {}", entry.reminder));
+        } else if let Some((right, _)) = v.pending.take() {
+            v.record(now, format!("gave up on {} request", right));
+        } else {
+            v.next_hit = now + Duration::days(v.interval as i64);
+            v.record(now, String::from("gave up on request"));
+        }
+        self.save_time();
+    }
+
+    /// write the time table, logging failures
+    pub(crate) fn save_time(&self) {
+        if self.dry_run {
+            return;
+        }
+        if let Err(err) = self.write_time() {
+            error!("could not write {} as timetable: {}", self.time_file, err);
+        }
     }
 
     /// parse time table file
@@ -127,6 +967,28 @@ impl Config {
             return;
         }
         let json = json.unwrap();
+        if let Some(value) = json.get(IMAP_STATE) {
+            for (k, v) in value["folders"].as_object().into_iter().flatten() {
+                let checkpoint = Checkpoint {
+                    uid_validity: v["uid-validity"].as_u64().unwrap_or(0) as u32,
+                    uid: v["uid"].as_u64().unwrap_or(0) as u32,
+                };
+                trace!("read checkpoint of {} as {:?}", k, checkpoint);
+                self.checkpoints.insert(k.to_string(), checkpoint);
+            }
+            self.processed = value["processed"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|v| v.as_str())
+                .map(|v| v.to_string())
+                .collect();
+        }
+        if let Some(value) = json.get(DIGEST_STATE) {
+            self.digest.last = value["last"]
+                .as_str()
+                .and_then(|v| v.parse::<DateTime<Utc>>().ok());
+        }
         for v in self.companies.iter_mut() {
             let v: &mut Company = v;
             if let Some(value) = json.get(&v.name) {
@@ -136,6 +998,7 @@ impl Config {
                         if let Ok(value) = value {
                             trace!("read next hit for {} on {}", v.name, value);
                             v.next_hit = value;
+                            v.known = true;
                         } else if let Err(err) = value {
                             error!("could not load next hit for {}: {}", v.name, err);
                         }
@@ -147,6 +1010,156 @@ impl Config {
                         v.reminder = value as u8;
                     }
                 }
+                if let Some(value) = value.get("message-id") {
+                    if let Some(value) = value.as_str() {
+                        v.message_id = Some(value.to_string());
+                    }
+                }
+                if let Some(value) = value.get("reference") {
+                    if let Some(value) = value.as_str() {
+                        v.reference = Some(value.to_string());
+                    }
+                }
+                if let Some(value) = value.get("paused") {
+                    if let Some(value) = value.as_bool() {
+                        v.paused = value;
+                    }
+                }
+                if let Some(value) = value.get("answered") {
+                    if let Some(value) = value.as_str() {
+                        match value.parse::<DateTime<Utc>>() {
+                            Ok(value) => v.answered = Some(value),
+                            Err(err) => {
+                                error!("could not load answer date for {}: {}", v.name, err)
+                            }
+                        }
+                    }
+                }
+                if let Some(value) = value.get("ticket") {
+                    if let Some(value) = value.as_str() {
+                        v.ticket = Some(value.to_string());
+                    }
+                }
+                if let Some(value) = value.get("last-reply") {
+                    if let Some(value) = value.as_str() {
+                        match value.parse::<DateTime<Utc>>() {
+                            Ok(value) => v.last_reply = Some(value),
+                            Err(err) => error!("could not load last reply for {}: {}", v.name, err),
+                        }
+                    }
+                }
+                if let Some(value) = value.get("fulfilled") {
+                    if let Some(value) = value.as_str() {
+                        match value.parse::<DateTime<Utc>>() {
+                            Ok(value) => v.fulfilled = Some(value),
+                            Err(err) => {
+                                error!("could not load fulfil date for {}: {}", v.name, err)
+                            }
+                        }
+                    }
+                }
+                if let Some(value) = value.get("response-days") {
+                    v.response_days = value
+                        .as_array()
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|v| v.as_u64())
+                        .map(|v| v as u32)
+                        .collect();
+                }
+                if let Some(value) = value.get("history") {
+                    v.history = value
+                        .as_array()
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|v| {
+                            let date = v["date"].as_str()?.parse::<DateTime<Utc>>().ok()?;
+                            Some((date, v["event"].as_str()?.to_string()))
+                        })
+                        .collect();
+                }
+                if let Some(value) = value.get("right") {
+                    match value.as_str().map(Right::parse) {
+                        Some(Ok(right)) => v.right = right,
+                        _ => error!("could not load request type for {}", v.name),
+                    }
+                }
+                if let Some(value) = value.get("pending") {
+                    let right = value
+                        .get("right")
+                        .and_then(|v| v.as_str())
+                        .map(Right::parse);
+                    let details = value.get("details").and_then(|v| v.as_str());
+                    match right {
+                        Some(Ok(right)) => {
+                            v.pending = Some((right, details.unwrap_or_default().to_string()))
+                        }
+                        _ => error!("could not load pending request for {}", v.name),
+                    }
+                }
+                if let Some(value) = value.get("extended") {
+                    match value.as_str().map(|v| v.parse::<DateTime<Utc>>()) {
+                        Some(Ok(value)) => v.extended = Some(value),
+                        _ => error!("could not load extension date for {}", v.name),
+                    }
+                }
+                if let Some(value) = value.get("verifying") {
+                    match value.as_str().map(|v| v.parse::<DateTime<Utc>>()) {
+                        Some(Ok(value)) => v.verifying = Some(value),
+                        _ => error!("could not load verification date for {}", v.name),
+                    }
+                }
+                if let Some(value) = value.get("suspended") {
+                    if let Some(value) = value.as_i64() {
+                        v.suspended = Duration::seconds(value);
+                    }
+                }
+                if let Some(value) = value.get("complained") {
+                    match value.as_str().map(|v| v.parse::<DateTime<Utc>>()) {
+                        Some(Ok(value)) => v.complained = Some(value),
+                        _ => error!("could not load complaint date for {}", v.name),
+                    }
+                }
+                if let Some(value) = value.get("outcome") {
+                    let kind = value
+                        .get("kind")
+                        .and_then(|v| v.as_str())
+                        .map(Outcome::parse);
+                    let date = value
+                        .get("date")
+                        .and_then(|v| v.as_str())
+                        .and_then(|v| v.parse::<DateTime<Utc>>().ok());
+                    match (kind, date) {
+                        (Some(Ok(kind)), Some(date)) => v.outcome = Some((kind, date)),
+                        (Some(Err(err)), _) => {
+                            error!("could not load outcome for {}: {}", v.name, err)
+                        }
+                        _ => error!("could not load outcome for {}", v.name),
+                    }
+                }
+                if let Some(value) = value.get("invalid") {
+                    let mail = value.get("mail").and_then(|v| v.as_str());
+                    let reason = value.get("reason").and_then(|v| v.as_str());
+                    let date = value
+                        .get("date")
+                        .and_then(|v| v.as_str())
+                        .and_then(|v| v.parse::<DateTime<Utc>>().ok());
+                    if let (Some(mail), Some(reason), Some(date)) = (mail, reason, date) {
+                        if mail == v.mail {
+                            v.invalid = Some((mail.to_string(), reason.to_string(), date));
+                        } else {
+                            info!("address of {} changed, sending requests again", v.name);
+                        }
+                    }
+                }
+                if let Some(value) = value.get("last") {
+                    if let Some(value) = value.as_str() {
+                        match value.parse::<DateTime<Utc>>() {
+                            Ok(value) => v.last_hit = Some(value),
+                            Err(err) => error!("could not load last hit for {}: {}", v.name, err),
+                        }
+                    }
+                }
             } else {
                 debug!("{} has no entry in the time table file", v.name);
             }
@@ -168,6 +1181,78 @@ impl Config {
         for v in self.companies.iter() {
             let v: &Company = v;
             json[&v.name] = json!({"next": v.next_hit.to_rfc3339(), "reminder": v.reminder});
+            if let Some(last) = v.last_hit {
+                json[&v.name]["last"] = json!(last.to_rfc3339());
+            }
+            if let Some(message_id) = &v.message_id {
+                json[&v.name]["message-id"] = json!(message_id);
+            }
+            if let Some(reference) = &v.reference {
+                json[&v.name]["reference"] = json!(reference);
+            }
+            if v.paused {
+                json[&v.name]["paused"] = json!(true);
+            }
+            if let Some(answered) = v.answered {
+                json[&v.name]["answered"] = json!(answered.to_rfc3339());
+            }
+            if let Some(ticket) = &v.ticket {
+                json[&v.name]["ticket"] = json!(ticket);
+            }
+            if let Some(last_reply) = v.last_reply {
+                json[&v.name]["last-reply"] = json!(last_reply.to_rfc3339());
+            }
+            if let Some(fulfilled) = v.fulfilled {
+                json[&v.name]["fulfilled"] = json!(fulfilled.to_rfc3339());
+            }
+            if !v.response_days.is_empty() {
+                json[&v.name]["response-days"] = json!(v.response_days);
+            }
+            if !v.history.is_empty() {
+                json[&v.name]["history"] = v
+                    .history
+                    .iter()
+                    .map(|(date, event)| json!({"date": date.to_rfc3339(), "event": event}))
+                    .collect();
+            }
+            if v.right != Right::Access {
+                json[&v.name]["right"] = json!(v.right.to_string());
+            }
+            if let Some((right, details)) = &v.pending {
+                json[&v.name]["pending"] = json!({"right": right.to_string(), "details": details});
+            }
+            if let Some(extended) = v.extended {
+                json[&v.name]["extended"] = json!(extended.to_rfc3339());
+            }
+            if let Some(verifying) = v.verifying {
+                json[&v.name]["verifying"] = json!(verifying.to_rfc3339());
+            }
+            if !v.suspended.is_zero() {
+                json[&v.name]["suspended"] = json!(v.suspended.num_seconds());
+            }
+            if let Some(complained) = v.complained {
+                json[&v.name]["complained"] = json!(complained.to_rfc3339());
+            }
+            if let Some((outcome, date)) = v.outcome {
+                json[&v.name]["outcome"] =
+                    json!({"kind": outcome.to_string(), "date": date.to_rfc3339()});
+            }
+            if let Some((mail, reason, date)) = &v.invalid {
+                json[&v.name]["invalid"] =
+                    json!({"mail": mail, "reason": reason, "date": date.to_rfc3339()});
+            }
+        }
+
+        if let Some(last) = self.digest.last {
+            json[DIGEST_STATE] = json!({"last": last.to_rfc3339()});
+        }
+
+        if !self.checkpoints.is_empty() || !self.processed.is_empty() {
+            json[IMAP_STATE] = json!({"folders": {}, "processed": self.processed});
+            for (k, v) in self.checkpoints.iter() {
+                json[IMAP_STATE]["folders"][k] =
+                    json!({"uid-validity": v.uid_validity, "uid": v.uid});
+            }
         }
 
         debug!(
@@ -175,9 +1260,13 @@ impl Config {
             serde_json::to_string_pretty(&json).unwrap()
         );
         let json: String = json.to_string();
-        let mut file = File::create(&self.time_file)?;
+        // replaced in one step, a torn file would make every company look new
+        let tmp = format!("{}.tmp", self.time_file);
+        let mut file = File::create(&tmp)?;
 
         file.write_all(json.as_bytes())?;
+        file.sync_all()?;
+        std::fs::rename(tmp, &self.time_file)?;
 
         Ok(())
     }
@@ -189,9 +1278,33 @@ impl Default for Config {
             Imap: ServerConfig::new(),
             Smtp: ServerConfig::new(),
             ImapControl: ServerConfig::new(),
+            control_auth: ControlAuth::new(),
+            control_address: None,
             companies: Vec::new(),
+            interval: None,
+            language: Language::default(),
+            company_file: None,
+            config_file: None,
+            command_line: Vec::new(),
+            socket: None,
+            http: None,
+            scheduler: Scheduler::new(),
+            spool: Spool::new(),
+            transport: Backend::Smtp,
+            sent_folder: None,
+            inbox: String::from("INBOX"),
+            reply_folder: Some(String::from("Datenbrief")),
+            reply_keywords: false,
+            archive: Archive::new(),
+            autoreply: AutoReply::new(),
+            classifier: Classifier::new(),
+            digest: Digest::new(),
+            complaint: Complaint::new(),
+            poll: Duration::minutes(5),
             dry_run: false,
             time_file: String::from("time.json"),
+            checkpoints: HashMap::new(),
+            processed: Vec::new(),
         }
     }
 }
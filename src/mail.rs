@@ -0,0 +1,261 @@
+use super::{
+    mime::{self, Part},
+    right::Right,
+    template::Language,
+    Company,
+};
+use chrono::prelude::*;
+use sha2::{Digest, Sha256};
+
+/// rfc 5322 message
+#[derive(Debug, Clone)]
+pub struct Message {
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Message {
+    pub fn new() -> Self {
+        Self {
+            headers: Vec::new(),
+            body: String::new(),
+        }
+    }
+
+    /// append a header
+    pub fn header(&mut self, name: &str, value: &str) -> &mut Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// first value of header `name`
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// render the message with CRLF line endings
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for (k, v) in self.headers.iter() {
+            out.push_str(&format!("{}: {}\r\n", k, v));
+        }
+        out.push_str("\r\n");
+        for line in self.body.lines() {
+            out.push_str(line);
+            out.push_str("\r\n");
+        }
+        out
+    }
+}
+
+impl Default for Message {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// encode a header value as rfc 2047 word if it is not ascii
+pub fn encode_word(value: &str) -> String {
+    if value.is_ascii() {
+        return value.to_string();
+    }
+    format!("=?utf-8?B?{}?=", base64::encode(value.as_bytes()))
+}
+
+/// human readable reference like `DB-2019-ACME-01`, counting up within a year
+pub fn reference(company: &Company, now: DateTime<Utc>) -> String {
+    let name: String = company
+        .name
+        .chars()
+        .filter(|v| v.is_ascii_alphanumeric())
+        .take(12)
+        .collect::<String>()
+        .to_uppercase();
+    let prefix = format!("DB-{}-{}-", now.year(), name);
+    let count = company
+        .reference
+        .as_ref()
+        .and_then(|v| v.strip_prefix(&prefix))
+        .and_then(|v| v.parse::<u32>().ok())
+        .unwrap_or(0);
+    format!("{}{:02}", prefix, count + 1)
+}
+
+/// unique message id in the domain of `from`
+pub fn message_id(reference: &str, from: &str, now: DateTime<Utc>) -> String {
+    let domain = from.rsplit('@').next().unwrap_or("localhost");
+    let mut hash = Sha256::new();
+    hash.input(reference.as_bytes());
+    hash.input(now.timestamp_nanos().to_le_bytes());
+    hash.input(std::process::id().to_le_bytes());
+    let hash: String = hash.result()[..8]
+        .iter()
+        .map(|v| format!("{:02x}", v))
+        .collect();
+    format!(
+        "<{}.{}.{}@{}>",
+        reference.to_lowercase(),
+        now.format("%Y%m%d%H%M%S"),
+        hash,
+        domain
+    )
+}
+
+/// common headers of all letters to `company`
+fn letter(company: &Company, subject: &str, message_id: &str, now: DateTime<Utc>) -> Message {
+    let mut message = Message::new();
+    message
+        .header(
+            "From",
+            &format!("{} <{}>", encode_word(&company.onw_name), company.alias),
+        )
+        .header("To", &company.mail)
+        .header("Subject", &encode_word(subject))
+        .header("Date", &now.to_rfc2822())
+        .header("Message-ID", message_id)
+        .header("MIME-Version", "1.0")
+        .header("Content-Type", "text/plain; charset=utf-8")
+        .header("Content-Transfer-Encoding", "8bit");
+    message
+}
+
+/// answer to the control mail `command` with the result of the command
+pub fn control_reply(from: &str, command: &Part, result: &str, now: DateTime<Utc>) -> Message {
+    let to = command.header("From").unwrap_or_default();
+    let subject = command.decoded("Subject").unwrap_or_default();
+    let subject = match subject.trim() {
+        "" => String::from("datenbriefd"),
+        v if v.to_lowercase().starts_with("re:") => v.to_string(),
+        v => format!("Re: {}", v),
+    };
+    let mut message = Message::new();
+    message
+        .header("From", &format!("datenbriefd <{}>", from))
+        .header("To", to)
+        .header("Subject", &encode_word(&subject))
+        .header("Date", &now.to_rfc2822())
+        .header("Message-ID", &message_id("control", from, now))
+        .header("Auto-Submitted", "auto-replied")
+        .header("MIME-Version", "1.0")
+        .header("Content-Type", "text/plain; charset=utf-8")
+        .header("Content-Transfer-Encoding", "8bit");
+    if let Some(id) = command.header("Message-ID") {
+        message.header("In-Reply-To", id).header("References", id);
+    }
+    message.body = result.to_string();
+    message
+}
+
+/// build the digest covering `since` to `now` with the summary `text`
+pub fn digest(
+    from: &str,
+    to: &str,
+    since: DateTime<Utc>,
+    now: DateTime<Utc>,
+    text: &str,
+) -> Message {
+    let subject = format!(
+        "datenbriefd digest {} to {}",
+        since.format("%Y-%m-%d"),
+        now.format("%Y-%m-%d")
+    );
+    let mut message = Message::new();
+    message
+        .header("From", &format!("datenbriefd <{}>", from))
+        .header("To", to)
+        .header("Subject", &subject)
+        .header("Date", &now.to_rfc2822())
+        .header("Message-ID", &message_id("digest", from, now))
+        .header("Auto-Submitted", "auto-generated")
+        .header("MIME-Version", "1.0")
+        .header("Content-Type", "text/plain; charset=utf-8")
+        .header("Content-Transfer-Encoding", "8bit");
+    message.body = text.to_string();
+    message
+}
+
+/// build the request for `right` to `company` in `language`
+pub fn request(
+    company: &Company,
+    language: Language,
+    right: Right,
+    details: &str,
+    reference: &str,
+    message_id: &str,
+    now: DateTime<Utc>,
+) -> Message {
+    let subject = language.request_subject(right, reference);
+    let mut message = letter(company, &subject, message_id, now);
+    message.body = language.request(right, company, reference, details);
+    message
+}
+
+/// build a reminder for the open request of `company`, threaded below the request
+pub fn reminder(
+    company: &Company,
+    language: Language,
+    message_id: &str,
+    now: DateTime<Utc>,
+) -> Message {
+    let reference = company.reference.as_deref().unwrap_or_default();
+    let original = company.message_id.as_deref().unwrap_or_default();
+    let right = company.right();
+    let subject = language.reminder_subject(right, reference);
+    let mut message = letter(company, &subject, message_id, now);
+    if !original.is_empty() {
+        message
+            .header("In-Reply-To", original)
+            .header("References", original);
+    }
+    let expired = company.deadline().is_none_or(|v| v <= now);
+    message.body = language.reminder(right, company, reference, company.last_hit, expired);
+    message
+}
+
+/// build the complaint `text` from `from` to `to` with the raw mails `attachments` as eml files
+pub fn complaint(
+    from: &str,
+    to: &str,
+    subject: &str,
+    text: &str,
+    attachments: &[(String, Vec<u8>)],
+    now: DateTime<Utc>,
+) -> Message {
+    let boundary = format!("=_datenbriefd_{:x}", now.timestamp_nanos());
+    let id = message_id("complaint", &mime::address(from), now);
+    let mut message = Message::new();
+    message
+        .header("From", from)
+        .header("To", to)
+        .header("Subject", &encode_word(subject))
+        .header("Date", &now.to_rfc2822())
+        .header("Message-ID", &id)
+        .header("MIME-Version", "1.0")
+        .header(
+            "Content-Type",
+            &format!("multipart/mixed; boundary=\"{}\"", boundary),
+        );
+    let mut body = format!(
+        "--{}\nContent-Type: text/plain; charset=utf-8\nContent-Transfer-Encoding: 8bit\n\n{}\n",
+        boundary, text
+    );
+    for (name, data) in attachments {
+        // the mails may use any charset, so they are not embedded as message/rfc822
+        body.push_str(&format!(
+            "--{}\nContent-Type: application/octet-stream; name=\"{}\"\n\
+             Content-Disposition: attachment; filename=\"{}\"\n\
+             Content-Transfer-Encoding: base64\n\n",
+            boundary, name, name
+        ));
+        for line in base64::encode(data).as_bytes().chunks(76) {
+            body.push_str(&String::from_utf8_lossy(line));
+            body.push('\n');
+        }
+    }
+    body.push_str(&format!("--{}--\n", boundary));
+    message.body = body;
+    message
+}
@@ -3,7 +3,7 @@ extern crate log;
 extern crate env_logger;
 
 use clap::{App, Arg, SubCommand};
-use datenbriefd::{Company, Config};
+use datenbriefd::{conf, Company, Config, Encryption, ServerConfig};
 
 fn main() {
     env_logger::init();
@@ -30,6 +30,21 @@ fn main() {
                 .help("set time json file")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("socket")
+                .long("socket")
+                .value_name("FILE")
+                .help("unix socket for the json control api, the default one if none is given")
+                .takes_value(true)
+                .min_values(0),
+        )
+        .arg(
+            Arg::with_name("http")
+                .long("http")
+                .value_name("ADDRESS")
+                .help("port or address for the http api and dashboard")
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("control.server")
                 .long("control-server")
@@ -50,6 +65,7 @@ fn main() {
                 .value_name("SCHEMA")
                 .help("encryption type for the control imap")
                 .takes_value(true)
+                .possible_value("none")
                 .possible_value("tls")
                 .possible_value("starttls"),
         )
@@ -67,6 +83,41 @@ fn main() {
                 .help("password for control imap")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("control.ca")
+                .long("control-ca")
+                .value_name("FILE")
+                .help("pem bundle of additional trusted certificates for control imap")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("control.cert")
+                .long("control-cert")
+                .value_name("FILE")
+                .help("pem client certificate for control imap")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("control.key")
+                .long("control-key")
+                .value_name("FILE")
+                .help("pem pkcs8 key of the client certificate for control imap")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("control.min-tls")
+                .long("control-min-tls")
+                .value_name("VERSION")
+                .help("minimum tls version for control imap")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("control.fingerprint")
+                .long("control-fingerprint")
+                .value_name("SHA256")
+                .help("pinned certificate fingerprint for control imap")
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("imap.server")
                 .long("imap-server")
@@ -87,6 +138,7 @@ fn main() {
                 .value_name("SCHEMA")
                 .help("encryption type for the imap")
                 .takes_value(true)
+                .possible_value("none")
                 .possible_value("tls")
                 .possible_value("starttls"),
         )
@@ -104,6 +156,62 @@ fn main() {
                 .help("password for imap")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("imap.ca")
+                .long("imap-ca")
+                .value_name("FILE")
+                .help("pem bundle of additional trusted certificates for imap")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("imap.cert")
+                .long("imap-cert")
+                .value_name("FILE")
+                .help("pem client certificate for imap")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("imap.key")
+                .long("imap-key")
+                .value_name("FILE")
+                .help("pem pkcs8 key of the client certificate for imap")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("imap.min-tls")
+                .long("imap-min-tls")
+                .value_name("VERSION")
+                .help("minimum tls version for imap")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("imap.fingerprint")
+                .long("imap-fingerprint")
+                .value_name("SHA256")
+                .help("pinned certificate fingerprint for imap")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("imap.sent")
+                .long("imap-sent")
+                .value_name("FOLDER")
+                .help("imap folder to store sent requests in")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("imap.inbox")
+                .long("imap-inbox")
+                .value_name("FOLDER")
+                .help("imap folder replies arrive in")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("imap.folder")
+                .long("imap-folder")
+                .value_name("FOLDER")
+                .help("imap folder to move handled replies to, empty to keep them")
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("smtp.server")
                 .long("smtp-server")
@@ -124,6 +232,7 @@ fn main() {
                 .value_name("SCHEMA")
                 .help("encryption type for the smtp")
                 .takes_value(true)
+                .possible_value("none")
                 .possible_value("tls")
                 .possible_value("starttls"),
         )
@@ -141,6 +250,41 @@ fn main() {
                 .help("password for smtp")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("smtp.ca")
+                .long("smtp-ca")
+                .value_name("FILE")
+                .help("pem bundle of additional trusted certificates for smtp")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("smtp.cert")
+                .long("smtp-cert")
+                .value_name("FILE")
+                .help("pem client certificate for smtp")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("smtp.key")
+                .long("smtp-key")
+                .value_name("FILE")
+                .help("pem pkcs8 key of the client certificate for smtp")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("smtp.min-tls")
+                .long("smtp-min-tls")
+                .value_name("VERSION")
+                .help("minimum tls version for smtp")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("smtp.fingerprint")
+                .long("smtp-fingerprint")
+                .value_name("SHA256")
+                .help("pinned certificate fingerprint for smtp")
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("interval")
                 .long("interval")
@@ -149,6 +293,86 @@ fn main() {
                 .help("set global interval, if local interval is not set")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("language")
+                .long("language")
+                .value_name("LANGUAGE")
+                .help("set global letter language, if local language is not set")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("max-per-day")
+                .long("max-per-day")
+                .value_name("COUNT")
+                .help("maximum number of requests to send per day")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("max-per-hour")
+                .long("max-per-hour")
+                .value_name("COUNT")
+                .help("maximum number of requests to send per hour")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("stagger")
+                .long("stagger")
+                .value_name("DAYS")
+                .help("spread first requests of new companies over this many days")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("reminders")
+                .long("reminders")
+                .value_name("COUNT")
+                .help("maximum number of reminders for an unanswered request")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("transport")
+                .long("transport")
+                .value_name("TRANSPORT")
+                .help("how to send messages")
+                .takes_value(true)
+                .possible_value("smtp")
+                .possible_value("sendmail")
+                .possible_value("maildir"),
+        )
+        .arg(
+            Arg::with_name("sendmail")
+                .long("sendmail")
+                .value_name("FILE")
+                .help("sendmail binary for the sendmail transport")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("maildir")
+                .long("maildir")
+                .value_name("DIR")
+                .help("directory for the maildir transport")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("archive")
+                .long("archive")
+                .value_name("DIR")
+                .help("directory to archive replies of companies in")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("spool-dir")
+                .long("spool-dir")
+                .value_name("DIR")
+                .help("directory for messages waiting to be sent")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("max-attempts")
+                .long("max-attempts")
+                .value_name("COUNT")
+                .help("give up sending a message after this many attempts")
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("dry-run")
                 .long("dry-run")
@@ -191,6 +415,13 @@ fn main() {
                 .takes_value(true),
         );
 
+    app = app.subcommand(
+        SubCommand::with_name("status")
+            .about("show the state of all companies")
+            .setting(clap::AppSettings::ColorAuto)
+            .setting(clap::AppSettings::ColoredHelp),
+    );
+
     if cfg!(feature = "completion") {
         app = app.subcommand(
             SubCommand::with_name("completion")
@@ -226,7 +457,7 @@ fn main() {
 
     if cfg!(feature = "completion") {
         if let Some(matches) = matches.subcommand_matches("completion") {
-            completion(&matches, &mut app);
+            completion(matches, &mut app);
             std::process::exit(0);
         }
     }
@@ -251,9 +482,21 @@ fn main() {
             None
         }
     };
-    drop(config_name);
+
+    // companies added by control mails live in conf.d next to the config file, they are
+    // loaded even if the config file itself is missing or broken
+    let conf_dir = conf::conf_dir(std::path::Path::new(config_name));
+    let mut companies_config = match &toml_config {
+        Some(toml_config) => toml_config.clone(),
+        None => toml::Value::Table(Default::default()),
+    };
+    if let Err(err) = conf::merge_conf_dir(&mut companies_config, &conf_dir) {
+        warn!("could not read {}: {}", conf_dir.display(), err);
+    }
 
     let mut config = Config::new();
+    config.company_file = Some(conf_dir.join("control.toml").to_string_lossy().to_string());
+    config.config_file = Some(config_name.to_string());
 
     if let Some(value) = &matches.value_of("control.server") {
         trace!("set imap control server to {}", value);
@@ -299,15 +542,15 @@ fn main() {
     }
 
     if let Some(value) = &matches.value_of("control.encryption") {
-        let value = datenbriefd::Encryption::parse(value);
-        trace!("set imap control encryption to {}", "FIXME");
+        let value = encryption(value);
+        trace!("set imap control encryption to {}", value);
         config.ImapControl.encryption = value;
     } else if let Some(toml_config) = &toml_config {
         if let Some(value) = toml_config.get("control") {
             if let Some(value) = value.get("encryption") {
                 if let Some(value) = value.as_str() {
-                    let value = datenbriefd::Encryption::parse(value);
-                    trace!("set imap control encryption to {}", "FIXME");
+                    let value = encryption(value);
+                    trace!("set imap control encryption to {}", value);
                     config.ImapControl.encryption = value;
                 }
             }
@@ -342,6 +585,55 @@ fn main() {
         }
     }
 
+    if let Some(toml_config) = &toml_config {
+        if let Some(value) = toml_config.get("control") {
+            if let Some(value) = value.get("senders") {
+                for value in value.as_array().into_iter().flatten() {
+                    if let Some(value) = value.as_str() {
+                        trace!("allow control commands from {}", value);
+                        config.control_auth.senders.push(value.to_lowercase());
+                    }
+                }
+            }
+            if let Some(value) = value.get("token") {
+                if let Some(value) = value.as_str() {
+                    //trace!("set control token to {}", value);
+                    config.control_auth.token = Some(value.to_string());
+                }
+            }
+            if let Some(value) = value.get("keyring") {
+                if let Some(value) = value.as_str() {
+                    trace!("set control keyring to {}", value);
+                    config.control_auth.keyring = Some(value.to_string());
+                }
+            }
+            if let Some(value) = value.get("gpgv") {
+                if let Some(value) = value.as_str() {
+                    trace!("set gpgv binary to {}", value);
+                    config.control_auth.gpgv = value.to_string();
+                }
+            }
+            if let Some(value) = value.get("address") {
+                if let Some(value) = value.as_str() {
+                    trace!("set control address to {}", value);
+                    config.control_address = Some(value.to_string());
+                }
+            }
+            if let Some(value) = value.get("audit") {
+                if let Some(value) = value.as_str() {
+                    trace!("set audit log to {}", value);
+                    config.control_auth.audit_log = value.to_string();
+                }
+            }
+            if let Some(value) = value.get("max-age") {
+                if let Some(value) = value.as_integer() {
+                    trace!("accept control mails up to {} hours old", value);
+                    config.control_auth.max_age = chrono::Duration::hours(value.clamp(1, 24 * 365));
+                }
+            }
+        }
+    }
+
     if let Some(value) = &matches.value_of("imap.server") {
         trace!("set imap server to {}", value);
         config.Imap.host = value.to_string();
@@ -376,15 +668,15 @@ fn main() {
     }
 
     if let Some(value) = &matches.value_of("imap.encryption") {
-        let value = datenbriefd::Encryption::parse(value);
-        trace!("set imap encryption to {}", "FIXME");
+        let value = encryption(value);
+        trace!("set imap encryption to {}", value);
         config.Imap.encryption = value;
     } else if let Some(toml_config) = &toml_config {
         if let Some(value) = toml_config.get("imap") {
             if let Some(value) = value.get("encryption") {
                 if let Some(value) = value.as_str() {
-                    let value = datenbriefd::Encryption::parse(value);
-                    trace!("set imap encryption to {}", "FIXME");
+                    let value = encryption(value);
+                    trace!("set imap encryption to {}", value);
                     config.Imap.encryption = value;
                 }
             }
@@ -419,6 +711,69 @@ fn main() {
         }
     }
 
+    if let Some(value) = &matches.value_of("imap.sent") {
+        trace!("set imap sent folder to {}", value);
+        config.sent_folder = Some(value.to_string());
+    } else if let Some(toml_config) = &toml_config {
+        if let Some(value) = toml_config.get("imap") {
+            if let Some(value) = value.get("sent") {
+                if let Some(value) = value.as_str() {
+                    trace!("set imap sent folder to {}", value);
+                    config.sent_folder = Some(value.to_string());
+                }
+            }
+        }
+    }
+
+    if let Some(value) = &matches.value_of("imap.inbox") {
+        trace!("set imap inbox to {}", value);
+        config.inbox = value.to_string();
+    } else if let Some(toml_config) = &toml_config {
+        if let Some(value) = toml_config.get("imap") {
+            if let Some(value) = value.get("inbox") {
+                if let Some(value) = value.as_str() {
+                    trace!("set imap inbox to {}", value);
+                    config.inbox = value.to_string();
+                }
+            }
+        }
+    }
+
+    if let Some(value) = &matches.value_of("imap.folder") {
+        trace!("set imap reply folder to {}", value);
+        config.reply_folder = Some(value.to_string());
+    } else if let Some(toml_config) = &toml_config {
+        if let Some(value) = toml_config.get("imap") {
+            if let Some(value) = value.get("folder") {
+                if let Some(value) = value.as_str() {
+                    trace!("set imap reply folder to {}", value);
+                    config.reply_folder = Some(value.to_string());
+                }
+            }
+        }
+    }
+
+    if let Some(toml_config) = &toml_config {
+        if let Some(value) = toml_config.get("imap") {
+            if let Some(value) = value.get("keywords") {
+                if let Some(value) = value.as_bool() {
+                    trace!("set imap keywords to {}", value);
+                    config.reply_keywords = value;
+                }
+            }
+            if let Some(value) = value.get("poll") {
+                if let Some(value) = integer::<u32>("imap.poll", value) {
+                    if value > 0 {
+                        trace!("set imap poll interval to {} seconds", value);
+                        config.poll = chrono::Duration::seconds(value.into());
+                    } else {
+                        warn!("imap.poll has to be at least one second, ignoring it");
+                    }
+                }
+            }
+        }
+    }
+
     if let Some(value) = &matches.value_of("smtp.server") {
         trace!("set smtp server to {}", value);
         config.Smtp.host = value.to_string();
@@ -453,15 +808,15 @@ fn main() {
     }
 
     if let Some(value) = &matches.value_of("smtp.encryption") {
-        let value = datenbriefd::Encryption::parse(value);
-        trace!("set imap control encryption to {}", "FIXME");
+        let value = encryption(value);
+        trace!("set imap control encryption to {}", value);
         config.Smtp.encryption = value;
     } else if let Some(toml_config) = &toml_config {
         if let Some(value) = toml_config.get("smtp") {
             if let Some(value) = value.get("encryption") {
                 if let Some(value) = value.as_str() {
-                    let value = datenbriefd::Encryption::parse(value);
-                    trace!("set smtp encryption to {}", "FIXME");
+                    let value = encryption(value);
+                    trace!("set smtp encryption to {}", value);
                     config.Smtp.encryption = value;
                 }
             }
@@ -496,6 +851,21 @@ fn main() {
         }
     }
 
+    if let Some(toml_config) = &toml_config {
+        if let Some(value) = toml_config.get("control") {
+            tls_config(&mut config.ImapControl, value);
+        }
+        if let Some(value) = toml_config.get("imap") {
+            tls_config(&mut config.Imap, value);
+        }
+        if let Some(value) = toml_config.get("smtp") {
+            tls_config(&mut config.Smtp, value);
+        }
+    }
+    tls_args(&mut config.ImapControl, &matches, "control");
+    tls_args(&mut config.Imap, &matches, "imap");
+    tls_args(&mut config.Smtp, &matches, "smtp");
+
     if let Some(value) = &matches.value_of("interval") {
         let value: Result<usize, std::num::ParseIntError> = value.parse();
         if let Ok(value) = value {
@@ -512,6 +882,288 @@ fn main() {
         }
     }
 
+    if let Some(value) = &matches.value_of("language") {
+        config.language = language(value);
+        trace!("set global language to {}", config.language);
+    } else if let Some(toml_config) = &toml_config {
+        if let Some(value) = toml_config.get("language") {
+            if let Some(value) = value.as_str() {
+                config.language = language(value);
+                trace!("set global language to {}", config.language);
+            }
+        }
+    }
+
+    if let Some(value) = &matches.value_of("max-per-day") {
+        let value: Result<usize, std::num::ParseIntError> = value.parse();
+        if let Ok(value) = value {
+            trace!("set max requests per day to {}", value);
+            config.scheduler.max_per_day = Some(value);
+        } else if let Err(err) = value {
+            warn!("max-per-day is not a usize number: {}", err);
+        }
+    } else if let Some(toml_config) = &toml_config {
+        if let Some(value) = toml_config.get("scheduler") {
+            if let Some(value) = value.get("max-per-day") {
+                if let Some(value) = integer::<usize>("scheduler.max-per-day", value) {
+                    trace!("set max requests per day to {}", value);
+                    config.scheduler.max_per_day = Some(value);
+                }
+            }
+        }
+    }
+
+    if let Some(value) = &matches.value_of("max-per-hour") {
+        let value: Result<usize, std::num::ParseIntError> = value.parse();
+        if let Ok(value) = value {
+            trace!("set max requests per hour to {}", value);
+            config.scheduler.max_per_hour = Some(value);
+        } else if let Err(err) = value {
+            warn!("max-per-hour is not a usize number: {}", err);
+        }
+    } else if let Some(toml_config) = &toml_config {
+        if let Some(value) = toml_config.get("scheduler") {
+            if let Some(value) = value.get("max-per-hour") {
+                if let Some(value) = integer::<usize>("scheduler.max-per-hour", value) {
+                    trace!("set max requests per hour to {}", value);
+                    config.scheduler.max_per_hour = Some(value);
+                }
+            }
+        }
+    }
+
+    if let Some(value) = &matches.value_of("stagger") {
+        let value: Result<u32, std::num::ParseIntError> = value.parse();
+        if let Ok(value) = value {
+            trace!("set stagger window to {} days", value);
+            config.scheduler.stagger_days = value;
+        } else if let Err(err) = value {
+            warn!("stagger is not a u32 number: {}", err);
+        }
+    } else if let Some(toml_config) = &toml_config {
+        if let Some(value) = toml_config.get("scheduler") {
+            if let Some(value) = value.get("stagger") {
+                if let Some(value) = integer::<u32>("scheduler.stagger", value) {
+                    trace!("set stagger window to {} days", value);
+                    config.scheduler.stagger_days = value;
+                }
+            }
+        }
+    }
+
+    {
+        let table = toml_config.as_ref().and_then(|v| v.get("transport"));
+        let get = |name: &str| table.and_then(|v| v.get(name)).and_then(|v| v.as_str());
+        let kind = matches.value_of("transport").or_else(|| get("type"));
+        let sendmail = matches.value_of("sendmail").or_else(|| get("sendmail"));
+        let maildir = matches.value_of("maildir").or_else(|| get("maildir"));
+        if let Some(kind) = kind {
+            match datenbriefd::transport::Backend::parse(kind, sendmail, maildir) {
+                Ok(value) => {
+                    trace!("set transport to {:?}", value);
+                    config.transport = value;
+                }
+                Err(err) => {
+                    error!("{}", err);
+                    std::process::exit(1);
+                }
+            }
+        }
+    }
+
+    if let Some(value) = &matches.value_of("reminders") {
+        let value: Result<u8, std::num::ParseIntError> = value.parse();
+        if let Ok(value) = value {
+            trace!("set max reminders to {}", value);
+            config.scheduler.max_reminders = value;
+        } else if let Err(err) = value {
+            warn!("reminders is not a u8 number: {}", err);
+        }
+    } else if let Some(toml_config) = &toml_config {
+        if let Some(value) = toml_config.get("reminders") {
+            if let Some(value) = value.get("max") {
+                if let Some(value) = integer::<u8>("reminders.max", value) {
+                    trace!("set max reminders to {}", value);
+                    config.scheduler.max_reminders = value;
+                }
+            }
+        }
+    }
+
+    if let Some(toml_config) = &toml_config {
+        if let Some(value) = toml_config.get("reminders") {
+            if value.get("after").is_some() {
+                warn!(
+                    "reminders.after is ignored, the first reminder is sent at the legal deadline"
+                );
+            }
+            if let Some(value) = value.get("grace") {
+                if let Some(value) = integer::<u32>("reminders.grace", value) {
+                    trace!("set first reminder to {} days after the deadline", value);
+                    config.scheduler.reminder_grace = value;
+                }
+            }
+            if let Some(value) = value.get("interval") {
+                if let Some(value) = integer::<u32>("reminders.interval", value) {
+                    trace!("set reminder interval to {} days", value);
+                    config.scheduler.reminder_interval = value;
+                }
+            }
+        }
+    }
+
+    if let Some(value) = &matches.value_of("archive") {
+        trace!("set archive dir to {}", value);
+        config.archive.dir = value.to_string();
+    } else if let Some(toml_config) = &toml_config {
+        if let Some(value) = toml_config.get("archive") {
+            if let Some(value) = value.as_str() {
+                trace!("set archive dir to {}", value);
+                config.archive.dir = value.to_string();
+            }
+        }
+    }
+
+    if let Some(toml_config) = &toml_config {
+        if let Some(value) = toml_config.get("autoreply") {
+            if let Some(value) = value.get("patterns") {
+                config
+                    .autoreply
+                    .patterns
+                    .extend(regexes("autoreply.patterns", value));
+            }
+            if let Some(value) = value.get("tickets") {
+                config
+                    .autoreply
+                    .tickets
+                    .extend(regexes("autoreply.tickets", value));
+            }
+        }
+    }
+
+    // own rules are tested before the built in ones
+    if let Some(toml_config) = &toml_config {
+        if let Some(value) = toml_config.get("classify") {
+            let mut rules = Vec::new();
+            for outcome in datenbriefd::classify::OUTCOMES.iter() {
+                if let Some(value) = value.get(outcome.to_string()) {
+                    let name = format!("classify.{}", outcome);
+                    for pattern in regexes(&name, value) {
+                        rules.push((*outcome, pattern));
+                    }
+                }
+            }
+            rules.append(&mut config.classifier.rules);
+            config.classifier.rules = rules;
+        }
+    }
+
+    if let Some(toml_config) = &toml_config {
+        if let Some(value) = toml_config.get("digest") {
+            if let Some(value) = value.get("period") {
+                if let Some(value) = value.as_str() {
+                    match datenbriefd::digest::Period::parse(value) {
+                        Ok(value) => {
+                            trace!("set digest period to {:?}", value);
+                            config.digest.period = Some(value);
+                        }
+                        Err(err) => {
+                            error!("{}", err);
+                            std::process::exit(1);
+                        }
+                    }
+                }
+            }
+            if let Some(value) = value.get("to") {
+                if let Some(value) = value.as_str() {
+                    trace!("send digest to {}", value);
+                    config.digest.to = Some(value.to_string());
+                }
+            }
+            if let Some(value) = value.get("from") {
+                if let Some(value) = value.as_str() {
+                    trace!("send digest from {}", value);
+                    config.digest.from = Some(value.to_string());
+                }
+            }
+        }
+    }
+
+    if let Some(toml_config) = &toml_config {
+        if let Some(value) = toml_config.get("complaint") {
+            if let Some(value) = value.get("send") {
+                if let Some(value) = value.as_bool() {
+                    trace!("set sending complaints to the authority to {}", value);
+                    config.complaint.send = value;
+                }
+            }
+            if let Some(value) = value.get("authority") {
+                if let Some(value) = value.as_str() {
+                    trace!("set supervisory authority to {}", value);
+                    config.complaint.authority = Some(value.to_string());
+                }
+            }
+            if let Some(value) = value.get("to") {
+                if let Some(value) = value.as_str() {
+                    trace!("send complaint drafts to {}", value);
+                    config.complaint.to = Some(value.to_string());
+                }
+            }
+            if let Some(value) = value.get("from") {
+                if let Some(value) = value.as_str() {
+                    trace!("send complaint drafts from {}", value);
+                    config.complaint.from = Some(value.to_string());
+                }
+            }
+        }
+    }
+
+    if let Some(value) = &matches.value_of("spool-dir") {
+        trace!("set spool dir to {}", value);
+        config.spool.dir = value.to_string();
+    } else if let Some(toml_config) = &toml_config {
+        if let Some(value) = toml_config.get("spool") {
+            if let Some(value) = value.get("dir") {
+                if let Some(value) = value.as_str() {
+                    trace!("set spool dir to {}", value);
+                    config.spool.dir = value.to_string();
+                }
+            }
+        }
+    }
+
+    if let Some(value) = &matches.value_of("max-attempts") {
+        let value: Result<u32, std::num::ParseIntError> = value.parse();
+        if let Ok(value) = value {
+            trace!("set max attempts to {}", value);
+            config.spool.max_attempts = value;
+        } else if let Err(err) = value {
+            warn!("max-attempts is not a u32 number: {}", err);
+        }
+    } else if let Some(toml_config) = &toml_config {
+        if let Some(value) = toml_config.get("spool") {
+            if let Some(value) = value.get("attempts") {
+                if let Some(value) = integer::<u32>("spool.attempts", value) {
+                    trace!("set max attempts to {}", value);
+                    config.spool.max_attempts = value;
+                }
+            }
+        }
+    }
+
+    if let Some(toml_config) = &toml_config {
+        if let Some(value) = toml_config.get("spool") {
+            if let Some(value) = value.get("backoff") {
+                if let Some(value) = value.as_integer() {
+                    // longer delays are capped at one day anyway
+                    let value = value.clamp(0, 24 * 60);
+                    trace!("set spool backoff to {} minutes", value);
+                    config.spool.backoff = chrono::Duration::minutes(value);
+                }
+            }
+        }
+    }
+
     if let Some(value) = &matches.value_of("time-file") {
         trace!("set time file to {}", value);
         config.time_file = value.to_string();
@@ -524,6 +1176,48 @@ fn main() {
         }
     }
 
+    if matches.is_present("socket") {
+        let value = match matches.value_of("socket") {
+            Some(value) => value.to_string(),
+            None => datenbriefd::api::default_socket(),
+        };
+        trace!("set api socket to {}", value);
+        config.socket = Some(value);
+    } else if let Some(toml_config) = &toml_config {
+        if let Some(value) = toml_config.get("api") {
+            if let Some(value) = value.get("socket") {
+                // `socket = true` listens on the socket datenbriefctl uses by default
+                let value = match value {
+                    toml::Value::Boolean(true) => Some(datenbriefd::api::default_socket()),
+                    value => value.as_str().map(|v| v.to_string()),
+                };
+                if let Some(value) = value {
+                    trace!("set api socket to {}", value);
+                    config.socket = Some(value);
+                }
+            }
+        }
+    }
+
+    if let Some(value) = &matches.value_of("http") {
+        trace!("set http api address to {}", value);
+        config.http = Some(value.to_string());
+    } else if let Some(toml_config) = &toml_config {
+        if let Some(value) = toml_config.get("api") {
+            if let Some(value) = value.get("http") {
+                // a port alone listens on localhost
+                let value = match value {
+                    toml::Value::Integer(port) => Some(port.to_string()),
+                    value => value.as_str().map(|v| v.to_string()),
+                };
+                if let Some(value) = value {
+                    trace!("set http api address to {}", value);
+                    config.http = Some(value);
+                }
+            }
+        }
+    }
+
     // parse companies
     if let Some(value) = &matches.value_of("company-name") {
         let mut comp: Company = Company::new();
@@ -561,53 +1255,165 @@ fn main() {
         config.companies.push(comp);
     }
 
-    if let Some(toml_config) = &toml_config {
-        if let Some(value) = toml_config.get("companies") {
-            if let Some(value) = value.as_table() {
-                for (k, v) in value.iter() {
-                    let mut company = Company::new();
-                    company.name = k.to_string();
-                    trace!("set companie name to '{}'", k);
-                    if let Some(value) = v.get("alias") {
-                        if let Some(value) = value.as_str() {
-                            trace!("set alias to '{}'", value);
-                            company.alias = value.to_string();
-                        }
-                    }
-                    if let Some(value) = v.get("mail") {
-                        if let Some(value) = value.as_str() {
-                            trace!("set mail to '{}'", value);
-                            company.mail = value.to_string();
-                        }
-                    }
-                    if let Some(value) = v.get("name") {
-                        if let Some(value) = value.as_str() {
-                            trace!("set own name to '{}'", value);
-                            company.onw_name = value.to_string();
-                        }
-                    }
-                    if let Some(value) = v.get("interval") {
-                        if let Some(value) = value.as_integer() {
-                            trace!("set interval to {} days", value);
-                            company.interval = value as usize;
-                        }
-                    } else if let Some(interval) = interval {
-                        trace!("set interval to global default ({})", interval);
-                        company.interval = interval;
-                    }
-                    config.companies.push(company);
-                }
-            }
-        }
-    } else {
+    config.interval = interval;
+    config.command_line = config.companies.clone();
+
+    config
+        .companies
+        .extend(conf::companies(&companies_config, interval));
+    if config.companies.is_empty() {
         eprintln!("no config for companies supplied");
     }
 
+    let status = matches.subcommand_matches("status").is_some();
     drop(matches); // removed parsed arguments
     drop(toml_config);
+    if status {
+        config.load();
+        print!("{}", config.status());
+        return;
+    }
     config.run();
 }
 
+/// parse a letter language, exits on unknown values
+fn language(value: &str) -> datenbriefd::template::Language {
+    match datenbriefd::template::Language::parse(value) {
+        Ok(value) => value,
+        Err(err) => {
+            error!("{}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// parse an encryption schema, exits on unknown values
+fn encryption(value: &str) -> Encryption {
+    match Encryption::parse(value) {
+        Ok(value) => value,
+        Err(err) => {
+            error!("{}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// compile a toml array of regular expressions, exits on invalid patterns
+fn regexes(name: &str, value: &toml::Value) -> Vec<regex::Regex> {
+    let mut out = Vec::new();
+    for value in value.as_array().into_iter().flatten() {
+        if let Some(value) = value.as_str() {
+            match regex::Regex::new(value) {
+                Ok(value) => {
+                    trace!("add {} pattern {}", name, value);
+                    out.push(value);
+                }
+                Err(err) => {
+                    error!("invalid {} pattern: {}", name, err);
+                    std::process::exit(1);
+                }
+            }
+        }
+    }
+    out
+}
+
+/// read the tls options of a server table
+/// integer setting `name` if it is one and fits into `T`, warns and skips it otherwise
+fn integer<T: std::convert::TryFrom<i64>>(name: &str, value: &toml::Value) -> Option<T> {
+    let integer = value.as_integer();
+    let result = integer.and_then(|v| T::try_from(v).ok());
+    if result.is_none() {
+        warn!(
+            "{} is out of range or not a number: {}, ignoring it",
+            name, value
+        );
+    }
+    result
+}
+
+fn tls_config(server: &mut ServerConfig, value: &toml::Value) {
+    if let Some(value) = value.get("ca") {
+        if let Some(value) = value.as_str() {
+            trace!("set ca file of {} to {}", server.host, value);
+            server.ca_file = Some(value.to_string());
+        }
+    }
+    if let Some(value) = value.get("cert") {
+        if let Some(value) = value.as_str() {
+            trace!("set client certificate of {} to {}", server.host, value);
+            server.client_cert = Some(value.to_string());
+        }
+    }
+    if let Some(value) = value.get("key") {
+        if let Some(value) = value.as_str() {
+            trace!("set client key of {} to {}", server.host, value);
+            server.client_key = Some(value.to_string());
+        }
+    }
+    if let Some(value) = value.get("min-tls") {
+        if let Some(value) = value.as_str() {
+            match datenbriefd::parse_tls_version(value) {
+                Ok(version) => {
+                    trace!(
+                        "set minimum tls version of {} to {:?}",
+                        server.host,
+                        version
+                    );
+                    server.min_tls = Some(version);
+                }
+                Err(err) => {
+                    error!("{}", err);
+                    std::process::exit(1);
+                }
+            }
+        }
+    }
+    if let Some(value) = value.get("fingerprint") {
+        if let Some(value) = value.as_str() {
+            trace!("pin certificate of {} to {}", server.host, value);
+            server.fingerprint = Some(value.to_string());
+        }
+    }
+}
+
+/// tls options of `server` given on the command line as `--<prefix>-ca` and so on
+fn tls_args(server: &mut ServerConfig, matches: &clap::ArgMatches, prefix: &str) {
+    let arg = |name: &str| matches.value_of(format!("{}.{}", prefix, name));
+    if let Some(value) = arg("ca") {
+        trace!("set ca file of {} to {}", server.host, value);
+        server.ca_file = Some(value.to_string());
+    }
+    if let Some(value) = arg("cert") {
+        trace!("set client certificate of {} to {}", server.host, value);
+        server.client_cert = Some(value.to_string());
+    }
+    if let Some(value) = arg("key") {
+        trace!("set client key of {} to {}", server.host, value);
+        server.client_key = Some(value.to_string());
+    }
+    if let Some(value) = arg("min-tls") {
+        match datenbriefd::parse_tls_version(value) {
+            Ok(version) => {
+                trace!(
+                    "set minimum tls version of {} to {:?}",
+                    server.host,
+                    version
+                );
+                server.min_tls = Some(version);
+            }
+            Err(err) => {
+                error!("{}", err);
+                std::process::exit(1);
+            }
+        }
+    }
+    if let Some(value) = arg("fingerprint") {
+        trace!("pin certificate of {} to {}", server.host, value);
+        server.fingerprint = Some(value.to_string());
+    }
+}
+
 /// create completion
 #[cfg(feature = "completion")]
 fn completion(args: &clap::ArgMatches, app: &mut App) {
@@ -619,13 +1425,13 @@ fn completion(args: &clap::ArgMatches, app: &mut App) {
     use clap::Shell;
     let shell_l = shell.to_lowercase();
     let shell: Shell;
-    if shell_l == "fish".to_string() {
+    if shell_l == "fish" {
         shell = Shell::Fish;
-    } else if shell_l == "zsh".to_string() {
+    } else if shell_l == "zsh" {
         shell = Shell::Zsh;
-    } else if shell_l == "powershell".to_string() {
+    } else if shell_l == "powershell" {
         shell = Shell::PowerShell;
-    } else if shell_l == "elvish".to_string() {
+    } else if shell_l == "elvish" {
         shell = Shell::Elvish;
     } else {
         shell = Shell::Bash;
@@ -636,12 +1442,10 @@ fn completion(args: &clap::ArgMatches, app: &mut App) {
     use std::io::Write;
 
     let mut path = BufWriter::new(match args.value_of("out") {
-        Some(x) => Box::new(
-            File::create(&std::path::Path::new(x)).unwrap_or_else(|err| {
-                eprintln!("Error opening file: {}", err);
-                std::process::exit(1);
-            }),
-        ) as Box<dyn Write>,
+        Some(x) => Box::new(File::create(std::path::Path::new(x)).unwrap_or_else(|err| {
+            eprintln!("Error opening file: {}", err);
+            std::process::exit(1);
+        })) as Box<dyn Write>,
         None => Box::new(std::io::stdout()) as Box<dyn Write>,
     });
 
@@ -0,0 +1,405 @@
+use chrono::prelude::*;
+
+/// deeper multipart and message/rfc822 entities are kept as opaque bodies
+pub const MAX_DEPTH: usize = 16;
+
+/// parsed mime entity, bodies are already transfer decoded
+#[derive(Debug, Clone)]
+pub struct Part {
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    /// sub parts of multipart and message/rfc822 entities
+    pub parts: Vec<Part>,
+}
+
+impl Part {
+    /// parse a raw message or body part
+    pub fn parse(raw: &[u8]) -> Self {
+        Self::parse_nested(raw, 0)
+    }
+
+    /// parse a part nested `depth` entities deep
+    fn parse_nested(raw: &[u8], depth: usize) -> Self {
+        let nested = depth < MAX_DEPTH;
+        let (head, body) = split_head(raw);
+        let headers = parse_headers(&String::from_utf8_lossy(head));
+        let mut part = Self {
+            headers,
+            body: Vec::new(),
+            parts: Vec::new(),
+        };
+
+        let content_type = part.content_type();
+        if content_type.starts_with("multipart/") {
+            if let Some(boundary) = part.param("Content-Type", "boundary").filter(|_| nested) {
+                part.parts = split_multipart(body, &boundary)
+                    .into_iter()
+                    .map(|v| Part::parse_nested(v, depth + 1))
+                    .collect();
+            }
+            part.body = body.to_vec();
+            return part;
+        }
+
+        part.body = match part
+            .header("Content-Transfer-Encoding")
+            .map(|v| v.trim().to_lowercase())
+            .as_deref()
+        {
+            Some("base64") => decode_base64(body),
+            Some("quoted-printable") => decode_qp(body),
+            _ => body.to_vec(),
+        };
+        if nested && (content_type == "message/rfc822" || content_type == "message/global") {
+            let inner = Part::parse_nested(&part.body, depth + 1);
+            part.parts.push(inner);
+        }
+        part
+    }
+
+    /// first value of header `name`
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// the date header, if it is a valid rfc 2822 date
+    pub fn date(&self) -> Option<DateTime<Utc>> {
+        self.header("Date")
+            .and_then(|v| DateTime::parse_from_rfc2822(v.trim()).ok())
+            .map(|v| v.with_timezone(&Utc))
+    }
+
+    /// all values of header `name`
+    pub fn headers(&self, name: &str) -> Vec<&str> {
+        self.headers
+            .iter()
+            .filter(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+            .collect()
+    }
+
+    /// header `name` with encoded words decoded
+    pub fn decoded(&self, name: &str) -> Option<String> {
+        self.header(name).map(decode_words)
+    }
+
+    /// lower case mime type without parameters
+    pub fn content_type(&self) -> String {
+        self.header("Content-Type")
+            .and_then(|v| v.split(';').next())
+            .map(|v| v.trim().to_lowercase())
+            .filter(|v| !v.is_empty())
+            .unwrap_or_else(|| String::from("text/plain"))
+    }
+
+    /// parameter `name` of header `header`, like the boundary of a Content-Type
+    pub fn param(&self, header: &str, name: &str) -> Option<String> {
+        let value = self.header(header)?;
+        for param in value.split(';').skip(1) {
+            let mut split = param.splitn(2, '=');
+            let key = split.next()?.trim();
+            let value = split.next().unwrap_or_default().trim();
+            if key.eq_ignore_ascii_case(name) || key.eq_ignore_ascii_case(&format!("{}*", name)) {
+                let value = value.trim_matches('"');
+                // rfc 2231 encoded parameter
+                if key.ends_with('*') {
+                    if let Some(value) = value.splitn(3, '\'').nth(2) {
+                        return Some(String::from_utf8_lossy(&percent_decode(value)).to_string());
+                    }
+                }
+                return Some(decode_words(value));
+            }
+        }
+        None
+    }
+
+    /// file name of an attachment
+    pub fn filename(&self) -> Option<String> {
+        self.param("Content-Disposition", "filename")
+            .or_else(|| self.param("Content-Type", "name"))
+            .filter(|v| !v.is_empty())
+    }
+
+    /// all leaf parts, depth first
+    pub fn leaves(&self) -> Vec<&Part> {
+        if self.parts.is_empty() {
+            return vec![self];
+        }
+        self.parts.iter().flat_map(|v| v.leaves()).collect()
+    }
+
+    /// all parts including this one, depth first
+    pub fn walk(&self) -> Vec<&Part> {
+        let mut parts = vec![self];
+        for v in self.parts.iter() {
+            parts.extend(v.walk());
+        }
+        parts
+    }
+
+    /// body decoded with the charset of the part
+    pub fn body_text(&self) -> String {
+        let charset = self
+            .param("Content-Type", "charset")
+            .unwrap_or_default()
+            .to_lowercase();
+        decode_charset(&self.body, &charset)
+    }
+
+    /// plain text of the message, falls back to html with the tags removed
+    pub fn text(&self) -> String {
+        let leaves = self.leaves();
+        let plain = leaves
+            .iter()
+            .find(|v| v.content_type() == "text/plain" && !v.is_attachment());
+        if let Some(plain) = plain {
+            return plain.body_text();
+        }
+        leaves
+            .iter()
+            .find(|v| v.content_type() == "text/html" && !v.is_attachment())
+            .map(|v| strip_tags(&v.body_text()))
+            .unwrap_or_default()
+    }
+
+    /// whether the part is meant as attachment instead of message text
+    pub fn is_attachment(&self) -> bool {
+        let disposition = self
+            .header("Content-Disposition")
+            .map(|v| v.trim().to_lowercase())
+            .unwrap_or_default();
+        disposition.starts_with("attachment") || self.filename().is_some()
+    }
+
+    /// message ids in header `name`, without the angle brackets
+    pub fn message_ids(&self, name: &str) -> Vec<String> {
+        self.headers(name)
+            .iter()
+            .flat_map(|v| message_ids(v))
+            .collect()
+    }
+}
+
+/// extract all `<id>` from `value`
+pub fn message_ids(value: &str) -> Vec<String> {
+    value
+        .split('<')
+        .skip(1)
+        .filter_map(|v| v.split('>').next())
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .collect()
+}
+
+/// address part of `Name <addr>` or a bare address, lower cased
+pub fn address(value: &str) -> String {
+    let value = match (value.rfind('<'), value.rfind('>')) {
+        (Some(start), Some(end)) if start < end => &value[start + 1..end],
+        _ => value,
+    };
+    value.trim().trim_matches('"').to_lowercase()
+}
+
+fn split_head(raw: &[u8]) -> (&[u8], &[u8]) {
+    // part without any headers
+    if raw.starts_with(b"\r\n") {
+        return (&[], &raw[2..]);
+    }
+    if raw.starts_with(b"\n") {
+        return (&[], &raw[1..]);
+    }
+    for i in 0..raw.len() {
+        if raw[i..].starts_with(b"\r\n\r\n") {
+            return (&raw[..i], &raw[i + 4..]);
+        }
+        if raw[i..].starts_with(b"\n\n") {
+            return (&raw[..i], &raw[i + 2..]);
+        }
+    }
+    (raw, &[])
+}
+
+/// parse and unfold a header block
+pub fn parse_headers(head: &str) -> Vec<(String, String)> {
+    let mut headers: Vec<(String, String)> = Vec::new();
+    for line in head.lines() {
+        let line = line.trim_end_matches('\r');
+        if line.starts_with(' ') || line.starts_with('\t') {
+            if let Some(last) = headers.last_mut() {
+                last.1.push(' ');
+                last.1.push_str(line.trim());
+            }
+            continue;
+        }
+        if let Some(pos) = line.find(':') {
+            headers.push((
+                line[..pos].trim().to_string(),
+                line[pos + 1..].trim().to_string(),
+            ));
+        }
+    }
+    headers
+}
+
+pub(crate) fn split_multipart<'a>(body: &'a [u8], boundary: &str) -> Vec<&'a [u8]> {
+    let delimiter = format!("--{}", boundary);
+    let mut parts = Vec::new();
+    let mut start: Option<usize> = None;
+    let mut pos = 0;
+    while pos < body.len() {
+        let end = body[pos..]
+            .iter()
+            .position(|v| *v == b'\n')
+            .map_or(body.len(), |v| pos + v + 1);
+        let line = &body[pos..end];
+        let trimmed = String::from_utf8_lossy(line);
+        let trimmed = trimmed.trim_end();
+        if trimmed.starts_with(&delimiter) {
+            if let Some(start) = start {
+                // the line break before the delimiter belongs to the delimiter
+                let mut part_end = pos;
+                if part_end > start && body[part_end - 1] == b'\n' {
+                    part_end -= 1;
+                }
+                if part_end > start && body[part_end - 1] == b'\r' {
+                    part_end -= 1;
+                }
+                parts.push(&body[start..part_end]);
+            }
+            if trimmed[delimiter.len()..].starts_with("--") {
+                return parts;
+            }
+            start = Some(end);
+        }
+        pos = end;
+    }
+    if let Some(start) = start {
+        parts.push(&body[start..]);
+    }
+    parts
+}
+
+fn decode_base64(body: &[u8]) -> Vec<u8> {
+    let clean: Vec<u8> = body
+        .iter()
+        .cloned()
+        .filter(|v| !v.is_ascii_whitespace())
+        .collect();
+    base64::decode(&clean).unwrap_or_else(|err| {
+        debug!("invalid base64 body: {}", err);
+        body.to_vec()
+    })
+}
+
+fn decode_qp(body: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(body.len());
+    let mut i = 0;
+    while i < body.len() {
+        if body[i] == b'=' {
+            // soft line break
+            if body[i + 1..].starts_with(b"\r\n") {
+                i += 3;
+                continue;
+            }
+            if body[i + 1..].starts_with(b"\n") {
+                i += 2;
+                continue;
+            }
+            if let Some(v) = body
+                .get(i + 1..i + 3)
+                .and_then(|v| std::str::from_utf8(v).ok())
+                .and_then(|v| u8::from_str_radix(v, 16).ok())
+            {
+                out.push(v);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(body[i]);
+        i += 1;
+    }
+    out
+}
+
+fn percent_decode(value: &str) -> Vec<u8> {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            if let Some(v) = value
+                .get(i + 1..i + 3)
+                .and_then(|v| u8::from_str_radix(v, 16).ok())
+            {
+                out.push(v);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    out
+}
+
+/// decode bytes in `charset`, unknown charsets are read as utf-8
+pub fn decode_charset(data: &[u8], charset: &str) -> String {
+    match charset {
+        "iso-8859-1" | "iso-8859-15" | "latin1" | "windows-1252" | "cp1252" | "us-ascii" => {
+            data.iter().map(|v| *v as char).collect()
+        }
+        _ => String::from_utf8_lossy(data).to_string(),
+    }
+}
+
+/// decode rfc 2047 encoded words
+pub fn decode_words(value: &str) -> String {
+    let mut out = String::new();
+    let mut rest = value;
+    let mut last_was_word = false;
+    while let Some(start) = rest.find("=?") {
+        let word = rest[start + 2..].splitn(4, '?').collect::<Vec<&str>>();
+        if word.len() < 4 || !word[3].starts_with('=') {
+            break;
+        }
+        let (charset, encoding, text) = (word[0], word[1], word[2]);
+        let consumed = start + charset.len() + encoding.len() + text.len() + 6;
+        let between = &rest[..start];
+        // whitespace between two encoded words is dropped
+        if !(last_was_word && between.trim().is_empty()) {
+            out.push_str(between);
+        }
+        let data = match encoding.to_lowercase().as_str() {
+            "b" => base64::decode(text).unwrap_or_default(),
+            _ => decode_qp(text.replace('_', " ").as_bytes()),
+        };
+        let charset = charset.split('*').next().unwrap_or_default().to_lowercase();
+        out.push_str(&decode_charset(&data, &charset));
+        rest = &rest[consumed..];
+        last_was_word = true;
+    }
+    out.push_str(rest);
+    out
+}
+
+/// crude html to text conversion
+pub fn strip_tags(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for v in html.chars() {
+        match v {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            _ if !in_tag => out.push(v),
+            _ => (),
+        }
+    }
+    out.replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+}
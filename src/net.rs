@@ -0,0 +1,205 @@
+use super::{sha256, Encryption, ServerConfig};
+use native_tls::{Certificate, Identity, TlsConnector, TlsStream};
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{IpAddr, TcpStream},
+    time::Duration,
+};
+
+/// read timeout of all mail server connections
+pub const READ_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// refuse to send credentials unencrypted to anything but the local host
+pub fn check_cleartext(config: &ServerConfig) -> io::Result<()> {
+    if let Encryption::none = config.encryption {
+        let loopback = config.host.eq_ignore_ascii_case("localhost")
+            || config.host.parse::<IpAddr>().is_ok_and(|v| v.is_loopback());
+        if !loopback {
+            return Err(other(format!(
+                "refusing to send credentials to {} without encryption",
+                config.host
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// connection to a mail server, either plain or wrapped in tls
+pub enum Stream {
+    Plain(TcpStream),
+    Tls(Box<TlsStream<TcpStream>>),
+}
+
+impl Stream {
+    /// open a tcp connection and start tls if `config.encryption` is `tls`
+    pub fn connect(config: &ServerConfig) -> io::Result<Self> {
+        debug!("connecting to {}:{}", config.host, config.port);
+        let tcp = TcpStream::connect((config.host.as_str(), config.port))?;
+        tcp.set_read_timeout(Some(READ_TIMEOUT))?;
+        match config.encryption {
+            Encryption::tls => Stream::Plain(tcp).upgrade(config),
+            Encryption::starttls | Encryption::none => Ok(Stream::Plain(tcp)),
+        }
+    }
+
+    /// start tls on a plain connection
+    pub fn upgrade(self, config: &ServerConfig) -> io::Result<Self> {
+        let tcp = match self {
+            Stream::Plain(tcp) => tcp,
+            Stream::Tls(_) => return Ok(self),
+        };
+        let tls = connector(config)?
+            .connect(&config.host, tcp)
+            .map_err(other)?;
+
+        if let Some(pin) = &config.fingerprint {
+            let cert = tls
+                .peer_certificate()
+                .map_err(other)?
+                .ok_or_else(|| other("server did not send a certificate"))?;
+            let fingerprint = sha256(&cert.to_der().map_err(other)?);
+            if !fingerprint.eq_ignore_ascii_case(&pin.replace(':', "")) {
+                return Err(other(format!(
+                    "certificate fingerprint {} of {} does not match pinned {}",
+                    fingerprint, config.host, pin
+                )));
+            }
+            debug!("certificate of {} matches pinned fingerprint", config.host);
+        }
+        Ok(Stream::Tls(Box::new(tls)))
+    }
+
+    /// set the read timeout of the underlying socket
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        match self {
+            Stream::Plain(tcp) => tcp.set_read_timeout(timeout),
+            Stream::Tls(tls) => tls.get_ref().set_read_timeout(timeout),
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Stream::Plain(tcp) => tcp.read(buf),
+            Stream::Tls(tls) => tls.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Stream::Plain(tcp) => tcp.write(buf),
+            Stream::Tls(tls) => tls.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stream::Plain(tcp) => tcp.flush(),
+            Stream::Tls(tls) => tls.flush(),
+        }
+    }
+}
+
+/// build the tls connector described by `config`
+fn connector(config: &ServerConfig) -> io::Result<TlsConnector> {
+    let mut builder = TlsConnector::builder();
+    if let Some(ca_file) = &config.ca_file {
+        for cert in Certificate::stack_from_pem(&fs::read(ca_file)?).map_err(other)? {
+            builder.add_root_certificate(cert);
+        }
+    }
+    match (&config.client_cert, &config.client_key) {
+        (Some(cert), Some(key)) => {
+            let identity =
+                Identity::from_pkcs8(&fs::read(cert)?, &fs::read(key)?).map_err(other)?;
+            builder.identity(identity);
+        }
+        (None, None) => (),
+        _ => return Err(other("client certificate and key have to be set together")),
+    }
+    if config.min_tls.is_some() {
+        builder.min_protocol_version(config.min_tls);
+    }
+    // a pinned certificate is checked after the handshake instead
+    if config.fingerprint.is_some() {
+        builder.danger_accept_invalid_certs(true);
+        builder.danger_accept_invalid_hostnames(true);
+    }
+    builder.build().map_err(other)
+}
+
+/// line based connection used by the smtp and imap clients
+pub struct Connection {
+    stream: BufReader<Stream>,
+}
+
+impl Connection {
+    pub fn new(stream: Stream) -> Self {
+        Self {
+            stream: BufReader::new(stream),
+        }
+    }
+
+    /// start tls, the buffer has to be empty at this point
+    pub fn upgrade(self, config: &ServerConfig) -> io::Result<Self> {
+        let stream = self.stream.into_inner().upgrade(config)?;
+        Ok(Self::new(stream))
+    }
+
+    /// read one line without the trailing CRLF
+    pub fn read_line(&mut self) -> io::Result<String> {
+        let mut line = Vec::new();
+        if self.stream.read_until(b'\n', &mut line)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "connection closed by server",
+            ));
+        }
+        while line.ends_with(b"\n") || line.ends_with(b"\r") {
+            line.pop();
+        }
+        // servers may send 8 bit data outside of literals
+        let line = String::from_utf8_lossy(&line).to_string();
+        trace!("< {}", line);
+        Ok(line)
+    }
+
+    /// read exactly `len` bytes
+    pub fn read_exact(&mut self, len: usize) -> io::Result<Vec<u8>> {
+        let mut data = vec![0; len];
+        self.stream.read_exact(&mut data)?;
+        Ok(data)
+    }
+
+    /// write a line and append CRLF
+    pub fn write_line(&mut self, line: &str) -> io::Result<()> {
+        trace!("> {}", line);
+        let stream = self.stream.get_mut();
+        stream.write_all(line.as_bytes())?;
+        stream.write_all(b"\r\n")?;
+        stream.flush()
+    }
+
+    /// write raw data
+    pub fn write_all(&mut self, data: &[u8]) -> io::Result<()> {
+        let stream = self.stream.get_mut();
+        stream.write_all(data)?;
+        stream.flush()
+    }
+
+    pub fn get_ref(&self) -> &Stream {
+        self.stream.get_ref()
+    }
+}
+
+/// wrap any error into an `io::Error`
+pub(crate) fn other<E>(err: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::other(err)
+}
@@ -0,0 +1,97 @@
+use chrono::{prelude::*, Duration};
+
+/// right of the data subject a request exercises
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Right {
+    /// Art. 15, sent periodically
+    #[default]
+    Access,
+    /// Art. 16, correct inaccurate data
+    Rectification,
+    /// Art. 17, delete the data
+    Erasure,
+    /// Art. 18, only store the data
+    Restriction,
+    /// Art. 20, hand out the data in a machine readable format
+    Portability,
+    /// Art. 21, stop processing the data
+    Objection,
+}
+
+/// all rights requests can be sent for
+pub const RIGHTS: [Right; 6] = [
+    Right::Access,
+    Right::Rectification,
+    Right::Erasure,
+    Right::Restriction,
+    Right::Portability,
+    Right::Objection,
+];
+
+impl Right {
+    /// parse a right by name or article, like `erasure` or `art17`
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let value = value.to_lowercase();
+        let article = value
+            .trim_start_matches("art")
+            .trim_start_matches('.')
+            .trim();
+        match (value.as_str(), article) {
+            ("access", _) | (_, "15") => Ok(Right::Access),
+            ("rectification", _) | (_, "16") => Ok(Right::Rectification),
+            ("erasure", _) | (_, "17") => Ok(Right::Erasure),
+            ("restriction", _) | (_, "18") => Ok(Right::Restriction),
+            ("portability", _) | (_, "20") => Ok(Right::Portability),
+            ("objection", _) | (_, "21") => Ok(Right::Objection),
+            _ => Err(format!("unknown request type '{}'", value)),
+        }
+    }
+
+    /// article of the GDPR granting the right
+    pub fn article(self) -> u8 {
+        match self {
+            Right::Access => 15,
+            Right::Rectification => 16,
+            Right::Erasure => 17,
+            Right::Restriction => 18,
+            Right::Portability => 20,
+            Right::Objection => 21,
+        }
+    }
+
+    /// whether the company answers with a copy of the data instead of a confirmation
+    pub fn returns_data(self) -> bool {
+        matches!(self, Right::Access | Right::Portability)
+    }
+
+    /// whether the letter is pointless without details from the operator
+    pub fn needs_details(self) -> bool {
+        self == Right::Rectification
+    }
+
+    /// when to remind of a request sent at `sent` which has to be answered by `deadline`
+    ///
+    /// Rectification, erasure, restriction and objection have to be acted on without undue
+    /// delay, so their reminders start halfway to the deadline.
+    pub fn first_reminder(self, sent: DateTime<Utc>, deadline: DateTime<Utc>) -> DateTime<Utc> {
+        if self.returns_data() {
+            deadline
+        } else {
+            sent + Duration::days((deadline - sent).num_days() / 2)
+        }
+    }
+}
+
+impl std::fmt::Display for Right {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            Right::Access => "access",
+            Right::Rectification => "rectification",
+            Right::Erasure => "erasure",
+            Right::Restriction => "restriction",
+            Right::Portability => "portability",
+            Right::Objection => "objection",
+        };
+        write!(f, "{}", name)
+    }
+}
@@ -0,0 +1,196 @@
+use super::{classify::Action, Company};
+use chrono::{prelude::*, Duration};
+
+/// policy deciding when requests are sent
+#[derive(Debug, Clone)]
+pub struct Scheduler {
+    /// maximum number of requests sent in 24 hours
+    pub max_per_day: Option<usize>,
+    /// maximum number of requests sent in one hour
+    pub max_per_hour: Option<usize>,
+    /// days over which the first requests of new companies are spread
+    pub stagger_days: u32,
+    /// days after the legal deadline before the first reminder is sent
+    pub reminder_grace: u32,
+    /// days between two reminders
+    pub reminder_interval: u32,
+    /// reminders sent for one request at most
+    pub max_reminders: u8,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self {
+            max_per_day: None,
+            max_per_hour: None,
+            stagger_days: 30,
+            reminder_grace: 0,
+            reminder_interval: 14,
+            max_reminders: 2,
+        }
+    }
+
+    /// minimum gap between two sends so that no limit can be exceeded
+    pub fn spacing(&self) -> Option<Duration> {
+        let day = self
+            .max_per_day
+            .map(|v| Duration::days(1) / v.max(1) as i32);
+        let hour = self
+            .max_per_hour
+            .map(|v| Duration::hours(1) / v.max(1) as i32);
+        match (day, hour) {
+            (Some(day), Some(hour)) => Some(day.max(hour)),
+            (day, hour) => day.or(hour),
+        }
+    }
+
+    /// spread the first send of companies without a time table entry over the stagger window
+    pub fn stagger(&self, companies: &mut [Company], now: DateTime<Utc>) {
+        let new: Vec<&mut Company> = companies.iter_mut().filter(|v| !v.known).collect();
+        if new.is_empty() {
+            return;
+        }
+        let window = Duration::days(i64::from(self.stagger_days));
+        let step = window / new.len() as i32;
+        info!(
+            "staggering first request of {} companies over {} days",
+            new.len(),
+            self.stagger_days
+        );
+        for (i, v) in new.into_iter().enumerate() {
+            v.next_hit = now + step * i as i32;
+            v.known = true;
+            trace!("scheduled first request for {} on {}", v.name, v.next_hit);
+        }
+    }
+
+    /// move requests which are closer together than the allowed spacing apart
+    pub fn spread(&self, companies: &mut [Company]) {
+        let spacing = match self.spacing() {
+            Some(spacing) => spacing,
+            None => return,
+        };
+        let mut order: Vec<&mut Company> = companies.iter_mut().collect();
+        order.sort_by_key(|v| v.next_hit);
+
+        // the latest send defines where the next one may be placed
+        let mut last: Option<DateTime<Utc>> = order.iter().filter_map(|v| v.last_hit).max();
+        for v in order {
+            if let Some(last) = last {
+                if v.next_hit < last + spacing {
+                    debug!(
+                        "moving request for {} from {} to {}",
+                        v.name,
+                        v.next_hit,
+                        last + spacing
+                    );
+                    v.next_hit = last + spacing;
+                }
+            }
+            last = Some(v.next_hit);
+        }
+    }
+
+    /// indexes of the companies to send to now, limited by the hourly and daily quota
+    pub fn due(&self, companies: &[Company], now: DateTime<Utc>) -> Vec<usize> {
+        let sent_since = |since: DateTime<Utc>| {
+            companies
+                .iter()
+                .filter(|v| v.last_hit.is_some_and(|v| v > since))
+                .count()
+        };
+        let mut quota = usize::MAX;
+        if let Some(max) = self.max_per_day {
+            quota = quota.min(max.saturating_sub(sent_since(now - Duration::days(1))));
+        }
+        if let Some(max) = self.max_per_hour {
+            quota = quota.min(max.saturating_sub(sent_since(now - Duration::hours(1))));
+        }
+
+        let mut due: Vec<usize> = (0..companies.len())
+            .filter(|i| {
+                let v = &companies[*i];
+                let pending = v.pending.is_some() && !v.is_open();
+                (v.next_hit <= now || pending) && v.invalid.is_none() && !v.paused
+            })
+            .collect();
+        due.sort_by_key(|i| companies[*i].next_hit);
+        if due.len() > quota {
+            debug!("{} requests due, sending {} now", due.len(), quota);
+            due.truncate(quota);
+        }
+        due
+    }
+
+    /// when the first reminder for the open request of `company` is due
+    fn first_reminder(&self, company: &Company) -> Option<DateTime<Utc>> {
+        company.message_id.as_ref()?;
+        if company.invalid.is_some() || company.paused || company.answered.is_some() {
+            return None;
+        }
+        let (sent, deadline) = (company.last_hit?, company.deadline()?);
+        let first = company.right.first_reminder(sent, deadline);
+        Some(first + Duration::days(i64::from(self.reminder_grace)))
+    }
+
+    /// when the next reminder for the open request of `company` is due
+    pub fn reminder_at(&self, company: &Company) -> Option<DateTime<Utc>> {
+        if company.reminder >= self.max_reminders {
+            return None;
+        }
+        let days = self.reminder_interval * u32::from(company.reminder);
+        Some(self.first_reminder(company)? + Duration::days(i64::from(days)))
+    }
+
+    /// when the open request of `company` is unanswered after all reminders or was refused
+    pub fn escalation_at(&self, company: &Company) -> Option<DateTime<Utc>> {
+        if let (Some(_), Some((outcome, date))) = (&company.message_id, company.outcome) {
+            if outcome.action() == Action::Escalate {
+                return Some(date);
+            }
+        }
+        let days = self.reminder_interval * u32::from(self.max_reminders);
+        Some(self.first_reminder(company)? + Duration::days(i64::from(days)))
+    }
+
+    /// indexes of the companies which should get a reminder now
+    pub fn reminders_due(&self, companies: &[Company], now: DateTime<Utc>) -> Vec<usize> {
+        (0..companies.len())
+            .filter(|i| self.reminder_at(&companies[*i]).is_some_and(|v| v <= now))
+            .collect()
+    }
+}
+
+/// `date` plus `months` calendar months, the last day of the month if the day does not exist
+pub fn add_months(date: DateTime<Utc>, months: u32) -> DateTime<Utc> {
+    let months = date.month0() + months;
+    let year = date.year() + (months / 12) as i32;
+    let month = months % 12 + 1;
+    let mut day = date.day();
+    let mut naive = NaiveDate::from_ymd_opt(year, month, day);
+    while naive.is_none() {
+        day -= 1;
+        naive = NaiveDate::from_ymd_opt(year, month, day);
+    }
+    DateTime::from_utc(naive.unwrap().and_time(date.time()), Utc)
+}
+
+/// end of the period of `months` for answering a request received at `received`
+///
+/// Following regulation 1182/71 the period ends with the last second of the day with
+/// the number of the day of `received`, or of the last day of the month if there is no
+/// such day. A period ending on a weekend runs until the next monday, public holidays
+/// are not known.
+pub fn deadline(received: DateTime<Utc>, months: u32) -> DateTime<Utc> {
+    let mut day = add_months(received, months).date();
+    while day.weekday() == Weekday::Sat || day.weekday() == Weekday::Sun {
+        day = day.succ();
+    }
+    day.and_hms(23, 59, 59)
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
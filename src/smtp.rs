@@ -0,0 +1,109 @@
+use super::{
+    net::{self, other, Connection, Stream},
+    Encryption, ServerConfig,
+};
+use std::io;
+
+/// minimal smtp submission client
+pub struct Client {
+    conn: Connection,
+}
+
+impl Client {
+    /// connect, say hello, start tls if requested and authenticate
+    pub fn connect(config: &ServerConfig) -> io::Result<Self> {
+        let mut client = Self {
+            conn: Connection::new(Stream::connect(config)?),
+        };
+        client.expect(220)?;
+        client.ehlo()?;
+
+        if let Encryption::starttls = config.encryption {
+            client.command("STARTTLS", 220)?;
+            client.conn = client.conn.upgrade(config)?;
+            client.ehlo()?;
+        }
+
+        if !config.user.is_empty() {
+            net::check_cleartext(config)?;
+            let token = format!("\0{}\0{}", config.user, config.password);
+            let token = base64::encode(token.as_bytes());
+            // not written through `write_line` to keep the credentials out of the trace log
+            client
+                .conn
+                .write_all(format!("AUTH PLAIN {}\r\n", token).as_bytes())?;
+            client.expect(235)?;
+        }
+
+        Ok(client)
+    }
+
+    /// submit `data` from `from` to all `to`
+    pub fn send(&mut self, from: &str, to: &[&str], data: &[u8]) -> io::Result<()> {
+        let result = self.transaction(from, to, data);
+        if let Err(ref err) = result {
+            // the server rejected the message, reset so the next one can be sent
+            if err.kind() == io::ErrorKind::Other {
+                self.command("RSET", 250)?;
+            }
+        }
+        result
+    }
+
+    fn transaction(&mut self, from: &str, to: &[&str], data: &[u8]) -> io::Result<()> {
+        self.command(&format!("MAIL FROM:<{}>", from), 250)?;
+        for v in to {
+            self.command(&format!("RCPT TO:<{}>", v), 250)?;
+        }
+        self.command("DATA", 354)?;
+
+        // dot stuffing
+        let mut body = Vec::with_capacity(data.len() + 5);
+        let data = data.strip_suffix(b"\n").unwrap_or(data);
+        for line in data.split(|v| *v == b'\n') {
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            if line.starts_with(b".") {
+                body.push(b'.');
+            }
+            body.extend_from_slice(line);
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(b".\r\n");
+        self.conn.write_all(&body)?;
+        self.expect(250)?;
+        Ok(())
+    }
+
+    /// say goodbye
+    pub fn quit(mut self) -> io::Result<()> {
+        self.command("QUIT", 221)
+    }
+
+    fn ehlo(&mut self) -> io::Result<()> {
+        let host = std::env::var("HOSTNAME").unwrap_or_else(|_| String::from("localhost"));
+        self.command(&format!("EHLO {}", host), 250)
+    }
+
+    fn command(&mut self, command: &str, code: u16) -> io::Result<()> {
+        self.conn.write_line(command)?;
+        self.expect(code)
+    }
+
+    /// read a (multiline) reply and check its code
+    fn expect(&mut self, code: u16) -> io::Result<()> {
+        loop {
+            let line = self.conn.read_line()?;
+            let reply: u16 = line
+                .get(..3)
+                .and_then(|v| v.parse().ok())
+                .ok_or_else(|| other(format!("invalid smtp reply: {}", line)))?;
+            if line.as_bytes().get(3) == Some(&b'-') {
+                continue;
+            }
+            if reply != code {
+                return Err(other(format!("smtp server replied: {}", line)));
+            }
+            return Ok(());
+        }
+    }
+}
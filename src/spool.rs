@@ -0,0 +1,267 @@
+use super::{slug, Company};
+use chrono::{prelude::*, Duration};
+use serde_json::Value;
+use std::{fs, io, path::PathBuf};
+
+/// on disk queue of rendered messages waiting for submission
+#[derive(Debug, Clone)]
+pub struct Spool {
+    pub dir: String,
+    /// attempts before a message is moved to `failed`
+    pub max_attempts: u32,
+    /// delay after the first failed attempt, doubled after every further failure
+    pub backoff: Duration,
+}
+
+/// metadata of a spooled message
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub id: String,
+    pub company: String,
+    pub from: String,
+    pub to: Vec<String>,
+    pub message_id: String,
+    pub reference: String,
+    /// number of the reminder, `0` for the request itself
+    pub reminder: u8,
+    pub created: DateTime<Utc>,
+    pub attempts: u32,
+    pub next_attempt: DateTime<Utc>,
+    pub error: Option<String>,
+}
+
+impl Spool {
+    pub fn new() -> Self {
+        Self {
+            dir: String::from("spool"),
+            max_attempts: 10,
+            backoff: Duration::minutes(5),
+        }
+    }
+
+    /// spool id of the next request to `company`, stays the same until a send is accepted
+    pub fn id(company: &Company) -> String {
+        let name = slug(&company.name);
+        let id = match company.last_hit {
+            Some(last) => format!("{}-{}", name, last.format("%Y%m%dT%H%M%S")),
+            None => format!("{}-first", name),
+        };
+        match &company.pending {
+            Some((right, _)) => format!("{}-{}", id, right),
+            None => id,
+        }
+    }
+
+    /// id of the next reminder for the open request of `company`
+    pub fn reminder_id(company: &Company) -> String {
+        format!("{}-r{}", Self::id(company), company.reminder + 1)
+    }
+
+    fn path(&self, id: &str, ext: &str) -> PathBuf {
+        PathBuf::from(&self.dir).join(format!("{}.{}", id, ext))
+    }
+
+    fn failed_path(&self, id: &str, ext: &str) -> PathBuf {
+        PathBuf::from(&self.dir)
+            .join("failed")
+            .join(format!("{}.{}", id, ext))
+    }
+
+    /// whether `id` is queued or has failed
+    pub fn contains(&self, id: &str) -> bool {
+        self.path(id, "json").exists() || self.failed_path(id, "json").exists()
+    }
+
+    /// whether `id` was given up on
+    pub fn has_failed(&self, id: &str) -> bool {
+        self.failed_path(id, "json").exists()
+    }
+
+    /// queue `message`, the metadata is written last so half written entries are never read
+    pub fn push(&self, entry: &Entry, message: &[u8]) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.path(&entry.id, "eml"), message)?;
+        self.write(entry)?;
+        debug!("spooled {} for {}", entry.id, entry.company);
+        Ok(())
+    }
+
+    fn write(&self, entry: &Entry) -> io::Result<()> {
+        let json = json!({
+            "company": entry.company,
+            "from": entry.from,
+            "to": entry.to,
+            "message-id": entry.message_id,
+            "reference": entry.reference,
+            "reminder": entry.reminder,
+            "created": entry.created.to_rfc3339(),
+            "attempts": entry.attempts,
+            "next": entry.next_attempt.to_rfc3339(),
+            "error": entry.error,
+        });
+        let tmp = self.path(&entry.id, "json.tmp");
+        fs::write(&tmp, json.to_string())?;
+        fs::rename(tmp, self.path(&entry.id, "json"))
+    }
+
+    /// all queued entries, oldest first
+    pub fn entries(&self) -> io::Result<Vec<Entry>> {
+        Self::read_entries(PathBuf::from(&self.dir))
+    }
+
+    /// entries which were given up on, oldest first
+    pub fn failed(&self) -> io::Result<Vec<Entry>> {
+        Self::read_entries(PathBuf::from(&self.dir).join("failed"))
+    }
+
+    fn read_entries(dir: PathBuf) -> io::Result<Vec<Entry>> {
+        let mut entries = Vec::new();
+        let dir = match fs::read_dir(dir) {
+            Ok(dir) => dir,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(entries),
+            Err(err) => return Err(err),
+        };
+        for file in dir {
+            let path = file?.path();
+            if path.extension().and_then(|v| v.to_str()) != Some("json") {
+                continue;
+            }
+            let id = match path.file_stem().and_then(|v| v.to_str()) {
+                Some(id) => id.to_string(),
+                None => continue,
+            };
+            match Self::parse(&id, &fs::read_to_string(&path)?) {
+                Some(entry) => entries.push(entry),
+                None => warn!("could not parse spool entry {}", path.display()),
+            }
+        }
+        entries.sort_by_key(|v| v.created);
+        Ok(entries)
+    }
+
+    fn parse(id: &str, data: &str) -> Option<Entry> {
+        let json: Value = serde_json::from_str(data).ok()?;
+        let date = |key: &str| json.get(key)?.as_str()?.parse::<DateTime<Utc>>().ok();
+        let string = |key: &str| {
+            json.get(key)
+                .and_then(|v| v.as_str())
+                .unwrap_or_default()
+                .to_string()
+        };
+        Some(Entry {
+            id: id.to_string(),
+            company: json.get("company")?.as_str()?.to_string(),
+            from: json.get("from")?.as_str()?.to_string(),
+            to: json
+                .get("to")?
+                .as_array()?
+                .iter()
+                .filter_map(|v| v.as_str())
+                .map(|v| v.to_string())
+                .collect(),
+            message_id: string("message-id"),
+            reference: string("reference"),
+            reminder: json.get("reminder").and_then(|v| v.as_u64()).unwrap_or(0) as u8,
+            created: date("created")?,
+            attempts: json.get("attempts")?.as_u64()? as u32,
+            next_attempt: date("next")?,
+            error: json
+                .get("error")
+                .and_then(|v| v.as_str())
+                .map(|v| v.to_string()),
+        })
+    }
+
+    /// raw message of `id`
+    pub fn message(&self, id: &str) -> io::Result<Vec<u8>> {
+        fs::read(self.path(id, "eml"))
+    }
+
+    /// drop `id` after it was accepted
+    pub fn remove(&self, id: &str) -> io::Result<()> {
+        fs::remove_file(self.path(id, "json"))?;
+        fs::remove_file(self.path(id, "eml"))
+    }
+
+    /// keep the message of the accepted `id` until it was filed in the sent folder
+    pub fn keep_sent(&self, id: &str) -> io::Result<()> {
+        fs::create_dir_all(PathBuf::from(&self.dir).join("sent"))?;
+        fs::rename(self.path(id, "eml"), self.sent_path(id))?;
+        fs::remove_file(self.path(id, "json"))
+    }
+
+    fn sent_path(&self, id: &str) -> PathBuf {
+        PathBuf::from(&self.dir)
+            .join("sent")
+            .join(format!("{}.eml", id))
+    }
+
+    /// ids of accepted messages not yet filed in the sent folder
+    pub fn sent(&self) -> io::Result<Vec<String>> {
+        let dir = match fs::read_dir(PathBuf::from(&self.dir).join("sent")) {
+            Ok(dir) => dir,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+        let mut ids = Vec::new();
+        for file in dir {
+            let path = file?.path();
+            if path.extension().and_then(|v| v.to_str()) != Some("eml") {
+                continue;
+            }
+            if let Some(id) = path.file_stem().and_then(|v| v.to_str()) {
+                ids.push(id.to_string());
+            }
+        }
+        ids.sort();
+        Ok(ids)
+    }
+
+    /// raw message of the accepted `id`
+    pub fn sent_message(&self, id: &str) -> io::Result<Vec<u8>> {
+        fs::read(self.sent_path(id))
+    }
+
+    /// drop the accepted `id` after it was filed
+    pub fn remove_sent(&self, id: &str) -> io::Result<()> {
+        fs::remove_file(self.sent_path(id))
+    }
+
+    /// record a failed attempt, returns `true` if the entry was given up on
+    pub fn retry(&self, entry: &mut Entry, error: String, now: DateTime<Utc>) -> io::Result<bool> {
+        entry.attempts += 1;
+        entry.error = Some(error);
+        if entry.attempts >= self.max_attempts {
+            fs::create_dir_all(PathBuf::from(&self.dir).join("failed"))?;
+            self.write(entry)?;
+            fs::rename(
+                self.path(&entry.id, "eml"),
+                self.failed_path(&entry.id, "eml"),
+            )?;
+            fs::rename(
+                self.path(&entry.id, "json"),
+                self.failed_path(&entry.id, "json"),
+            )?;
+            return Ok(true);
+        }
+        entry.next_attempt = now + self.delay(entry.attempts);
+        self.write(entry)?;
+        Ok(false)
+    }
+
+    /// backoff after `attempts` failed attempts, capped at one day
+    pub fn delay(&self, attempts: u32) -> Duration {
+        let factor = 1i64 << attempts.saturating_sub(1).min(16);
+        let max = Duration::days(1);
+        match self.backoff.num_milliseconds().checked_mul(factor) {
+            Some(delay) => Duration::milliseconds(delay).min(max),
+            None => max,
+        }
+    }
+}
+
+impl Default for Spool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,843 @@
+use super::{right::Right, Company};
+use chrono::prelude::*;
+
+/// language of the letters and the national law they cite
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Language {
+    /// german, citing the BDSG
+    #[default]
+    German,
+    /// english for companies in the EU
+    English,
+    /// english for companies in the United Kingdom, citing the UK GDPR
+    British,
+    /// french, citing the loi informatique et libertés
+    French,
+    /// spanish, citing the LOPDGDD
+    Spanish,
+}
+
+/// all built in languages
+pub const LANGUAGES: [Language; 5] = [
+    Language::German,
+    Language::English,
+    Language::British,
+    Language::French,
+    Language::Spanish,
+];
+
+impl Language {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.to_lowercase().replace('_', "-").as_str() {
+            "de" | "de-de" => Ok(Language::German),
+            "en" | "en-eu" | "en-ie" => Ok(Language::English),
+            "en-gb" | "en-uk" => Ok(Language::British),
+            "fr" | "fr-fr" => Ok(Language::French),
+            "es" | "es-es" => Ok(Language::Spanish),
+            _ => Err(format!("unknown language '{}'", value)),
+        }
+    }
+
+    /// `date` the way it is written in letters
+    pub fn date(self, date: DateTime<Utc>) -> String {
+        const EN: [&str; 12] = [
+            "January",
+            "February",
+            "March",
+            "April",
+            "May",
+            "June",
+            "July",
+            "August",
+            "September",
+            "October",
+            "November",
+            "December",
+        ];
+        const FR: [&str; 12] = [
+            "janvier",
+            "février",
+            "mars",
+            "avril",
+            "mai",
+            "juin",
+            "juillet",
+            "août",
+            "septembre",
+            "octobre",
+            "novembre",
+            "décembre",
+        ];
+        const ES: [&str; 12] = [
+            "enero",
+            "febrero",
+            "marzo",
+            "abril",
+            "mayo",
+            "junio",
+            "julio",
+            "agosto",
+            "septiembre",
+            "octubre",
+            "noviembre",
+            "diciembre",
+        ];
+        let month = date.month0() as usize;
+        match self {
+            Language::German => date.format("%d.%m.%Y").to_string(),
+            Language::English | Language::British => {
+                format!("{} {} {}", date.day(), EN[month], date.year())
+            }
+            Language::French => format!("{} {} {}", date.day(), FR[month], date.year()),
+            Language::Spanish => format!("{} de {} de {}", date.day(), ES[month], date.year()),
+        }
+    }
+
+    /// name of a request for `right`, the subject of the letter
+    fn title(self, right: Right) -> &'static str {
+        match (self, right) {
+            (Language::German, Right::Access) => "Auskunftsersuchen nach Art. 15 DSGVO",
+            (Language::German, Right::Rectification) => {
+                "Antrag auf Berichtigung nach Art. 16 DSGVO"
+            }
+            (Language::German, Right::Erasure) => "Antrag auf Löschung nach Art. 17 DSGVO",
+            (Language::German, Right::Restriction) => {
+                "Antrag auf Einschränkung der Verarbeitung nach Art. 18 DSGVO"
+            }
+            (Language::German, Right::Portability) => {
+                "Antrag auf Datenübertragung nach Art. 20 DSGVO"
+            }
+            (Language::German, Right::Objection) => "Widerspruch nach Art. 21 DSGVO",
+            (_, Right::Access) if self.is_english() => {
+                "Subject access request under Art. 15 {gdpr}"
+            }
+            (_, Right::Rectification) if self.is_english() => {
+                "Request for rectification under Art. 16 {gdpr}"
+            }
+            (_, Right::Erasure) if self.is_english() => "Request for erasure under Art. 17 {gdpr}",
+            (_, Right::Restriction) if self.is_english() => {
+                "Request for restriction of processing under Art. 18 {gdpr}"
+            }
+            (_, Right::Portability) if self.is_english() => {
+                "Data portability request under Art. 20 {gdpr}"
+            }
+            (_, Right::Objection) if self.is_english() => "Objection under Art. 21 {gdpr}",
+            (Language::French, Right::Access) => "Demande d'accès au titre de l'article 15 du RGPD",
+            (Language::French, Right::Rectification) => {
+                "Demande de rectification au titre de l'article 16 du RGPD"
+            }
+            (Language::French, Right::Erasure) => {
+                "Demande d'effacement au titre de l'article 17 du RGPD"
+            }
+            (Language::French, Right::Restriction) => {
+                "Demande de limitation du traitement au titre de l'article 18 du RGPD"
+            }
+            (Language::French, Right::Portability) => {
+                "Demande de portabilité au titre de l'article 20 du RGPD"
+            }
+            (Language::French, Right::Objection) => "Opposition au titre de l'article 21 du RGPD",
+            (_, Right::Access) => "Solicitud de acceso conforme al artículo 15 del RGPD",
+            (_, Right::Rectification) => {
+                "Solicitud de rectificación conforme al artículo 16 del RGPD"
+            }
+            (_, Right::Erasure) => "Solicitud de supresión conforme al artículo 17 del RGPD",
+            (_, Right::Restriction) => {
+                "Solicitud de limitación del tratamiento conforme al artículo 18 del RGPD"
+            }
+            (_, Right::Portability) => "Solicitud de portabilidad conforme al artículo 20 del RGPD",
+            (_, Right::Objection) => "Oposición conforme al artículo 21 del RGPD",
+        }
+    }
+
+    fn is_english(self) -> bool {
+        matches!(self, Language::English | Language::British)
+    }
+
+    pub fn request_subject(self, right: Right, reference: &str) -> String {
+        format!("[{}] {}", reference, self.gdpr(self.title(right)))
+    }
+
+    pub fn reminder_subject(self, right: Right, reference: &str) -> String {
+        let title = self.gdpr(self.title(right));
+        let subject = match self {
+            Language::German => format!("Erinnerung: {}", title),
+            Language::French => format!("Relance : {}", lowercase_first(&title)),
+            Language::Spanish => format!("Recordatorio: {}", lowercase_first(&title)),
+            _ => format!("Reminder: {}", lowercase_first(&title)),
+        };
+        format!("[{}] {}", reference, subject)
+    }
+
+    /// text of the request for `right` to `company`, `details` are added where the right needs them
+    pub fn request(
+        self,
+        right: Right,
+        company: &Company,
+        reference: &str,
+        details: &str,
+    ) -> String {
+        let text = match (self, right) {
+            (_, Right::Access) => self.access(),
+            (Language::German, _) => format!(
+                "Sehr geehrte Damen und Herren,
+
+{}
+
+Ich bitte um Antwort innerhalb eines Monats nach Eingang dieses Schreibens
+(Art. 12 Abs. 3 DSGVO). Bitte geben Sie dabei das Aktenzeichen {{reference}} an.
+
+Mit freundlichen Grüßen
+{{name}}
+",
+                self.body(right)
+            ),
+            (Language::French, _) => format!(
+                "Madame, Monsieur,
+
+{}
+
+Je vous remercie de me répondre dans un délai d'un mois à compter de la
+réception de cette demande (article 12, paragraphe 3, du RGPD) en rappelant
+la référence {{reference}}.
+
+Veuillez agréer, Madame, Monsieur, mes salutations distinguées.
+{{name}}
+",
+                self.body(right)
+            ),
+            (Language::Spanish, _) => format!(
+                "Estimados señores:
+
+{}
+
+Les ruego que respondan en el plazo de un mes desde la recepción de esta
+solicitud (artículo 12, apartado 3, del RGPD) indicando la referencia
+{{reference}}.
+
+Atentamente,
+{{name}}
+",
+                self.body(right)
+            ),
+            _ => format!(
+                "Dear Sir or Madam,
+
+{}
+
+Please respond within one month of receiving this request (Article 12(3)
+{{gdpr}}) and quote the reference {{reference}}.
+
+Yours faithfully
+{{name}}
+",
+                self.body(right)
+            ),
+        };
+        let details = match details.trim() {
+            "" => String::new(),
+            details => format!("{}\n\n", details),
+        };
+        // the details are written by the operator and not searched for placeholders
+        self.fill(&text, right, company, reference, None)
+            .replace("{details}", &details)
+    }
+
+    /// text of the access request, which is sent periodically
+    fn access(self) -> String {
+        let text = match self {
+            Language::German => {
+                "Sehr geehrte Damen und Herren,
+
+hiermit bitte ich um Auskunft gemäß Art. 15 DSGVO über die zu meiner Person
+bei {company} gespeicherten personenbezogenen Daten. Ich bin bei Ihnen unter
+der E-Mail-Adresse {alias} bekannt.
+
+Bitte teilen Sie mir insbesondere mit:
+
+ - welche Daten Sie über mich gespeichert haben,
+ - zu welchen Zwecken diese Daten verarbeitet werden,
+ - an welche Empfänger die Daten weitergegeben wurden,
+ - wie lange die Daten gespeichert werden,
+ - woher die Daten stammen, falls Sie sie nicht bei mir erhoben haben.
+
+{details}Bitte stellen Sie mir zusätzlich eine Kopie der Daten gemäß Art. 15 Abs. 3
+DSGVO in einem gängigen elektronischen Format zur Verfügung. Sollten Sie die
+Auskunft nach § 34 BDSG ganz oder teilweise verweigern, bitte ich um Angabe
+der Gründe.
+
+Ich bitte um Antwort innerhalb eines Monats nach Eingang dieses Schreibens
+(Art. 12 Abs. 3 DSGVO). Bitte geben Sie dabei das Aktenzeichen {reference} an.
+
+Mit freundlichen Grüßen
+{name}
+"
+            }
+            Language::English | Language::British => {
+                "Dear Sir or Madam,
+
+I am writing to make a subject access request under Article 15 of the
+{gdpr} for the personal data {company} holds about me. You know me
+under the e-mail address {alias}.
+
+In particular, please tell me:
+
+ - which personal data about me you hold,
+ - the purposes for which the data is processed,
+ - the recipients the data has been disclosed to,
+ - how long the data will be stored,
+ - where the data came from, if you did not collect it from me.
+
+{details}Please also provide a copy of the data in a commonly used electronic format
+(Article 15(3) {gdpr}).
+
+Please respond within one month of receiving this request (Article 12(3)
+{gdpr}) and quote the reference {reference}.
+
+Yours faithfully
+{name}
+"
+            }
+            Language::French => {
+                "Madame, Monsieur,
+
+Conformément à l'article 15 du règlement général sur la protection des
+données (RGPD) et à la loi n° 78-17 du 6 janvier 1978 relative à
+l'informatique, aux fichiers et aux libertés, je vous prie de me communiquer
+les données personnelles me concernant détenues par {company}. Vous me
+connaissez sous l'adresse électronique {alias}.
+
+Je vous demande notamment de m'indiquer :
+
+ - les données me concernant que vous conservez,
+ - les finalités du traitement,
+ - les destinataires auxquels les données ont été communiquées,
+ - la durée de conservation des données,
+ - la source des données, si elles n'ont pas été collectées auprès de moi.
+
+{details}Je vous prie également de me fournir une copie des données dans un format
+électronique d'usage courant (article 15, paragraphe 3, du RGPD).
+
+Je vous remercie de me répondre dans un délai d'un mois à compter de la
+réception de cette demande (article 12, paragraphe 3, du RGPD) en rappelant
+la référence {reference}.
+
+Veuillez agréer, Madame, Monsieur, mes salutations distinguées.
+{name}
+"
+            }
+            Language::Spanish => {
+                "Estimados señores:
+
+Conforme al artículo 15 del Reglamento General de Protección de Datos (RGPD)
+y al artículo 13 de la Ley Orgánica 3/2018 de Protección de Datos Personales
+y garantía de los derechos digitales (LOPDGDD), solicito acceso a los datos
+personales que {company} conserva sobre mí. Me conocen por la dirección de
+correo electrónico {alias}.
+
+En particular, les ruego que me indiquen:
+
+ - qué datos personales conservan sobre mí,
+ - los fines del tratamiento,
+ - los destinatarios a los que se han comunicado los datos,
+ - el plazo de conservación de los datos,
+ - el origen de los datos, si no los obtuvieron de mí.
+
+{details}Les ruego asimismo que me faciliten una copia de los datos en un formato
+electrónico de uso común (artículo 15, apartado 3, del RGPD).
+
+Les ruego que respondan en el plazo de un mes desde la recepción de esta
+solicitud (artículo 12, apartado 3, del RGPD) indicando la referencia
+{reference}.
+
+Atentamente,
+{name}
+"
+            }
+        };
+        text.to_string()
+    }
+
+    /// what is asked for under `right`, between the greeting and the deadline
+    fn body(self, right: Right) -> &'static str {
+        match (self, right) {
+            (Language::German, Right::Rectification) => {
+                "hiermit verlange ich gemäß Art. 16 DSGVO die Berichtigung der zu meiner Person
+bei {company} gespeicherten personenbezogenen Daten. Ich bin bei Ihnen unter
+der E-Mail-Adresse {alias} bekannt.
+
+Folgende Angaben sind unrichtig oder unvollständig und sind wie folgt zu
+berichtigen:
+
+{details}Bitte teilen Sie die Berichtigung gemäß Art. 19 DSGVO auch allen Empfängern
+mit, denen Sie die Daten offengelegt haben."
+            }
+            (Language::German, Right::Erasure) => {
+                "hiermit verlange ich gemäß Art. 17 DSGVO die unverzügliche Löschung aller zu
+meiner Person bei {company} gespeicherten personenbezogenen Daten. Ich bin bei
+Ihnen unter der E-Mail-Adresse {alias} bekannt.
+
+{details}Bitte teilen Sie die Löschung gemäß Art. 19 DSGVO auch allen Empfängern mit,
+denen Sie die Daten offengelegt haben, und bestätigen Sie mir die Löschung.
+Sollten Sie sich auf eine Ausnahme nach Art. 17 Abs. 3 DSGVO oder § 35 BDSG
+berufen, bitte ich um Angabe der Gründe."
+            }
+            (Language::German, Right::Restriction) => {
+                "hiermit verlange ich gemäß Art. 18 DSGVO die Einschränkung der Verarbeitung der
+zu meiner Person bei {company} gespeicherten personenbezogenen Daten. Ich bin
+bei Ihnen unter der E-Mail-Adresse {alias} bekannt.
+
+{details}Bis zur Klärung dürfen die Daten nur noch gespeichert werden. Bitte bestätigen
+Sie mir die Einschränkung und unterrichten Sie mich, bevor sie aufgehoben wird
+(Art. 18 Abs. 3 DSGVO)."
+            }
+            (Language::German, Right::Portability) => {
+                "hiermit verlange ich gemäß Art. 20 DSGVO, die personenbezogenen Daten, die ich
+{company} bereitgestellt habe, in einem strukturierten, gängigen und
+maschinenlesbaren Format zu erhalten. Ich bin bei Ihnen unter der
+E-Mail-Adresse {alias} bekannt.
+
+{details}Bitte stellen Sie mir die Daten zum Herunterladen bereit oder senden Sie sie
+an diese Adresse."
+            }
+            (Language::German, _) => {
+                "hiermit widerspreche ich gemäß Art. 21 DSGVO der Verarbeitung der zu meiner
+Person bei {company} gespeicherten personenbezogenen Daten, insbesondere für
+Zwecke der Direktwerbung und der Profilbildung (Art. 21 Abs. 2 DSGVO). Ich bin
+bei Ihnen unter der E-Mail-Adresse {alias} bekannt.
+
+{details}Bitte stellen Sie die Verarbeitung ein und bestätigen Sie mir dies. Soweit
+Sie sich auf zwingende schutzwürdige Gründe nach Art. 21 Abs. 1 DSGVO berufen,
+bitte ich um deren Darlegung."
+            }
+            (Language::French, Right::Rectification) => {
+                "Conformément à l'article 16 du RGPD, je vous demande de rectifier les données
+personnelles me concernant détenues par {company}. Vous me connaissez sous
+l'adresse électronique {alias}.
+
+Les données suivantes sont inexactes ou incomplètes et doivent être
+rectifiées comme suit :
+
+{details}Je vous prie de notifier cette rectification à chaque destinataire auquel
+les données ont été communiquées (article 19 du RGPD)."
+            }
+            (Language::French, Right::Erasure) => {
+                "Conformément à l'article 17 du RGPD, je vous demande d'effacer dans les
+meilleurs délais l'ensemble des données personnelles me concernant détenues
+par {company}. Vous me connaissez sous l'adresse électronique {alias}.
+
+{details}Je vous prie de notifier cet effacement à chaque destinataire auquel les
+données ont été communiquées (article 19 du RGPD) et de me le confirmer. Si
+vous invoquez une exception de l'article 17, paragraphe 3, du RGPD, je vous
+prie de m'en indiquer les motifs."
+            }
+            (Language::French, Right::Restriction) => {
+                "Conformément à l'article 18 du RGPD, je vous demande de limiter le
+traitement des données personnelles me concernant détenues par {company}.
+Vous me connaissez sous l'adresse électronique {alias}.
+
+{details}Jusqu'à ce que la question soit réglée, les données ne peuvent être que
+conservées. Je vous prie de me confirmer la limitation et de m'informer avant
+qu'elle ne soit levée (article 18, paragraphe 3, du RGPD)."
+            }
+            (Language::French, Right::Portability) => {
+                "Conformément à l'article 20 du RGPD, je vous demande de me transmettre les
+données personnelles que j'ai fournies à {company} dans un format structuré,
+couramment utilisé et lisible par machine. Vous me connaissez sous l'adresse
+électronique {alias}.
+
+{details}Je vous prie de mettre les données à ma disposition en téléchargement ou de
+me les envoyer à cette adresse."
+            }
+            (Language::French, _) => {
+                "Conformément à l'article 21 du RGPD, je m'oppose au traitement des données
+personnelles me concernant détenues par {company}, en particulier à des fins
+de prospection et de profilage (article 21, paragraphe 2, du RGPD). Vous me
+connaissez sous l'adresse électronique {alias}.
+
+{details}Je vous prie de cesser le traitement et de me le confirmer. Si vous invoquez
+des motifs légitimes et impérieux au sens de l'article 21, paragraphe 1, du
+RGPD, je vous prie de les exposer."
+            }
+            (Language::Spanish, Right::Rectification) => {
+                "Conforme al artículo 16 del RGPD, solicito la rectificación de los datos
+personales que {company} conserva sobre mí. Me conocen por la dirección de
+correo electrónico {alias}.
+
+Los siguientes datos son inexactos o incompletos y deben rectificarse como
+sigue:
+
+{details}Les ruego que comuniquen la rectificación a cada uno de los destinatarios a
+los que se hayan comunicado los datos (artículo 19 del RGPD)."
+            }
+            (Language::Spanish, Right::Erasure) => {
+                "Conforme al artículo 17 del RGPD, solicito la supresión sin dilación
+indebida de todos los datos personales que {company} conserva sobre mí. Me
+conocen por la dirección de correo electrónico {alias}.
+
+{details}Les ruego que comuniquen la supresión a cada uno de los destinatarios a los
+que se hayan comunicado los datos (artículo 19 del RGPD) y que me la
+confirmen. Si invocan alguna excepción del artículo 17, apartado 3, del RGPD,
+les ruego que me indiquen los motivos."
+            }
+            (Language::Spanish, Right::Restriction) => {
+                "Conforme al artículo 18 del RGPD, solicito la limitación del tratamiento de
+los datos personales que {company} conserva sobre mí. Me conocen por la
+dirección de correo electrónico {alias}.
+
+{details}Mientras no se resuelva la cuestión, los datos solo podrán conservarse. Les
+ruego que me confirmen la limitación y que me informen antes de levantarla
+(artículo 18, apartado 3, del RGPD)."
+            }
+            (Language::Spanish, Right::Portability) => {
+                "Conforme al artículo 20 del RGPD, solicito recibir los datos personales que
+he facilitado a {company} en un formato estructurado, de uso común y lectura
+mecánica. Me conocen por la dirección de correo electrónico {alias}.
+
+{details}Les ruego que pongan los datos a mi disposición para su descarga o que me
+los envíen a esta dirección."
+            }
+            (Language::Spanish, _) => {
+                "Conforme al artículo 21 del RGPD, me opongo al tratamiento de los datos
+personales que {company} conserva sobre mí, en particular con fines de
+mercadotecnia directa y elaboración de perfiles (artículo 21, apartado 2, del
+RGPD). Me conocen por la dirección de correo electrónico {alias}.
+
+{details}Les ruego que cesen el tratamiento y me lo confirmen. Si invocan motivos
+legítimos imperiosos conforme al artículo 21, apartado 1, del RGPD, les ruego
+que los expongan."
+            }
+            (_, Right::Rectification) => {
+                "I am writing to request the rectification of the personal data {company}
+holds about me under Article 16 of the {gdpr}. You know me under the e-mail
+address {alias}.
+
+The following data is inaccurate or incomplete and has to be corrected as
+follows:
+
+{details}Please also inform everyone the data was disclosed to of the rectification
+(Article 19 {gdpr})."
+            }
+            (_, Right::Erasure) => {
+                "I am writing to request the erasure of all personal data {company} holds
+about me under Article 17 of the {gdpr}. You know me under the e-mail address
+{alias}.
+
+{details}Please also inform everyone the data was disclosed to of the erasure (Article
+19 {gdpr}) and confirm the erasure to me. If you rely on an exemption under
+Article 17(3) {gdpr}, please tell me the reasons."
+            }
+            (_, Right::Restriction) => {
+                "I am writing to request the restriction of processing of the personal data
+{company} holds about me under Article 18 of the {gdpr}. You know me under the
+e-mail address {alias}.
+
+{details}Until the matter is resolved the data may only be stored. Please confirm the
+restriction and inform me before it is lifted (Article 18(3) {gdpr})."
+            }
+            (_, Right::Portability) => {
+                "I am writing to request the personal data I provided to {company} in a
+structured, commonly used and machine-readable format under Article 20 of the
+{gdpr}. You know me under the e-mail address {alias}.
+
+{details}Please make the data available for download or send it to this address."
+            }
+            _ => {
+                "I object under Article 21 of the {gdpr} to the processing of the personal
+data {company} holds about me, in particular for direct marketing and
+profiling (Article 21(2) {gdpr}). You know me under the e-mail address {alias}.
+
+{details}Please stop the processing and confirm this to me. If you rely on compelling
+legitimate grounds under Article 21(1) {gdpr}, please set them out."
+            }
+        }
+    }
+
+    /// text of a reminder of the request for `right` to `company` sent on `sent`
+    ///
+    /// Before the month of Art. 12(3) has `expired` the reminder points to the duty to act
+    /// without undue delay instead.
+    pub fn reminder(
+        self,
+        right: Right,
+        company: &Company,
+        reference: &str,
+        sent: Option<DateTime<Utc>>,
+        expired: bool,
+    ) -> String {
+        let (text, urgency) = match self {
+            Language::German => (
+                "Sehr geehrte Damen und Herren,
+
+am {sent} habe ich Ihnen unter dem Aktenzeichen {reference} folgendes Schreiben
+gesandt: „{title}“. Bis heute habe ich keine Antwort erhalten.
+
+{urgency} Ich bitte Sie, mein Anliegen nun unverzüglich zu bearbeiten.
+Andernfalls behalte ich mir vor, mich an die zuständige Aufsichtsbehörde zu
+wenden.
+
+Mit freundlichen Grüßen
+{name}
+",
+                if expired {
+                    "Die Frist von einem Monat nach Art. 12 Abs. 3 DSGVO ist abgelaufen."
+                } else {
+                    "Nach Art. {article} DSGVO haben Sie unverzüglich zu handeln."
+                },
+            ),
+            Language::English | Language::British => (
+                "Dear Sir or Madam,
+
+On {sent} I sent you a letter with the reference {reference}: {title}.
+I have not received a response.
+
+{urgency} Please deal with my request without further delay. Otherwise
+I reserve the right to lodge a complaint with {authority}.
+
+Yours faithfully
+{name}
+",
+                if expired {
+                    "The period of one month under Article 12(3) {gdpr} has expired."
+                } else {
+                    "Article {article} {gdpr} requires you to act without undue delay."
+                },
+            ),
+            Language::French => (
+                "Madame, Monsieur,
+
+Le {sent}, je vous ai adressé sous la référence {reference} le courrier
+suivant : « {title} ». À ce jour, je n'ai reçu aucune réponse.
+
+{urgency} Je vous prie de traiter ma demande sans délai. À défaut, je me
+réserve le droit de saisir la Commission nationale de l'informatique et des
+libertés (CNIL).
+
+Veuillez agréer, Madame, Monsieur, mes salutations distinguées.
+{name}
+",
+                if expired {
+                    "Le délai d'un mois prévu à l'article 12, paragraphe 3, du RGPD est expiré."
+                } else {
+                    "L'article {article} du RGPD vous impose d'agir dans les meilleurs délais."
+                },
+            ),
+            Language::Spanish => (
+                "Estimados señores:
+
+El {sent} les remití con la referencia {reference} el escrito «{title}».
+Hasta la fecha no he recibido respuesta.
+
+{urgency} Les ruego que atiendan mi solicitud sin más demora. En caso
+contrario, me reservo el derecho de presentar una reclamación ante la
+Agencia Española de Protección de Datos (AEPD).
+
+Atentamente,
+{name}
+",
+                if expired {
+                    "El plazo de un mes previsto en el artículo 12, apartado 3, del RGPD ha vencido."
+                } else {
+                    "El artículo {article} del RGPD les obliga a actuar sin dilación indebida."
+                },
+            ),
+        };
+        self.fill(
+            &text.replace("{urgency}", urgency),
+            right,
+            company,
+            reference,
+            sent,
+        )
+    }
+
+    pub fn complaint_subject(self, company: &Company) -> String {
+        let subject = match self {
+            Language::German => "Beschwerde nach Art. 77 DSGVO gegen",
+            Language::French => "Plainte au titre de l'article 77 du RGPD contre",
+            Language::Spanish => "Reclamación conforme al artículo 77 del RGPD contra",
+            _ => "Complaint under Art. 77 {gdpr} against",
+        };
+        let reference = company.reference.as_deref().unwrap_or("-");
+        format!("[{}] {} {}", reference, self.gdpr(subject), company.name)
+    }
+
+    /// text of a complaint to the supervisory authority about the unanswered open request
+    /// to `company`, `timeline` lists what happened
+    pub fn complaint(self, company: &Company, timeline: &str) -> String {
+        let text = match self {
+            Language::German => {
+                "Sehr geehrte Damen und Herren,
+
+hiermit reiche ich gemäß Art. 77 DSGVO Beschwerde gegen {company} ein.
+
+Am {sent} habe ich {company} unter dem Aktenzeichen {reference} folgendes
+Schreiben gesandt: „{title}“. Auch auf meine Erinnerungen habe ich bis heute
+keine inhaltliche Antwort erhalten. Die Frist nach Art. 12 Abs. 3 DSGVO endete
+am {deadline}.
+
+Angaben zum Verantwortlichen:
+
+ {company}
+ E-Mail: {mail}
+
+Ich bin bei {company} unter der E-Mail-Adresse {alias} bekannt.
+
+Verlauf:
+
+{timeline}
+
+Meine Schreiben und die automatischen Antworten von {company} sind als Anlagen
+beigefügt. Ich bitte Sie, den Vorgang zu prüfen und mich über das Ergebnis zu
+unterrichten.
+
+Mit freundlichen Grüßen
+{name}
+"
+            }
+            Language::English | Language::British => {
+                "Dear Sir or Madam,
+
+I wish to lodge a complaint under Article 77 of the {gdpr} against {company}.
+
+On {sent} I sent {company} a letter with the reference {reference}: {title}.
+Despite my reminders I have not received a substantive response. The period
+under Article 12(3) {gdpr} ended on {deadline}.
+
+Details of the controller:
+
+ {company}
+ E-mail: {mail}
+
+{company} knows me under the e-mail address {alias}.
+
+Timeline:
+
+{timeline}
+
+My letters and the automatic replies of {company} are attached. Please
+investigate the matter and inform me of the outcome.
+
+Yours faithfully
+{name}
+"
+            }
+            Language::French => {
+                "Madame, Monsieur,
+
+Conformément à l'article 77 du RGPD, je dépose une plainte contre {company}.
+
+Le {sent}, j'ai adressé à {company} sous la référence {reference} le courrier
+suivant : « {title} ». Malgré mes relances, je n'ai reçu aucune réponse sur le
+fond. Le délai prévu à l'article 12, paragraphe 3, du RGPD a expiré le
+{deadline}.
+
+Informations sur le responsable du traitement :
+
+ {company}
+ Courriel : {mail}
+
+{company} me connaît sous l'adresse électronique {alias}.
+
+Chronologie :
+
+{timeline}
+
+Mes courriers et les réponses automatiques de {company} sont joints. Je vous
+prie d'examiner cette affaire et de m'informer de la suite donnée.
+
+Veuillez agréer, Madame, Monsieur, mes salutations distinguées.
+{name}
+"
+            }
+            Language::Spanish => {
+                "Estimados señores:
+
+Conforme al artículo 77 del RGPD, presento una reclamación contra {company}.
+
+El {sent} remití a {company} con la referencia {reference} el escrito
+«{title}». A pesar de mis recordatorios, no he recibido una respuesta sobre el
+fondo. El plazo del artículo 12, apartado 3, del RGPD venció el {deadline}.
+
+Datos del responsable del tratamiento:
+
+ {company}
+ Correo electrónico: {mail}
+
+{company} me conoce por la dirección de correo electrónico {alias}.
+
+Cronología:
+
+{timeline}
+
+Adjunto mis escritos y las respuestas automáticas de {company}. Les ruego que
+examinen el asunto y me informen del resultado.
+
+Atentamente,
+{name}
+"
+            }
+        };
+        let reference = company.reference.as_deref().unwrap_or_default();
+        let deadline = company.deadline().map(|v| self.date(v)).unwrap_or_default();
+        // the timeline holds text from replies and is not searched for placeholders
+        self.fill(text, company.right, company, reference, company.last_hit)
+            .replace("{deadline}", &deadline)
+            .replace("{timeline}", timeline)
+    }
+
+    /// `text` with the name of the regulation this language cites
+    fn gdpr(self, text: &str) -> String {
+        match self {
+            Language::British => text.replace("{gdpr}", "UK GDPR"),
+            _ => text.replace("{gdpr}", "GDPR"),
+        }
+    }
+
+    /// replace the placeholders in `text`
+    fn fill(
+        self,
+        text: &str,
+        right: Right,
+        company: &Company,
+        reference: &str,
+        sent: Option<DateTime<Utc>>,
+    ) -> String {
+        let authority = match self {
+            Language::British => "the Information Commissioner's Office",
+            _ => "the competent supervisory authority",
+        };
+        let text = text
+            .replace("{title}", self.title(right))
+            .replace("{article}", &right.article().to_string())
+            .replace("{company}", &company.name)
+            .replace("{alias}", &company.alias)
+            .replace("{mail}", &company.mail)
+            .replace("{reference}", reference)
+            .replace("{sent}", &sent.map(|v| self.date(v)).unwrap_or_default())
+            .replace("{authority}", authority)
+            .replace("{name}", &company.onw_name);
+        self.gdpr(&text)
+    }
+}
+
+/// `text` starting with a lower case letter
+fn lowercase_first(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_lowercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+impl std::fmt::Display for Language {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let code = match self {
+            Language::German => "de",
+            Language::English => "en",
+            Language::British => "en-gb",
+            Language::French => "fr",
+            Language::Spanish => "es",
+        };
+        write!(f, "{}", code)
+    }
+}
@@ -6,10 +6,31 @@ fn config_parse_time() {
         alias: String::new(),
         name: String::from("test"),
         interval: 0,
+        language: None,
         mail: String::new(),
         next_hit: chrono::Utc::now(),
         onw_name: String::new(),
         reminder: 0,
+        last_hit: None,
+        known: false,
+        message_id: None,
+        reference: None,
+        invalid: None,
+        paused: false,
+        answered: None,
+        ticket: None,
+        outcome: None,
+        last_reply: None,
+        fulfilled: None,
+        response_days: Vec::new(),
+        history: Vec::new(),
+        right: Default::default(),
+        pending: None,
+        extended: None,
+        verifying: None,
+        suspended: chrono::Duration::zero(),
+        authority: None,
+        complained: None,
     };
     config.companies.push(test_company);
 
@@ -23,3 +44,1922 @@ fn config_parse_time() {
     );
     assert_eq!(config.companies[0].reminder, 20);
 }
+
+#[test]
+fn scheduler_stagger_and_quota() {
+    use super::{schedule::Scheduler, Company};
+    use chrono::{Duration, TimeZone, Utc};
+    let now = Utc.ymd(2019, 10, 1).and_hms(12, 0, 0);
+    let mut scheduler = Scheduler::new();
+    scheduler.stagger_days = 10;
+    scheduler.max_per_day = Some(2);
+
+    let mut companies: Vec<Company> = (0..5)
+        .map(|i| Company {
+            name: format!("company{}", i),
+            ..Company::new()
+        })
+        .collect();
+    companies[0].known = true;
+    companies[0].next_hit = now;
+
+    scheduler.stagger(&mut companies, now);
+    assert!(companies.iter().all(|v| v.known));
+    assert_eq!(companies[1].next_hit, now);
+    assert_eq!(companies[2].next_hit, now + Duration::days(10) / 4);
+
+    // two requests on the same instant get pushed apart
+    scheduler.spread(&mut companies);
+    let mut hits: Vec<_> = companies.iter().map(|v| v.next_hit).collect();
+    hits.sort();
+    for pair in hits.windows(2) {
+        assert!(pair[1] - pair[0] >= Duration::hours(12));
+    }
+
+    for v in companies.iter_mut() {
+        v.next_hit = now;
+    }
+    companies[4].last_hit = Some(now - Duration::hours(2));
+    assert_eq!(scheduler.due(&companies, now).len(), 1);
+}
+
+#[test]
+fn spool_retry_and_give_up() {
+    use super::{
+        spool::{Entry, Spool},
+        transport::Backend,
+        Company, Config,
+    };
+    use chrono::{Duration, Utc};
+    let dir = std::env::temp_dir().join(format!("datenbriefd-spool-{}", std::process::id()));
+    let mut spool = Spool::new();
+    spool.dir = dir.to_string_lossy().to_string();
+    spool.max_attempts = 3;
+
+    let company = Company {
+        name: String::from("ACME Inc."),
+        ..Company::new()
+    };
+    let now = Utc::now();
+    let mut entry = Entry {
+        id: Spool::id(&company),
+        company: company.name.clone(),
+        from: String::from("me@example.com"),
+        to: vec![String::from("privacy@example.com")],
+        message_id: String::new(),
+        reference: String::new(),
+        reminder: 0,
+        created: now,
+        attempts: 0,
+        next_attempt: now,
+        error: None,
+    };
+    assert_eq!(entry.id, "ACME_Inc_-first");
+    spool
+        .push(&entry, b"Subject: test\r\n\r\ntest\r\n")
+        .unwrap();
+    assert!(spool.contains(&entry.id));
+    assert_eq!(
+        spool.message(&entry.id).unwrap(),
+        b"Subject: test\r\n\r\ntest\r\n"
+    );
+
+    assert!(!spool.retry(&mut entry, String::from("down"), now).unwrap());
+    assert!(!spool.retry(&mut entry, String::from("down"), now).unwrap());
+    let entries = spool.entries().unwrap();
+    assert_eq!(entries[0].attempts, 2);
+    assert_eq!(entries[0].next_attempt, now + Duration::minutes(10));
+
+    assert!(spool.retry(&mut entry, String::from("down"), now).unwrap());
+    assert!(spool.entries().unwrap().is_empty());
+    assert!(spool.has_failed(&entry.id));
+    spool.backoff = Duration::weeks(1_000_000);
+    assert_eq!(spool.delay(40), Duration::days(1));
+
+    // a request which was given up on no longer blocks the quota
+    let mut config = Config::new();
+    config.spool.dir = dir.join("config").to_string_lossy().to_string();
+    config.spool.max_attempts = 1;
+    config.time_file = dir.join("time.json").to_string_lossy().to_string();
+    std::fs::write(dir.join("blocked"), "").unwrap();
+    let blocked = dir.join("blocked").to_string_lossy().to_string();
+    config.transport = Backend::Maildir(blocked);
+    config.scheduler.max_per_day = Some(1);
+    for name in ["dead", "alive"].iter() {
+        config.companies.push(Company {
+            name: name.to_string(),
+            next_hit: now,
+            ..Company::new()
+        });
+    }
+    config.companies[1].next_hit = now + Duration::minutes(1);
+    config.hit(0, now, false);
+    config.flush_spool(now);
+    let later = now + Duration::minutes(2);
+    assert_eq!(config.scheduler.due(&config.companies, later), vec![1]);
+    assert_eq!(config.companies[0].next_hit, now + Duration::days(365));
+    // the state is replaced through a temporary file
+    assert!(dir.join("time.json").exists());
+    assert!(!dir.join("time.json.tmp").exists());
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn encryption_parse() {
+    use super::Encryption;
+    assert!(matches!(Encryption::parse("NONE"), Ok(Encryption::none)));
+    assert!(matches!(Encryption::parse("tls"), Ok(Encryption::tls)));
+    assert!(matches!(
+        Encryption::parse("starttls"),
+        Ok(Encryption::starttls)
+    ));
+    assert!(Encryption::parse("ssl").is_err());
+    assert!(super::parse_tls_version("1.2").is_ok());
+    assert!(super::parse_tls_version("TLSv1.3").is_ok());
+    assert!(super::parse_tls_version("2.0").is_err());
+
+    let mut server = super::ServerConfig::new();
+    server.encryption = Encryption::none;
+    for (host, allowed) in [("localhost", true), ("::1", true), ("mail.example", false)].iter() {
+        server.host = host.to_string();
+        assert_eq!(
+            super::net::check_cleartext(&server).is_ok(),
+            *allowed,
+            "{}",
+            host
+        );
+    }
+    server.encryption = Encryption::starttls;
+    assert!(super::net::check_cleartext(&server).is_ok());
+}
+
+#[test]
+fn cleartext_login_refused() {
+    use super::{imap, Encryption, ServerConfig};
+    use std::io::{BufRead, BufReader, Write};
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        write!(stream, "* OK ready\r\n").unwrap();
+        let mut lines = Vec::new();
+        for line in BufReader::new(stream).lines() {
+            lines.push(line.unwrap());
+        }
+        lines
+    });
+    // reaches the local server, but is not a loopback address
+    let mut config = ServerConfig::new();
+    config.host = String::from("0.0.0.0");
+    config.port = port;
+    config.encryption = Encryption::none;
+    config.user = String::from("me");
+    config.password = String::from("secret");
+    let err = imap::Client::connect(&config).err().unwrap();
+    assert!(err.to_string().contains("without encryption"), "{}", err);
+    let lines = server.join().unwrap();
+    assert!(lines.iter().all(|v| !v.contains("secret")), "{:?}", lines);
+}
+
+#[test]
+fn imap_idle() {
+    use super::{imap, Encryption, ServerConfig};
+    use std::io::{BufRead, BufReader, Write};
+    use std::time::Duration;
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let reader = BufReader::new(stream.try_clone().unwrap());
+        write!(stream, "* PREAUTH ready\r\n").unwrap();
+        let mut lines = Vec::new();
+        let mut idle = None;
+        for line in reader.lines() {
+            let line = line.unwrap();
+            lines.push(line.clone());
+            if let Some(tag) = line.strip_suffix(" IDLE") {
+                write!(stream, "+ idling\r\n").unwrap();
+                // only the first idle sees a new message, the second one times out
+                if idle.is_none() {
+                    write!(stream, "* 3 EXISTS\r\n").unwrap();
+                }
+                idle = Some(tag.to_string());
+            } else if line == "DONE" {
+                let tag = idle.as_deref().unwrap();
+                write!(stream, "{} OK IDLE terminated\r\n", tag).unwrap();
+            } else {
+                let tag = line.split(' ').next().unwrap();
+                write!(stream, "* BYE\r\n{} OK\r\n", tag).unwrap();
+                break;
+            }
+        }
+        lines
+    });
+    let mut config = ServerConfig::new();
+    config.host = String::from("127.0.0.1");
+    config.port = port;
+    config.encryption = Encryption::none;
+    let mut client = imap::Client::connect(&config).unwrap();
+    assert!(client.idle(Duration::from_secs(10)).unwrap());
+    assert!(!client.idle(Duration::from_millis(200)).unwrap());
+    client.logout().unwrap();
+    // the tagged reply only comes after DONE, so both calls returning proves the order
+    let lines = server.join().unwrap();
+    assert_eq!(
+        lines,
+        ["A0001 IDLE", "DONE", "A0002 IDLE", "DONE", "A0003 LOGOUT"]
+    );
+}
+
+#[test]
+fn imap_move_without_uidplus() {
+    use super::{imap, Encryption, ServerConfig};
+    use std::io::{BufRead, BufReader, Write};
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let reader = BufReader::new(stream.try_clone().unwrap());
+        write!(stream, "* PREAUTH ready\r\n").unwrap();
+        let mut commands = Vec::new();
+        for line in reader.lines() {
+            let line = line.unwrap();
+            let (tag, command) = line.split_once(' ').unwrap();
+            if command == "CAPABILITY" {
+                write!(stream, "* CAPABILITY IMAP4rev1 IDLE\r\n").unwrap();
+            }
+            write!(stream, "{} OK\r\n", tag).unwrap();
+            commands.push(command.to_string());
+            if command == "LOGOUT" {
+                break;
+            }
+        }
+        commands
+    });
+    let mut config = ServerConfig::new();
+    config.host = String::from("127.0.0.1");
+    config.port = port;
+    config.encryption = Encryption::none;
+    let mut client = imap::Client::connect(&config).unwrap();
+    assert!(client.has_capability("idle").unwrap());
+    client.move_to("4", "Datenbrief").unwrap();
+    client.move_to("5", "Datenbrief").unwrap();
+    client.logout().unwrap();
+    let commands = server.join().unwrap();
+    assert_eq!(
+        commands,
+        [
+            "CAPABILITY",
+            "UID COPY 4 \"Datenbrief\"",
+            "UID STORE 4 +FLAGS.SILENT (\\Deleted)",
+            "UID COPY 5 \"Datenbrief\"",
+            "UID STORE 5 +FLAGS.SILENT (\\Deleted)",
+            "LOGOUT"
+        ]
+    );
+}
+
+#[test]
+fn imap_first_sync_in_batches() {
+    use super::{Company, Config, Encryption};
+    use chrono::{Duration, Utc};
+    use std::io::{BufRead, BufReader, Write};
+    let dir = std::env::temp_dir().join(format!("datenbriefd-batches-{}", std::process::id()));
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let reader = BufReader::new(stream.try_clone().unwrap());
+        write!(stream, "* PREAUTH ready\r\n").unwrap();
+        let mut commands = Vec::new();
+        for line in reader.lines() {
+            let line = line.unwrap();
+            let (tag, command) = line.split_once(' ').unwrap();
+            commands.push(command.to_string());
+            if command.starts_with("SELECT") {
+                write!(stream, "* 150 EXISTS\r\n* OK [UIDVALIDITY 7]\r\n").unwrap();
+                write!(stream, "* OK [UIDNEXT 200]\r\n").unwrap();
+            } else if command.starts_with("UID SEARCH") {
+                write!(stream, "* SEARCH 100 130\r\n").unwrap();
+            } else if command.starts_with("UID FETCH 150:") {
+                let answer =
+                    "From: privacy@acme.example\r\n\r\nAnbei erhalten Sie die Auskunft.\r\n";
+                write!(
+                    stream,
+                    "* 120 FETCH (UID 160 BODY[] {{{}}}\r\n",
+                    answer.len()
+                )
+                .unwrap();
+                write!(stream, "{})\r\n", answer).unwrap();
+            } else if command == "LOGOUT" {
+                write!(stream, "* BYE\r\n{} OK\r\n", tag).unwrap();
+                break;
+            }
+            write!(stream, "{} OK\r\n", tag).unwrap();
+        }
+        commands
+    });
+
+    let now = Utc::now();
+    let sent = now - Duration::days(10);
+    let mut config = Config::new();
+    config.archive.dir = dir.join("archive").to_string_lossy().to_string();
+    config.time_file = dir.join("time.json").to_string_lossy().to_string();
+    std::fs::create_dir_all(&dir).unwrap();
+    config.Imap.host = String::from("127.0.0.1");
+    config.Imap.port = port;
+    config.Imap.encryption = Encryption::none;
+    config.reply_folder = None;
+    config.companies.push(Company {
+        name: String::from("acme"),
+        mail: String::from("privacy@acme.example"),
+        message_id: Some(String::from("<db-2026-acme-01.1.abc@me.example>")),
+        last_hit: Some(sent),
+        ..Company::new()
+    });
+    config.watch_replies();
+    let commands = server.join().unwrap();
+    let commands: Vec<_> = commands.iter().filter(|v| v.starts_with("UID")).collect();
+    assert_eq!(
+        commands,
+        [
+            &format!("UID SEARCH SINCE {}", sent.format("%-d-%b-%Y")),
+            "UID FETCH 100:149 (UID BODY.PEEK[])",
+            "UID FETCH 150:199 (UID BODY.PEEK[])",
+        ]
+    );
+    assert!(config.companies[0].is_fulfilled());
+    assert_eq!(config.checkpoints.values().next().unwrap().uid, 199);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn pinned_certificate() {
+    use super::{net::Stream, Encryption, ServerConfig};
+    let cert = include_bytes!("localhost.pem");
+    let key = include_bytes!("localhost.key");
+    let identity = native_tls::Identity::from_pkcs8(cert, key).unwrap();
+    let acceptor = native_tls::TlsAcceptor::new(identity).unwrap();
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    std::thread::spawn(move || {
+        for stream in listener.incoming().take(2) {
+            // the client hangs up after the handshake
+            let _ = acceptor.accept(stream.unwrap());
+        }
+    });
+    let der = native_tls::Certificate::from_pem(cert).unwrap().to_der();
+    let fingerprint = super::sha256(&der.unwrap());
+
+    let mut config = ServerConfig::new();
+    config.host = String::from("127.0.0.1");
+    config.port = port;
+    config.encryption = Encryption::tls;
+    config.fingerprint = Some(String::from("00").repeat(32));
+    let err = Stream::connect(&config).err().unwrap();
+    assert!(err.to_string().contains("does not match pinned"), "{}", err);
+    config.fingerprint = Some(fingerprint);
+    assert!(Stream::connect(&config).is_ok());
+}
+
+#[test]
+fn transport_maildir() {
+    use super::{transport::Backend, ServerConfig};
+    let dir = std::env::temp_dir().join(format!("datenbriefd-maildir-{}", std::process::id()));
+    let backend = Backend::parse("maildir", None, dir.to_str()).unwrap();
+    let mut transport = backend.connect(&ServerConfig::new()).unwrap();
+    transport
+        .send(
+            "me@example.com",
+            &["privacy@example.com"],
+            b"Subject: test\r\n\r\n",
+        )
+        .unwrap();
+    transport.close().unwrap();
+
+    let files: Vec<_> = std::fs::read_dir(dir.join("new")).unwrap().collect();
+    assert_eq!(files.len(), 1);
+    assert!(std::fs::read_dir(dir.join("tmp")).unwrap().next().is_none());
+    assert!(Backend::parse("maildir", None, None).is_err());
+    assert!(Backend::parse("carrier-pigeon", None, None).is_err());
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn transport_sendmail() {
+    use super::{transport::Backend, ServerConfig};
+    use std::os::unix::fs::PermissionsExt;
+    let dir = std::env::temp_dir().join(format!("datenbriefd-sendmail-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let script = dir.join("sendmail");
+    let stub = format!(
+        "#!/bin/sh\nprintf '%s\\n' \"$@\" > {0}/args\ncat > {0}/message\n",
+        dir.display()
+    );
+    std::fs::write(&script, stub).unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let backend = Backend::parse("sendmail", script.to_str(), None).unwrap();
+    let mut transport = backend.connect(&ServerConfig::new()).unwrap();
+    let message = b"To: privacy@example.com\r\nSubject: test\r\n\r\n.\r\nHallo\r\n";
+    transport
+        .send("me@example.com", &["privacy@example.com"], message)
+        .unwrap();
+    transport.close().unwrap();
+    let args = std::fs::read_to_string(dir.join("args")).unwrap();
+    assert_eq!(args, "-t\n-i\n-f\nme@example.com\n");
+    assert_eq!(
+        std::fs::read(dir.join("message")).unwrap(),
+        message.to_vec()
+    );
+
+    let backend = Backend::Sendmail(String::from("false"));
+    let mut transport = backend.connect(&ServerConfig::new()).unwrap();
+    assert!(transport.send("me@example.com", &[], message).is_err());
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn reference_and_reminder_threading() {
+    use super::{mail, schedule::Scheduler, Company};
+    use chrono::{Duration, TimeZone, Utc};
+    let now = Utc.ymd(2026, 3, 1).and_hms(9, 0, 0);
+    let mut company = Company {
+        name: String::from("Acme GmbH"),
+        alias: String::from("acme@me.example"),
+        ..Company::new()
+    };
+    assert_eq!(mail::reference(&company, now), "DB-2026-ACMEGMBH-01");
+    company.reference = Some(String::from("DB-2026-ACMEGMBH-01"));
+    assert_eq!(mail::reference(&company, now), "DB-2026-ACMEGMBH-02");
+    company.reference = Some(String::from("DB-2025-ACMEGMBH-04"));
+    assert_eq!(mail::reference(&company, now), "DB-2026-ACMEGMBH-01");
+
+    let id = mail::message_id("DB-2026-ACMEGMBH-01", &company.alias, now);
+    assert!(id.starts_with("<db-2026-acmegmbh-01."));
+    assert!(id.ends_with("@me.example>"));
+    assert_ne!(
+        id,
+        mail::message_id("DB-2026-ACMEGMBH-02", &company.alias, now)
+    );
+
+    let scheduler = Scheduler::new();
+    company.last_hit = Some(now);
+    assert_eq!(scheduler.reminder_at(&company), None);
+    company.message_id = Some(id.clone());
+    let deadline = Utc.ymd(2026, 4, 1).and_hms(23, 59, 59);
+    assert_eq!(scheduler.reminder_at(&company), Some(deadline));
+    company.reminder = 1;
+    assert_eq!(
+        scheduler.reminder_at(&company),
+        Some(deadline + Duration::days(14))
+    );
+
+    let reminder = mail::reminder(&company, Default::default(), "<r@me.example>", now);
+    assert_eq!(reminder.get("In-Reply-To"), Some(id.as_str()));
+    assert_eq!(reminder.get("References"), Some(id.as_str()));
+    assert!(reminder
+        .get("Subject")
+        .unwrap()
+        .starts_with("[DB-2025-ACMEGMBH-04]"));
+}
+
+#[test]
+fn bounce_marks_address_invalid() {
+    use super::{bounce, mime::Part, Company, Config};
+    use chrono::Utc;
+    let dsn = "From: Mail Delivery System <MAILER-DAEMON@mx.example.org>\r
+Subject: Undelivered Mail Returned to Sender\r
+Content-Type: multipart/report; report-type=delivery-status;\r
+\tboundary=\"BOUND\"\r
+\r
+--BOUND\r
+Content-Type: text/plain\r
+\r
+The mail system could not deliver your message.\r
+--BOUND\r
+Content-Type: message/delivery-status\r
+\r
+Reporting-MTA: dns; mx.example.org\r
+\r
+Final-Recipient: rfc822; privacy@acme.example\r
+Action: failed\r
+Status: 5.1.1\r
+Diagnostic-Code: smtp; 550 5.1.1 user unknown\r
+--BOUND\r
+Content-Type: text/rfc822-headers\r
+\r
+From: Max <acme@me.example>\r
+Message-ID: <db-2026-acme-01.20260301090000.abc@me.example>\r
+--BOUND--\r
+";
+    let parsed = bounce::parse(&Part::parse(dsn.as_bytes())).unwrap();
+    assert_eq!(parsed.recipients, vec!["privacy@acme.example"]);
+    assert_eq!(parsed.status.as_deref(), Some("5.1.1"));
+    assert_eq!(
+        parsed.message_ids,
+        vec!["db-2026-acme-01.20260301090000.abc@me.example"]
+    );
+
+    let delayed = dsn
+        .replace("Action: failed", "Action: delayed")
+        .replace("5.1.1", "4.4.1");
+    assert!(bounce::parse(&Part::parse(delayed.as_bytes())).is_none());
+
+    let mut config = Config::new();
+    config.time_file = std::env::temp_dir()
+        .join(format!("datenbriefd-bounce-{}.json", std::process::id()))
+        .to_string_lossy()
+        .to_string();
+    config.companies.push(Company {
+        name: String::from("acme"),
+        mail: String::from("privacy@acme.example"),
+        reference: Some(String::from("DB-2026-ACME-01")),
+        ..Company::new()
+    });
+    config.process_reply(dsn.as_bytes(), Utc::now());
+    assert!(config.companies[0].invalid.is_some());
+    assert!(config.status().contains("address invalid"));
+    std::fs::remove_file(&config.time_file).unwrap();
+
+    let plain = "From: postmaster@mx.example.org\r
+Subject: Undeliverable: Auskunftsersuchen\r
+\r
+Delivery has failed to these recipients: privacy@acme.example\r
+Remote server returned '550 5.1.10 RESOLVER.ADR.RecipientNotFound'\r
+";
+    let parsed = bounce::parse(&Part::parse(plain.as_bytes())).unwrap();
+    assert!(parsed
+        .recipients
+        .contains(&String::from("privacy@acme.example")));
+    assert_eq!(parsed.status.as_deref(), Some("5.1.10"));
+
+    // quoting the request does not make the company the failed recipient
+    let cyrillic = "From: postmaster@mx.example.ru\r
+Subject: Undeliverable\r
+Content-Type: text/plain; charset=utf-8\r
+\r
+Сообщение не доставлено: ivan@mail.example.ru\r
+Message-ID: <db-2026-acme-01.20260301090000.abc@me.example>\r
+";
+    let parsed = bounce::parse(&Part::parse(cyrillic.as_bytes())).unwrap();
+    assert_eq!(parsed.recipients, vec!["ivan@mail.example.ru"]);
+    config.companies[0].invalid = None;
+    config.process_reply(cyrillic.as_bytes(), Utc::now());
+    assert!(config.companies[0].invalid.is_none());
+}
+
+#[test]
+fn nested_parts_depth() {
+    use super::mime::{Part, MAX_DEPTH};
+    let mut raw = String::from("Content-Type: text/plain\r\n\r\nbottom\r\n");
+    for i in 0..1000 {
+        raw = if i % 2 == 0 {
+            format!("Content-Type: message/rfc822\r\n\r\n{}", raw)
+        } else {
+            format!(
+                "Content-Type: multipart/mixed; boundary=b{0}\r\n\r\n--b{0}\r\n{1}\r\n--b{0}--\r\n",
+                i, raw
+            )
+        };
+    }
+    let part = Part::parse(raw.as_bytes());
+    assert_eq!(part.walk().len(), MAX_DEPTH + 1);
+    let opaque = part.leaves()[0];
+    assert!(opaque.parts.is_empty() && String::from_utf8_lossy(&opaque.body).contains("bottom"));
+}
+
+#[test]
+fn control_command_parse() {
+    use super::{control::Command, mime::Part};
+    assert_eq!(Command::parse("STATUS"), Ok(Command::Status));
+    assert_eq!(
+        Command::parse("send Acme GmbH"),
+        Ok(Command::Send(String::from("Acme GmbH")))
+    );
+    assert!(Command::parse("pause").is_err());
+    assert!(Command::parse("explode acme").is_err());
+
+    let mail = Part::parse(b"From: me@example.com\r\nSubject: \r\n\r\n\r\nresume acme\r\n");
+    assert_eq!(
+        Command::from_message(&mail),
+        Ok(Command::Resume(String::from("acme")))
+    );
+}
+
+#[test]
+fn archive_reply_with_attachment() {
+    use super::{archive::Archive, mime::Part, Company};
+    use chrono::Utc;
+    let dir = std::env::temp_dir().join(format!("datenbriefd-archive-{}", std::process::id()));
+    let archive = Archive {
+        dir: dir.to_string_lossy().to_string(),
+    };
+    let company = Company {
+        name: String::from("acme"),
+        reference: Some(String::from("DB-2026-ACME-01")),
+        ..Company::new()
+    };
+    let raw = b"From: privacy@acme.example\r
+Subject: =?utf-8?Q?Ihre_Auskunft_=C3=BCber_Daten?=\r
+Date: Tue, 3 Mar 2026 10:00:00 +0100\r
+Content-Type: multipart/mixed; boundary=b1\r
+\r
+--b1\r
+Content-Type: text/plain\r
+\r
+anbei Ihre Daten\r
+--b1\r
+Content-Type: application/json\r
+Content-Disposition: attachment; filename=\"../export.json\"\r
+Content-Transfer-Encoding: base64\r
+\r
+eyJuYW1lIjoiTWF4In0=\r
+--b1--\r
+";
+    let message = Part::parse(raw);
+    assert_eq!(message.text().trim(), "anbei Ihre Daten");
+    let stored = archive.store(&company, raw, &message, Utc::now()).unwrap();
+    assert!(stored.starts_with(dir.join("acme")));
+    assert_eq!(
+        std::fs::read(stored.join("export.json")).unwrap(),
+        br#"{"name":"Max"}"#
+    );
+
+    archive.store(&company, raw, &message, Utc::now()).unwrap();
+    let records = archive.records(&company).unwrap();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0]["subject"], "Ihre Auskunft über Daten");
+    assert_eq!(records[0]["reference"], "DB-2026-ACME-01");
+    assert_eq!(records[0]["attachments"][0]["name"], "export.json");
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn archive_pairs_password_mail() {
+    use super::{archive::Archive, mime::Part, unpack, Company};
+    use chrono::Utc;
+    let dir = std::env::temp_dir().join(format!("datenbriefd-pair-{}", std::process::id()));
+    let archive = Archive {
+        dir: dir.to_string_lossy().to_string(),
+    };
+    let company = Company {
+        name: String::from("acme"),
+        ..Company::new()
+    };
+    let locked = b"From: privacy@acme.example\r
+Subject: Ihre Daten\r
+Content-Type: multipart/mixed; boundary=b1\r
+\r
+--b1\r
+Content-Type: text/plain\r
+\r
+Das Passwort erhalten Sie in einer separaten Mail.\r
+--b1\r
+Content-Type: application/zip; name=daten.zip\r
+Content-Transfer-Encoding: base64\r
+\r
+UEsDBAoACQAAABQPU11wX6fQGwAAAA8AAAAJABwAZGF0YS5qc29uVVQJAAPXeNVq13jVanV4CwAB\r
+BAAAAAAEAAAAAO1VGu+wUqYbxkGhdB/b9veQP5jmvtJTjNtU0FBLBwhwX6fQGwAAAA8AAABQSwEC\r
+HgMKAAkAAAAUD1NdcF+n0BsAAAAPAAAACQAYAAAAAAABAAAApIEAAAAAZGF0YS5qc29uVVQFAAPX\r
+eNVqdXgLAAEEAAAAAAQAAAAAUEsFBgAAAAABAAEATwAAAG4AAAAAAA==\r
+--b1--\r
+";
+    let password = "From: privacy@acme.example\r
+Subject: Passwort\r
+\r
+Guten Tag,\r
+\r
+das Passwort für die Datei lautet: \"geheim42\".\r
+";
+    assert_eq!(
+        unpack::passwords("Password:\n  geheim42\nPasswort folgt separat"),
+        vec!["geheim42"]
+    );
+
+    let now = Utc::now();
+    let stored = archive
+        .store(&company, locked, &Part::parse(locked), now)
+        .unwrap();
+    assert_eq!(
+        archive.records(&company).unwrap()[0]["attachments"][0]["encrypted"],
+        true
+    );
+    assert!(!stored.join("daten.zip-unpacked/data.json").exists());
+
+    let later = now + chrono::Duration::minutes(5);
+    archive
+        .store(
+            &company,
+            password.as_bytes(),
+            &Part::parse(password.as_bytes()),
+            later,
+        )
+        .unwrap();
+    let records = archive.records(&company).unwrap();
+    assert_eq!(records[1]["passwords"][0], "geheim42");
+    assert_eq!(records[0]["attachments"][0]["password"], "geheim42");
+    assert_eq!(
+        std::fs::read(stored.join("daten.zip-unpacked/data.json")).unwrap(),
+        b"{\"name\":\"Max\"}\n"
+    );
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn acknowledgement_keeps_reminders() {
+    use super::{Company, Config};
+    use chrono::{Duration, Utc};
+    let now = Utc::now();
+    let dir = std::env::temp_dir().join(format!("datenbriefd-ack-{}", std::process::id()));
+    let mut config = Config::new();
+    config.archive.dir = dir.join("archive").to_string_lossy().to_string();
+    config.time_file = dir.join("time.json").to_string_lossy().to_string();
+    std::fs::create_dir_all(&dir).unwrap();
+    config.companies.push(Company {
+        name: String::from("acme"),
+        mail: String::from("privacy@acme.example"),
+        message_id: Some(String::from("<db-2026-acme-01.1.abc@me.example>")),
+        reference: Some(String::from("DB-2026-ACME-01")),
+        last_hit: Some(now - Duration::days(50)),
+        reminder: 1,
+        ..Company::new()
+    });
+
+    let ack = "From: support@acme.example\r
+Subject: [Ticket#20261019123] Re: [DB-2026-ACME-01] Auskunftsersuchen\r
+Auto-Submitted: auto-replied\r
+\r
+Wir haben Ihre Anfrage erhalten und melden uns.\r
+";
+    config.process_reply(ack.as_bytes(), now);
+    assert_eq!(config.companies[0].ticket.as_deref(), Some("20261019123"));
+    assert!(config.companies[0].answered.is_none());
+    assert_eq!(
+        config.scheduler.reminders_due(&config.companies, now),
+        vec![0]
+    );
+
+    let heuristic = "From: privacy@acme.example\r
+Subject: Re: [DB-2026-ACME-01] Auskunftsersuchen\r
+\r
+Vielen Dank, wir haben Ihre Anfrage erhalten. Vorgangsnummer: DB-2026-ACME-01\r
+";
+    let message = super::mime::Part::parse(heuristic.as_bytes());
+    assert!(config.autoreply.is_auto(&message));
+    assert_eq!(
+        config.autoreply.ticket(&message, Some("DB-2026-ACME-01")),
+        None
+    );
+
+    let answer = "From: privacy@acme.example\r
+Subject: Re: [DB-2026-ACME-01] Auskunftsersuchen\r
+\r
+anbei die Auskunft nach Art. 15 DSGVO.\r
+";
+    // the answer to last year's request found on the first sync leaves this one open
+    let old = format!(
+        "Date: {}\r\n{}",
+        (now - Duration::days(400)).to_rfc2822(),
+        answer
+    );
+    config.process_reply(old.as_bytes(), now);
+    assert!(config.companies[0].answered.is_none());
+    assert!(config.companies[0].response_days().is_empty());
+
+    config.process_reply(answer.as_bytes(), now);
+    assert_eq!(config.companies[0].answered, Some(now));
+    assert!(config
+        .scheduler
+        .reminders_due(&config.companies, now)
+        .is_empty());
+    assert!(config.status().contains("after 50 days"));
+    assert_eq!(config.companies[0].reminder, 0);
+    assert_eq!(config.companies[0].response_days(), &[50]);
+    assert_eq!(config.companies[0].next_hit, now + Duration::days(365));
+
+    // more data for the same request does not move the next request again
+    config.process_reply(answer.as_bytes(), now + Duration::days(2));
+    assert_eq!(config.companies[0].response_days(), &[50]);
+    assert_eq!(config.companies[0].next_hit, now + Duration::days(365));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn classify_replies() {
+    use super::{
+        classify::{Classifier, Outcome},
+        control::Command,
+        mime::Part,
+        Company, Config,
+    };
+    use chrono::{Duration, Utc};
+    let classifier = Classifier::new();
+    let classify = |text: &str| {
+        let raw = format!(
+            "From: privacy@acme.example\r\nSubject: Re: Auskunft\r\n\r\n{}",
+            text
+        );
+        classifier.classify(&Part::parse(raw.as_bytes()))
+    };
+    assert_eq!(
+        classify("Bitte senden Sie uns eine Kopie Ihres Personalausweises."),
+        Some(Outcome::Verification)
+    );
+    assert_eq!(
+        classify("Zu Ihrer Person sind bei uns keine Daten gespeichert."),
+        Some(Outcome::NoData)
+    );
+    assert_eq!(
+        classify("Ihre Daten finden Sie in unserem Datenschutz-Center."),
+        Some(Outcome::Portal)
+    );
+    assert_eq!(
+        classify("Anbei erhalten Sie die Auskunft.\r\n\r\n> (Art. 12 Abs. 3 DSGVO) ablehnen\r\n"),
+        Some(Outcome::Provided)
+    );
+    assert_eq!(classify("Danke, wir melden uns."), None);
+
+    let dir = std::env::temp_dir().join(format!("datenbriefd-classify-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let now = Utc::now();
+    let mut config = Config::new();
+    config.archive.dir = dir.join("archive").to_string_lossy().to_string();
+    config.time_file = dir.join("time.json").to_string_lossy().to_string();
+    config.companies.push(Company {
+        name: String::from("Acme GmbH"),
+        mail: String::from("privacy@acme.example"),
+        message_id: Some(String::from("<db-2026-acme-01.1.abc@me.example>")),
+        last_hit: Some(now - Duration::days(40)),
+        ..Company::new()
+    });
+    let reply = "From: privacy@acme.example\r
+Subject: Re: Auskunft\r
+\r
+Bitte weisen Sie Ihre Identität nach.\r
+";
+    config.process_reply(reply.as_bytes(), now);
+    assert!(!config.companies[0].paused);
+    assert!(config.status().contains("verifying identity since"));
+
+    // the data arriving later starts the periodic schedule again
+    let data = "From: privacy@acme.example\r
+Subject: Re: Auskunft\r
+\r
+Anbei erhalten Sie die Auskunft.\r
+";
+    let later = now + Duration::days(3);
+    config.process_reply(data.as_bytes(), later);
+    let v = &config.companies[0];
+    assert_eq!(v.verifying, None);
+    let next = later + Duration::days(365);
+    assert_eq!(v.next_hit, next);
+    assert_eq!(config.scheduler.due(&config.companies, next), vec![0]);
+
+    // mail without an open request does not count as an answer, it is only noted
+    let newsletter = "From: privacy@acme.example\r
+Subject: Neuigkeiten\r
+\r
+Danke, wir melden uns.\r
+";
+    config.process_reply(newsletter.as_bytes(), later + Duration::days(1));
+    let v = &config.companies[0];
+    assert_eq!(v.answered, Some(later));
+    assert!(v.history.last().unwrap().1.contains("needs review"));
+
+    let command = Command::parse("classify acme gmbh refused").unwrap();
+    assert_eq!(
+        command,
+        Command::Classify(String::from("acme gmbh"), Outcome::Refused)
+    );
+    config.execute(&command, now).unwrap();
+    assert!(config.companies[0].answered.is_some());
+    assert!(Command::parse("classify acme").is_err());
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn imap_mailbox_encoding() {
+    use super::imap::encode_mailbox;
+    assert_eq!(encode_mailbox("Datenbrief/Acme"), "Datenbrief/Acme");
+    assert_eq!(
+        encode_mailbox("Datenbrief/Müller & Söhne"),
+        "Datenbrief/M&APw-ller &- S&APY-hne"
+    );
+    assert_eq!(encode_mailbox("日本語"), "&ZeVnLIqe-");
+}
+
+#[test]
+fn imap_checkpoint_state() {
+    use super::{watch::Checkpoint, Company, Config};
+    let time_file = std::env::temp_dir()
+        .join(format!(
+            "datenbriefd-checkpoint-{}.json",
+            std::process::id()
+        ))
+        .to_string_lossy()
+        .to_string();
+    let mut config = Config::new();
+    config.time_file = time_file.clone();
+    config.companies.push(Company {
+        name: String::from("acme"),
+        ..Company::new()
+    });
+    let checkpoint = Checkpoint {
+        uid_validity: 7,
+        uid: 42,
+    };
+    config
+        .checkpoints
+        .insert(String::from("me@imap.example/INBOX"), checkpoint.clone());
+    config.processed.push(String::from("abc@acme.example"));
+    config.save_time();
+
+    let mut loaded = Config::new();
+    loaded.time_file = time_file.clone();
+    loaded.companies.push(Company {
+        name: String::from("acme"),
+        ..Company::new()
+    });
+    loaded.load();
+    assert_eq!(loaded.checkpoints["me@imap.example/INBOX"], checkpoint);
+    assert_eq!(loaded.processed, vec!["abc@acme.example"]);
+    assert!(loaded.companies[0].known);
+    std::fs::remove_file(time_file).unwrap();
+}
+
+#[test]
+fn control_requires_token() {
+    use super::{Company, Config};
+    use chrono::{Duration, TimeZone, Utc};
+    let dir = std::env::temp_dir().join(format!("datenbriefd-auth-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut config = Config::new();
+    config.time_file = dir.join("time.json").to_string_lossy().to_string();
+    config.control_auth.audit_log = dir.join("audit.log").to_string_lossy().to_string();
+    config
+        .control_auth
+        .senders
+        .push(String::from("op@me.example"));
+    config.control_auth.token = Some(String::from("s3cret"));
+    config.control_address = Some(String::from("datenbrief@me.example"));
+    let maildir = dir.join("maildir");
+    config.transport = super::transport::Backend::Maildir(maildir.to_string_lossy().to_string());
+    config.companies.push(Company {
+        name: String::from("acme"),
+        ..Company::new()
+    });
+
+    let now = Utc.ymd(2026, 10, 19).and_hms(12, 0, 0);
+    // a forged sender without the token
+    config.control_mail(
+        b"Date: Mon, 19 Oct 2026 11:59:00 +0000\r\nFrom: Op <op@me.example>\r\nSubject: pause acme\r\n\r\n",
+        now,
+    );
+    config.control_mail(
+        b"Date: Mon, 19 Oct 2026 11:59:00 +0000\r\nFrom: evil@example.com\r\nSubject: pause acme\r\n\r\ntoken s3cret\r\n",
+        now,
+    );
+    config.control_mail(
+        b"Date: Mon, 19 Oct 2026 11:59:00 +0000\r\nFrom: op@me.example\r\nSubject: pause acme\r\n\r\ntoken: wrong\r\n",
+        now,
+    );
+    assert!(!config.companies[0].paused);
+
+    config.control_mail(
+        b"Date: Mon, 19 Oct 2026 11:59:00 +0000\r\nFrom: op@me.example\r\nSubject: \r\n\r\nToken: s3cret\r\npause acme\r\n",
+        now,
+    );
+    assert!(config.companies[0].paused);
+
+    // only the accepted command is answered
+    let replies: Vec<_> = std::fs::read_dir(maildir.join("new")).unwrap().collect();
+    assert_eq!(replies.len(), 1);
+    config.control_mail(
+        b"Date: Mon, 19 Oct 2026 11:59:00 +0000\r\nFrom: op@me.example\r\nSubject: status\r\nMessage-ID: <1@me.example>\r\n\r\ntoken s3cret\r\n",
+        now,
+    );
+    let reply = std::fs::read_dir(maildir.join("new"))
+        .unwrap()
+        .map(|v| std::fs::read_to_string(v.unwrap().path()).unwrap())
+        .find(|v| v.contains("In-Reply-To: <1@me.example>"))
+        .unwrap();
+    assert!(reply.contains("Subject: Re: status\r\n"));
+    assert!(reply.contains("ok: status\r\n"));
+    assert!(reply.contains("last reply"));
+
+    // neither the same mail again nor an old one is accepted
+    let resume = b"Date: Mon, 19 Oct 2026 11:59:00 +0000\r
+From: op@me.example\r
+Message-ID: <2@me.example>\r
+\r
+token s3cret\r
+resume acme\r
+";
+    config.control_mail(resume, now + Duration::minutes(5));
+    assert!(!config.companies[0].paused);
+    config.companies[0].paused = true;
+    config.control_mail(resume, now + Duration::minutes(10));
+    let stale = String::from_utf8_lossy(resume).replace("<2@", "<3@");
+    config.control_mail(stale.as_bytes(), now + Duration::days(3));
+    assert!(config.companies[0].paused);
+
+    let audit = std::fs::read_to_string(dir.join("audit.log")).unwrap();
+    assert_eq!(audit.matches(" rejected ").count(), 5);
+    assert!(audit.contains("rejected <2@me.example> from op@me.example: mail was already handled"));
+    assert!(audit.contains("is too far from now"));
+    assert!(audit.contains("sender 'evil@example.com' is not allowed"));
+    assert!(audit.contains("accepted - from op@me.example: pause acme ok"));
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn control_signature_replay() {
+    use super::{Company, Config};
+    use chrono::{Duration, TimeZone, Utc};
+    use std::os::unix::fs::PermissionsExt;
+    let dir = std::env::temp_dir().join(format!("datenbriefd-replay-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let now = Utc.ymd(2026, 10, 19).and_hms(12, 0, 0);
+    // stands in for gpgv, every signature is valid and was made a minute ago
+    let gpgv = dir.join("gpgv");
+    let status = format!(
+        "#!/bin/sh\necho '[GNUPG:] VALIDSIG ABCD 2026-10-19 {} 0 4 0 22 8 01 ABCD'\n",
+        (now - Duration::minutes(1)).timestamp()
+    );
+    std::fs::write(&gpgv, status).unwrap();
+    std::fs::set_permissions(&gpgv, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let mut config = Config::new();
+    config.time_file = dir.join("time.json").to_string_lossy().to_string();
+    config.control_auth.audit_log = dir.join("audit.log").to_string_lossy().to_string();
+    config
+        .control_auth
+        .senders
+        .push(String::from("op@me.example"));
+    config.control_auth.keyring = Some(dir.join("keyring.gpg").to_string_lossy().to_string());
+    config.control_auth.gpgv = gpgv.to_string_lossy().to_string();
+    config.companies.push(Company {
+        name: String::from("acme"),
+        ..Company::new()
+    });
+    let mail = |id: &str, date: chrono::DateTime<Utc>| {
+        format!(
+            "Date: {}\r
+From: op@me.example\r
+Message-ID: <{}@me.example>\r
+\r
+-----BEGIN PGP SIGNED MESSAGE-----\r
+Hash: SHA256\r
+\r
+pause acme\r
+-----BEGIN PGP SIGNATURE-----\r
+\r
+iHUEARYIAB0WIQ==\r
+-----END PGP SIGNATURE-----\r
+",
+            date.to_rfc2822(),
+            id
+        )
+    };
+    config.control_mail(mail("s1", now).as_bytes(), now);
+    assert!(config.companies[0].paused);
+
+    // fresh headers do not make the same signature new
+    config.companies[0].paused = false;
+    let later = now + Duration::minutes(5);
+    config.control_mail(mail("s2", later).as_bytes(), later);
+    let stale = now + Duration::days(3);
+    config.control_mail(mail("s3", stale).as_bytes(), stale);
+    assert!(!config.companies[0].paused);
+
+    let audit = std::fs::read_to_string(dir.join("audit.log")).unwrap();
+    assert!(audit.contains("rejected <s2@me.example> from op@me.example: mail was already handled"));
+    assert!(audit.contains("rejected <s3@me.example> from op@me.example: signature from"));
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn control_company_snippet() {
+    use super::{conf, control::Command, Company, Config};
+    use chrono::Utc;
+    let dir = std::env::temp_dir().join(format!("datenbriefd-conf-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("conf.d").join("control.toml");
+    let mut config = Config::new();
+    config.time_file = dir.join("time.json").to_string_lossy().to_string();
+    config.company_file = Some(file.to_string_lossy().to_string());
+    config.interval = Some(180);
+    let next = Utc::now() + chrono::Duration::days(30);
+    config.companies.push(Company {
+        name: String::from("Acme"),
+        mail: String::from("old@acme.example"),
+        next_hit: next,
+        known: true,
+        ..Company::new()
+    });
+
+    let text = "token s3cret\ncompany\n[companies.acme]\nmail = \"privacy@acme.example\"\n\n[companies.\"Shop.io\"]\nmail = \"dsgvo@shop.example\"\nalias = \"shop@me.example\"\n-- \nsent from my phone\n";
+    let command = Command::from_text(text).unwrap();
+    let now = Utc::now();
+    assert!(config.execute(&command, now).is_ok());
+    assert_eq!(config.companies.len(), 2);
+    // no new request for a changed address
+    assert_eq!(config.companies[0].mail, "privacy@acme.example");
+    assert_eq!(config.companies[0].next_hit, next);
+    assert_eq!(config.companies[1].interval, 180);
+    assert!(config.companies[1].known);
+
+    let command = Command::from_text("company shop.io\ninterval = 90\n").unwrap();
+    assert!(config.execute(&command, now).is_ok());
+    assert!(
+        Command::from_text("company acme\nmial = \"typo@acme.example\"\n")
+            .and_then(|v| config.execute(&v, now))
+            .is_err()
+    );
+    assert!(Command::from_text("company\nmail = \"x@example.com\"\n").is_err());
+
+    let stored: toml::Value = toml::from_str(&std::fs::read_to_string(&file).unwrap()).unwrap();
+    assert_eq!(
+        stored["companies"]["Acme"]["mail"].as_str(),
+        Some("privacy@acme.example")
+    );
+    assert_eq!(
+        stored["companies"]["Shop.io"]["alias"].as_str(),
+        Some("shop@me.example")
+    );
+    assert_eq!(
+        stored["companies"]["Shop.io"]["interval"].as_integer(),
+        Some(90)
+    );
+
+    // config files keep working with mistakes, and without the main file
+    std::fs::write(dir.join("conf.d").join("broken.toml"), "[companies").unwrap();
+    let mut merged = toml::Value::Table(Default::default());
+    conf::merge_conf_dir(&mut merged, &dir.join("conf.d")).unwrap();
+    let snippet = "interval = 0\nmail = 7\nmial = \"typo@acme.example\"\nname = \"Erika\"";
+    let stored = merged["companies"].as_table_mut().unwrap();
+    stored.insert(String::from("Acme"), toml::from_str(snippet).unwrap());
+    stored.insert(String::from("Odd"), toml::Value::Integer(1));
+    let companies = conf::companies(&merged, Some(180));
+    let names: Vec<&str> = companies.iter().map(|v| v.name.as_str()).collect();
+    assert_eq!(names, ["Acme", "Shop.io"]);
+    assert_eq!(companies[0].interval, 180);
+    assert_eq!(companies[0].onw_name, "Erika");
+    assert_eq!(companies[1].interval, 90);
+
+    // reloading works without config.toml and keeps the companies from the command line
+    config.config_file = Some(dir.join("config.toml").to_string_lossy().to_string());
+    config.command_line = vec![Company {
+        name: String::from("Cli"),
+        mail: String::from("privacy@cli.example"),
+        ..Company::new()
+    }];
+    assert_eq!(
+        config.reload(now).unwrap(),
+        "loaded 3 companies, 1 added, 0 removed"
+    );
+    let names: Vec<&str> = config.companies.iter().map(|v| v.name.as_str()).collect();
+    assert_eq!(names, ["Cli", "Acme", "Shop.io"]);
+    assert_eq!(config.companies[1].next_hit, next);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn digest_summary() {
+    use super::{digest::Period, schedule::add_months, Company, Config};
+    use chrono::{prelude::*, Duration};
+    let jan = Utc.ymd(2027, 1, 31).and_hms(9, 0, 0);
+    assert_eq!(add_months(jan, 1), Utc.ymd(2027, 2, 28).and_hms(9, 0, 0));
+    assert_eq!(add_months(jan, 13), Utc.ymd(2028, 2, 29).and_hms(9, 0, 0));
+
+    let dir = std::env::temp_dir().join(format!("datenbriefd-digest-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let now = Utc::now();
+    let mut config = Config::new();
+    config.time_file = dir.join("time.json").to_string_lossy().to_string();
+    config.spool.dir = dir.join("spool").to_string_lossy().to_string();
+    let maildir = dir.join("maildir");
+    config.transport = super::transport::Backend::Maildir(maildir.to_string_lossy().to_string());
+    config.digest.period = Some(Period::Weekly);
+    config.digest.to = Some(String::from("op@me.example"));
+    config.control_address = Some(String::from("datenbrief@me.example"));
+    config.companies.push(Company {
+        name: String::from("acme"),
+        next_hit: now + Duration::days(3),
+        ..Company::new()
+    });
+    config.companies.push(Company {
+        name: String::from("slowpoke"),
+        next_hit: now + Duration::days(300),
+        last_hit: Some(now - Duration::days(45)),
+        message_id: Some(String::from("<DB-1@me.example>")),
+        reminder: 2,
+        ..Company::new()
+    });
+    config.companies.push(Company {
+        name: String::from("gone"),
+        next_hit: now,
+        invalid: Some((
+            String::from("x@gone.example"),
+            String::from("550 no such user"),
+            now,
+        )),
+        ..Company::new()
+    });
+
+    let text = config.digest_text(now - Duration::weeks(1), now, now + Duration::weeks(1));
+    let upcoming = &text[text.find("sends until").unwrap()..text.find("past the legal").unwrap()];
+    assert!(upcoming.contains("acme") && !upcoming.contains("gone"));
+    assert!(text.contains("slowpoke             sent"));
+    assert!(text.contains("x@gone.example bounced: 550 no such user"));
+    assert!(text.contains("replies:\n  none\n"));
+
+    // the first digest goes out a full period after the start
+    config.send_digest(now);
+    assert!(!maildir.join("new").exists());
+    config.send_digest(now + Duration::days(8));
+    let digest = std::fs::read_dir(maildir.join("new"))
+        .unwrap()
+        .map(|v| std::fs::read_to_string(v.unwrap().path()).unwrap())
+        .next()
+        .unwrap();
+    assert!(digest.contains("To: op@me.example\r\n"));
+    assert!(digest.contains("past the legal deadline:"));
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn api_socket_requests() {
+    use super::{
+        api::{self, Request},
+        watch::Event,
+        Config,
+    };
+    use chrono::Utc;
+    use std::io::{BufRead, BufReader, Write};
+    assert_eq!(
+        Request::parse(r#"{"op": "pause", "company": "acme"}"#),
+        Ok(Request::Pause(String::from("acme")))
+    );
+    assert_eq!(
+        Request::parse(&Request::Reload.to_json().to_string()),
+        Ok(Request::Reload)
+    );
+    assert!(Request::parse(r#"{"op": "history"}"#).is_err());
+
+    let dir = std::env::temp_dir().join(format!("datenbriefd-api-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("conf.d")).unwrap();
+    let file = dir.join("config.toml");
+    std::fs::write(&file, "[companies.acme]\nmail = \"privacy@acme.example\"\n").unwrap();
+    let mut config = Config::new();
+    config.time_file = dir.join("time.json").to_string_lossy().to_string();
+    config.config_file = Some(file.to_string_lossy().to_string());
+    let now = Utc::now();
+    assert!(config.reload(now).is_ok());
+
+    let (tx, rx) = std::sync::mpsc::channel();
+    let socket = dir.join("api.sock").to_string_lossy().to_string();
+    api::spawn_socket(&socket, tx).unwrap();
+    let client = std::thread::spawn(move || {
+        let mut stream = std::os::unix::net::UnixStream::connect(&socket).unwrap();
+        let mut lines = BufReader::new(stream.try_clone().unwrap()).lines();
+        let mut send = |line: &str| {
+            writeln!(stream, "{}", line).unwrap();
+            serde_json::from_str::<serde_json::Value>(&lines.next().unwrap().unwrap()).unwrap()
+        };
+        let paused = send(r#"{"op": "pause", "company": "ACME"}"#);
+        let unknown = send(r#"{"op": "explode"}"#);
+        let history = send(r#"{"op": "history", "company": "acme"}"#);
+        (paused, unknown, history)
+    });
+    for _ in 0..2 {
+        if let Event::Request(request, reply) = rx.recv().unwrap() {
+            reply
+                .send(api::response(config.request(&request, now)))
+                .unwrap();
+        }
+    }
+    let (paused, unknown, history) = client.join().unwrap();
+    assert_eq!(paused["result"], "paused acme");
+    assert_eq!(unknown["ok"], false);
+    assert_eq!(history["result"]["events"][0]["event"], "paused");
+
+    // reloading keeps the state and picks up conf.d
+    std::fs::write(
+        dir.join("conf.d").join("control.toml"),
+        "[companies.shop]\nmail = \"dsgvo@shop.example\"\n",
+    )
+    .unwrap();
+    assert_eq!(
+        config.reload(now).unwrap(),
+        "loaded 2 companies, 1 added, 0 removed"
+    );
+    assert!(config
+        .request(&Request::Status(Some(String::from("acme"))), now)
+        .unwrap()["paused"]
+        .as_bool()
+        .unwrap());
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn http_dashboard_routes() {
+    use super::{api, http, watch::Event, Company, Config};
+    use chrono::Utc;
+    use std::io::{Read, Write};
+    assert_eq!(http::bind_address("8080"), "127.0.0.1:8080");
+    assert_eq!(http::bind_address("[::1]:8080"), "[::1]:8080");
+
+    let dir = std::env::temp_dir().join(format!("datenbriefd-http-{}", std::process::id()));
+    let mut config = Config::new();
+    config.archive.dir = dir.to_string_lossy().to_string();
+    config.companies.push(Company {
+        name: String::from("Müller AG"),
+        ..Company::new()
+    });
+    let reply = b"From: privacy@mueller.example\r\n\r\nDas Passwort lautet: Geheim123\r\n";
+    let message = super::mime::Part::parse(reply);
+    let v = &config.companies[0];
+    config
+        .archive
+        .store(v, reply, &message, Utc::now())
+        .unwrap();
+    let (tx, rx) = std::sync::mpsc::channel();
+    let address = http::spawn("127.0.0.1:0", tx).unwrap();
+    std::thread::spawn(move || {
+        for event in rx {
+            if let Event::Request(request, reply) = event {
+                let _ = reply.send(api::response(config.request(&request, Utc::now())));
+            }
+        }
+    });
+    let get = |path: &str, host: &str| {
+        let mut stream = std::net::TcpStream::connect(address).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: {}\r\n\r\n", path, host).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    };
+    let host = address.to_string();
+    assert!(get("/", &host).contains("<title>datenbriefd</title>"));
+    let history = get("/api/companies/m%C3%BCller%20ag/history", &host);
+    assert!(history.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(history.contains(r#""company":"Müller AG""#));
+    // the dashboard has no login, passwords for attachments stay in the archive
+    assert!(history.contains("privacy@mueller.example"));
+    assert!(!history.contains("Geheim123") && !history.contains("password"));
+    assert!(get("/api/companies/nobody", &host).starts_with("HTTP/1.1 404"));
+    // names other than localhost may be rebound to it by any web page
+    assert!(get("/api/status", "evil.example:80").starts_with("HTTP/1.1 403"));
+    assert!(get("/api/status", "localhost").contains(r#""overdue":false"#));
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn letter_languages() {
+    use super::{
+        template::{Language, LANGUAGES},
+        Company, Config,
+    };
+    use chrono::prelude::*;
+    assert_eq!(Language::parse("EN_GB"), Ok(Language::British));
+    assert!(Language::parse("tlh").is_err());
+    let sent = Utc.ymd(2026, 3, 5).and_hms(10, 0, 0);
+    assert_eq!(Language::German.date(sent), "05.03.2026");
+    assert_eq!(Language::British.date(sent), "5 March 2026");
+    assert_eq!(Language::French.date(sent), "5 mars 2026");
+    assert_eq!(Language::Spanish.date(sent), "5 de marzo de 2026");
+
+    let company = Company {
+        name: String::from("Acme Ltd"),
+        alias: String::from("acme@me.example"),
+        onw_name: String::from("Erika Mustermann"),
+        last_hit: Some(sent),
+        ..Company::new()
+    };
+    for language in LANGUAGES.iter() {
+        let request = language.request(Default::default(), &company, "DB-2026-ACME-01", "");
+        assert!(request.contains("DB-2026-ACME-01") && request.contains("acme@me.example"));
+        assert!(!request.contains('{'), "placeholder left in {}", language);
+        let reminder = language.reminder(Default::default(), &company, "X", Some(sent), true);
+        assert!(reminder.contains("2026"));
+        assert_eq!(Language::parse(&language.to_string()), Ok(*language));
+    }
+    let request = |language: Language| language.request(Default::default(), &company, "X", "");
+    assert!(request(Language::German).contains("§ 34 BDSG"));
+    assert!(request(Language::British).contains("UK GDPR"));
+    assert!(!request(Language::English).contains("UK GDPR"));
+
+    // the company language wins over the global one
+    let dir = std::env::temp_dir().join(format!("datenbriefd-lang-{}", std::process::id()));
+    let mut config = Config::new();
+    config.spool.dir = dir.to_string_lossy().to_string();
+    config.language = Language::French;
+    config.companies.push(Company {
+        language: Language::parse("en-gb").ok(),
+        ..company.clone()
+    });
+    config.companies.push(Company {
+        name: String::from("Société"),
+        ..company
+    });
+    let now = Utc::now();
+    config.hit(0, now, false);
+    config.hit(1, now, false);
+    let entries = config.spool.entries().unwrap();
+    let message =
+        |i: usize| String::from_utf8(config.spool.message(&entries[i].id).unwrap()).unwrap();
+    assert!(message(0).contains("Subject access request under Art. 15 UK GDPR"));
+    assert!(message(1).contains("Madame, Monsieur"));
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn request_rights() {
+    use super::{
+        api::Request,
+        classify::Outcome,
+        control::Command,
+        mail,
+        right::{Right, RIGHTS},
+        template::LANGUAGES,
+        transport::Backend,
+        Company, Config,
+    };
+    use chrono::prelude::*;
+    assert_eq!(Right::parse("Erasure"), Ok(Right::Erasure));
+    assert_eq!(Right::parse("art. 20"), Ok(Right::Portability));
+    assert!(Right::parse("19").is_err());
+
+    let company = Company {
+        name: String::from("Acme"),
+        alias: String::from("acme@me.example"),
+        onw_name: String::from("Erika Mustermann"),
+        ..Company::new()
+    };
+    for language in LANGUAGES.iter() {
+        for right in RIGHTS.iter() {
+            let letter = format!("{} {}", language, right);
+            let subject = language.request_subject(*right, "R");
+            let text = language.request(*right, &company, "R", "name is Erika, not Erik");
+            assert!(subject.contains(&right.article().to_string()), "{}", letter);
+            assert!(text.contains("name is Erika, not Erik\n\n"), "{}", letter);
+            assert!(
+                !text.contains('{') && !text.contains("\n\n\n"),
+                "{}",
+                letter
+            );
+            let reminder = language.reminder(*right, &company, "R", None, false);
+            assert!(!reminder.contains('{'), "{}", letter);
+        }
+    }
+
+    assert_eq!(
+        Command::from_text("request Acme GmbH art17\nalso the newsletter\n-- \nsig"),
+        Ok(Command::Request(
+            String::from("Acme GmbH"),
+            Right::Erasure,
+            String::from("also the newsletter")
+        ))
+    );
+    let once = Request::Once(String::from("acme"), Right::Objection, String::new());
+    assert_eq!(Request::parse(&once.to_json().to_string()), Ok(once));
+
+    let dir = std::env::temp_dir().join(format!("datenbriefd-rights-{}", std::process::id()));
+    let mut config = Config::new();
+    config.spool.dir = dir.to_string_lossy().to_string();
+    config.archive.dir = dir.join("archive").to_string_lossy().to_string();
+    config.time_file = dir.join("time.json").to_string_lossy().to_string();
+    config.transport = Backend::Maildir(dir.join("out").to_string_lossy().to_string());
+    let now = Utc.ymd(2026, 10, 19).and_hms(12, 0, 0);
+    let next = now + chrono::Duration::days(100);
+    config.companies.push(Company {
+        mail: String::from("privacy@acme.example"),
+        next_hit: next,
+        known: true,
+        ..company
+    });
+    let command = Command::Request(String::from("acme"), Right::Rectification, String::new());
+    assert!(config.execute(&command, now).is_err());
+    let command = Command::Request(String::from("acme"), Right::Erasure, String::new());
+    config.execute(&command, now).unwrap();
+    assert_eq!(config.scheduler.due(&config.companies, now), vec![0]);
+    config.flush_spool(now);
+
+    let v = &config.companies[0];
+    assert_eq!(
+        (v.right(), v.next_hit, v.pending.is_none()),
+        (Right::Erasure, next, true)
+    );
+    assert!(config.status().contains("open erasure request"));
+    // without undue delay, so reminded after half of the usual 30 days
+    assert_eq!(
+        config.scheduler.reminder_at(v),
+        Some(now + chrono::Duration::days(15))
+    );
+    let reminder = mail::reminder(v, Default::default(), "<r@me.example>", now);
+    assert!(reminder.body.contains("unverzüglich zu handeln"));
+
+    // a second request waits for the open one to be answered
+    let command = Command::Request(String::from("acme"), Right::Objection, String::new());
+    let reply = config.execute(&command, now).unwrap();
+    assert!(
+        reply.contains("until the open request is answered"),
+        "{}",
+        reply
+    );
+    assert!(config.scheduler.due(&config.companies, now).is_empty());
+    config.flush_spool(now);
+    assert_eq!(config.companies[0].right(), Right::Erasure);
+    let later = now + chrono::Duration::days(3);
+    config.set_outcome(0, Outcome::Provided, later);
+    assert_eq!(config.companies[0].next_hit, next);
+    assert_eq!(config.scheduler.due(&config.companies, later), vec![0]);
+
+    let mut reloaded = Config::new();
+    reloaded.companies.push(Company {
+        name: String::from("Acme"),
+        ..Company::new()
+    });
+    reloaded.parse_time(&std::fs::read_to_string(&config.time_file).unwrap());
+    assert_eq!(reloaded.companies[0].right(), Right::Erasure);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn legal_deadline() {
+    use super::{classify::Outcome, control::Command, schedule, Company, Config};
+    use chrono::{Duration, TimeZone, Utc};
+    let deadline = |y, m, d| Utc.ymd(y, m, d).and_hms(23, 59, 59);
+    // the 28th of february 2026 is a saturday
+    let end_of_january = Utc.ymd(2026, 1, 31).and_hms(10, 0, 0);
+    assert_eq!(schedule::deadline(end_of_january, 1), deadline(2026, 3, 2));
+    assert_eq!(schedule::deadline(end_of_january, 3), deadline(2026, 4, 30));
+    let leap = Utc.ymd(2024, 1, 31).and_hms(10, 0, 0);
+    assert_eq!(schedule::deadline(leap, 1), deadline(2024, 2, 29));
+
+    let dir = std::env::temp_dir().join(format!("datenbriefd-deadline-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut config = Config::new();
+    config.time_file = dir.join("time.json").to_string_lossy().to_string();
+    for name in ["extended", "late", "verified"].iter() {
+        config.companies.push(Company {
+            name: name.to_string(),
+            last_hit: Some(end_of_january),
+            message_id: Some(format!("<{}@me.example>", name)),
+            ..Company::new()
+        });
+    }
+    config.set_outcome(0, Outcome::Extension, end_of_january + Duration::days(10));
+    assert_eq!(config.companies[0].deadline(), Some(deadline(2026, 4, 30)));
+    assert_eq!(
+        config.scheduler.reminder_at(&config.companies[0]),
+        Some(deadline(2026, 4, 30))
+    );
+    let late = deadline(2026, 3, 2) + Duration::hours(1);
+    config.set_outcome(1, Outcome::Extension, late);
+    assert_eq!(config.companies[1].deadline(), Some(deadline(2026, 3, 2)));
+    assert!(config.companies[1].is_overdue(deadline(2026, 3, 3)));
+
+    // the identity check takes 10 days and moves the deadline with it
+    config.set_outcome(2, Outcome::Verification, end_of_january + Duration::days(5));
+    assert_eq!(config.companies[2].deadline(), None);
+    assert_eq!(config.scheduler.reminder_at(&config.companies[2]), None);
+    assert!(!config.companies[2].is_overdue(deadline(2026, 4, 1)));
+    let resume = Command::Resume(String::from("verified"));
+    let verified = end_of_january + Duration::days(15);
+    config.execute(&resume, verified).unwrap();
+    assert_eq!(config.companies[2].deadline(), Some(deadline(2026, 3, 10)));
+
+    let mut reloaded = Config::new();
+    reloaded.companies = config
+        .companies
+        .iter()
+        .map(|v| Company {
+            name: v.name.clone(),
+            ..Company::new()
+        })
+        .collect();
+    reloaded.parse_time(&std::fs::read_to_string(&config.time_file).unwrap());
+    let deadlines: Vec<_> = reloaded.companies.iter().map(|v| v.deadline()).collect();
+    let expected: Vec<_> = config.companies.iter().map(|v| v.deadline()).collect();
+    assert_eq!(deadlines, expected);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn complaint_draft() {
+    use super::{classify::Outcome, template::Language, transport::Backend, Company, Config};
+    use chrono::{Duration, TimeZone, Utc};
+    let dir = std::env::temp_dir().join(format!("datenbriefd-complaint-{}", std::process::id()));
+    let maildir = dir.join("out");
+    let mut config = Config::new();
+    config.archive.dir = dir.join("archive").to_string_lossy().to_string();
+    config.time_file = dir.join("time.json").to_string_lossy().to_string();
+    config.transport = Backend::Maildir(maildir.to_string_lossy().to_string());
+    config.control_address = Some(String::from("datenbriefd@me.example"));
+    config.control_auth.senders = vec![String::from("operator@me.example")];
+    let sent = Utc.ymd(2026, 6, 1).and_hms(9, 0, 0);
+    let mut company = Company {
+        name: String::from("Acme"),
+        mail: String::from("privacy@acme.example"),
+        alias: String::from("acme@me.example"),
+        onw_name: String::from("Erika Mustermann"),
+        last_hit: Some(sent),
+        message_id: Some(String::from("<r@me.example>")),
+        reference: Some(String::from("DB-1")),
+        reminder: 2,
+        ..Company::new()
+    };
+    company.record(sent, String::from("sent request DB-1"));
+    config.companies.push(company);
+    let letter = b"Subject: [DB-1] Auskunft\r\n\r\nHallo".to_vec();
+    let archive = &config.archive;
+    let v = &config.companies[0];
+    archive.store_letter(v, "db-1", &letter, sent).unwrap();
+
+    let escalation = config.scheduler.escalation_at(v).unwrap();
+    assert_eq!(escalation, Utc.ymd(2026, 7, 29).and_hms(23, 59, 59));
+    config.file_complaints(escalation - Duration::days(1));
+    assert_eq!(config.companies[0].complained, None);
+    config.file_complaints(escalation);
+    config.file_complaints(escalation + Duration::days(1));
+    assert_eq!(config.companies[0].complained, Some(escalation));
+
+    let mails: Vec<_> = std::fs::read_dir(maildir.join("new")).unwrap().collect();
+    assert_eq!(mails.len(), 1);
+    let file = dir.join("archive").join("Acme").join("complaint-DB_1.eml");
+    let draft = std::fs::read_to_string(file).unwrap();
+    assert!(draft.contains("To: operator@me.example"));
+    assert!(draft.contains("Subject: [DB-1] Beschwerde nach Art. 77 DSGVO gegen Acme"));
+    assert!(draft.contains(" 2026-06-01  sent request DB-1"));
+    assert!(draft.contains("filename=\"20260601T090000-db-1.eml\""));
+    let british = Language::British.complaint_subject(&config.companies[0]);
+    assert!(british.contains("Art. 77 UK GDPR against Acme"));
+
+    // a refusal is escalated without waiting for the reminders
+    config.companies.push(Company {
+        name: String::from("Refuser"),
+        mail: String::from("privacy@refuser.example"),
+        alias: String::from("refuser@me.example"),
+        last_hit: Some(sent),
+        message_id: Some(String::from("<s@me.example>")),
+        reference: Some(String::from("DB-2")),
+        ..Company::new()
+    });
+    let refused = sent + Duration::days(3);
+    config.set_outcome(1, Outcome::Refused, refused);
+    assert_eq!(config.scheduler.reminder_at(&config.companies[1]), None);
+    config.file_complaints(refused);
+    assert_eq!(config.companies[1].complained, Some(refused));
+    let mails: Vec<_> = std::fs::read_dir(maildir.join("new")).unwrap().collect();
+    assert_eq!(mails.len(), 2);
+
+    // a failed mail is recorded once instead of being retried on every tick
+    let failed = escalation + Duration::days(2);
+    config.companies[0].complained = None;
+    config.transport = Backend::Sendmail(dir.join("missing").to_string_lossy().to_string());
+    config.file_complaints(failed);
+    config.file_complaints(failed + Duration::days(1));
+    let v = &config.companies[0];
+    assert_eq!(v.complained, Some(failed));
+    let failures = v
+        .history
+        .iter()
+        .filter(|(_, v)| v.contains("could not mail"));
+    assert_eq!(failures.count(), 1);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn sent_folder_copies() {
+    use super::{transport::Backend, Company, Config, Encryption};
+    use chrono::Utc;
+    use std::io::{BufRead, BufReader, Read, Write};
+    let dir = std::env::temp_dir().join(format!("datenbriefd-sent-{}", std::process::id()));
+    let mut config = Config::new();
+    config.spool.dir = dir.join("spool").to_string_lossy().to_string();
+    config.archive.dir = dir.join("archive").to_string_lossy().to_string();
+    config.time_file = dir.join("time.json").to_string_lossy().to_string();
+    config.transport = Backend::Maildir(dir.join("out").to_string_lossy().to_string());
+    config.sent_folder = Some(String::from("Sent"));
+    let now = Utc::now();
+    config.companies.push(Company {
+        name: String::from("Acme"),
+        mail: String::from("privacy@acme.example"),
+        alias: String::from("acme@me.example"),
+        next_hit: now,
+        ..Company::new()
+    });
+    config.hit(0, now, false);
+    config.flush_spool(now);
+    let ids = config.spool.sent().unwrap();
+    assert_eq!(ids.len(), 1);
+    let message = config.spool.sent_message(&ids[0]).unwrap();
+
+    // the first connection refuses the append, the second one files the copy
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    config.Imap.host = String::from("127.0.0.1");
+    config.Imap.port = listener.local_addr().unwrap().port();
+    config.Imap.encryption = Encryption::none;
+    let (tx, rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        for accept in [false, true].iter() {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            write!(stream, "* PREAUTH ready\r\n").unwrap();
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 0 {
+                let (tag, command) = line.trim_end().split_once(' ').unwrap();
+                if !command.starts_with("APPEND") {
+                    write!(stream, "* BYE\r\n{} OK\r\n", tag).unwrap();
+                    break;
+                }
+                if !accept {
+                    write!(stream, "{} NO over quota\r\n", tag).unwrap();
+                } else {
+                    let len = command.rsplit('{').next().unwrap();
+                    let len: usize = len.trim_end_matches('}').parse().unwrap();
+                    write!(stream, "+ go ahead\r\n").unwrap();
+                    let mut message = vec![0; len + 2];
+                    reader.read_exact(&mut message).unwrap();
+                    message.truncate(len);
+                    tx.send(message).unwrap();
+                    write!(stream, "{} OK APPEND completed\r\n", tag).unwrap();
+                }
+                line.clear();
+            }
+        }
+    });
+    config.file_sent();
+    assert_eq!(config.spool.sent().unwrap(), ids);
+    config.file_sent();
+    assert_eq!(rx.recv().unwrap(), message);
+    assert!(config.spool.sent().unwrap().is_empty());
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn office_documents_stay_whole() {
+    use super::{archive::Archive, mime::Part, unpack, Company};
+    use chrono::Utc;
+    use std::io::Write;
+    let zip = |names: &[&str]| {
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        for name in names {
+            zip.start_file(*name, Default::default()).unwrap();
+            zip.write_all(b"<xml/>").unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    };
+    let docx = zip(&["[Content_Types].xml", "word/document.xml"]);
+    let export = zip(&["data.json"]);
+    let dir = std::env::temp_dir().join(format!("datenbriefd-office-{}", std::process::id()));
+    assert!(unpack::unpack(&docx, &dir.join("docx"), None)
+        .unwrap()
+        .is_none());
+    assert!(unpack::unpack(&export, &dir.join("zip"), None)
+        .unwrap()
+        .is_some());
+    assert!(unpack::is_document(
+        "Auskunft.ODT",
+        "application/octet-stream"
+    ));
+    let sheet = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet";
+    assert!(unpack::is_document("export", sheet));
+
+    let archive = Archive {
+        dir: dir.join("archive").to_string_lossy().to_string(),
+    };
+    let company = Company {
+        name: String::from("acme"),
+        ..Company::new()
+    };
+    let raw = format!(
+        "From: privacy@acme.example\r
+Subject: Ihre Daten\r
+Content-Type: multipart/mixed; boundary=b1\r
+\r
+--b1\r
+Content-Type: application/octet-stream; name=Auskunft.docx\r
+Content-Transfer-Encoding: base64\r
+\r
+{}\r
+--b1--\r
+",
+        base64::encode(&docx)
+    );
+    let message = Part::parse(raw.as_bytes());
+    let stored = archive
+        .store(&company, raw.as_bytes(), &message, Utc::now())
+        .unwrap();
+    assert_eq!(std::fs::read(stored.join("Auskunft.docx")).unwrap(), docx);
+    assert!(!stored.join("Auskunft.docx-unpacked").exists());
+    let records = archive.records(&company).unwrap();
+    assert!(records[0]["attachments"][0].get("unpacked").is_none());
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn fulfilment_starts_next_cycle() {
+    use super::{classify::Outcome, right::Right, Company, Config};
+    use chrono::{Duration, TimeZone, Utc};
+    let dir = std::env::temp_dir().join(format!("datenbriefd-fulfil-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut config = Config::new();
+    config.time_file = dir.join("time.json").to_string_lossy().to_string();
+    let sent = Utc.ymd(2026, 3, 1).and_hms(9, 0, 0);
+    config.companies.push(Company {
+        name: String::from("acme"),
+        interval: 90,
+        last_hit: Some(sent),
+        message_id: Some(String::from("<r@me.example>")),
+        reminder: 1,
+        ..Company::new()
+    });
+    let fulfilled = sent + Duration::days(20);
+    config.set_outcome(0, Outcome::Provided, fulfilled);
+    let v = &config.companies[0];
+    assert_eq!(v.response_days(), &[20]);
+    assert_eq!(v.reminder, 0);
+    assert_eq!(v.next_hit, fulfilled + Duration::days(90));
+    assert_eq!(config.scheduler.reminder_at(v), None);
+
+    // more data for the same request keeps the schedule
+    config.set_outcome(0, Outcome::Provided, fulfilled + Duration::days(5));
+    let v = &config.companies[0];
+    assert_eq!(v.response_days(), &[20]);
+    assert_eq!(v.next_hit, fulfilled + Duration::days(90));
+
+    // an erasure does not restart the access cycle
+    let next = sent + Duration::days(300);
+    config.companies.push(Company {
+        name: String::from("shop"),
+        next_hit: next,
+        last_hit: Some(sent),
+        message_id: Some(String::from("<e@me.example>")),
+        right: Right::Erasure,
+        ..Company::new()
+    });
+    config.set_outcome(1, Outcome::Provided, fulfilled);
+    let v = &config.companies[1];
+    assert!(v.is_fulfilled());
+    assert_eq!(v.response_days(), &[20]);
+    assert_eq!(v.next_hit, next);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn control_reply_mail() {
+    use super::{classify::Outcome, control::Command, mail, mime::Part, right::Right};
+    use chrono::{TimeZone, Utc};
+    let commands = [
+        Command::Status,
+        Command::Send(String::from("Acme GmbH")),
+        Command::Classify(String::from("acme"), Outcome::NoData),
+        Command::Request(String::from("acme"), Right::Erasure, String::new()),
+    ];
+    for command in commands.iter() {
+        assert_eq!(Command::parse(&command.to_string()).as_ref(), Ok(command));
+    }
+    let snippet = Command::from_text("company Shop.io\ninterval = 90\n").unwrap();
+    assert_eq!(snippet.to_string(), "company Shop.io");
+
+    let now = Utc.ymd(2026, 10, 19).and_hms(12, 0, 0);
+    let command = Part::parse(
+        b"From: Op <op@me.example>\r\nSubject: explode acme\r\nMessage-ID: <7@me.example>\r\n\r\n",
+    );
+    let reply = mail::control_reply("datenbrief@me.example", &command, "failed: no\n", now);
+    let reply = Part::parse(reply.render().as_bytes());
+    assert_eq!(reply.header("To"), Some("Op <op@me.example>"));
+    assert_eq!(
+        reply.header("From"),
+        Some("datenbriefd <datenbrief@me.example>")
+    );
+    assert_eq!(
+        reply.decoded("Subject").as_deref(),
+        Some("Re: explode acme")
+    );
+    assert_eq!(reply.header("In-Reply-To"), Some("<7@me.example>"));
+    assert_eq!(reply.header("References"), Some("<7@me.example>"));
+    assert_eq!(reply.header("Auto-Submitted"), Some("auto-replied"));
+    assert_eq!(reply.text().trim(), "failed: no");
+
+    let command = Part::parse(b"From: op@me.example\r\nSubject: Re: status\r\n\r\n");
+    let reply = mail::control_reply("datenbrief@me.example", &command, "ok", now);
+    assert_eq!(reply.get("Subject"), Some("Re: status"));
+    assert_eq!(reply.get("In-Reply-To"), None);
+    let command = Part::parse(b"From: op@me.example\r\n\r\nstatus\r\n");
+    let reply = mail::control_reply("datenbrief@me.example", &command, "ok", now);
+    assert_eq!(reply.get("Subject"), Some("datenbriefd"));
+}
@@ -0,0 +1,130 @@
+use super::{net::other, smtp, ServerConfig};
+use std::{
+    fs, io,
+    io::Write,
+    path::PathBuf,
+    process::{Command, Stdio},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// way to hand a message over for delivery
+pub trait Transport {
+    /// submit `message` from `from` to all `to`
+    fn send(&mut self, from: &str, to: &[&str], message: &[u8]) -> io::Result<()>;
+
+    /// end the session
+    fn close(self: Box<Self>) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// configured transport
+#[derive(Debug, Clone)]
+pub enum Backend {
+    /// submit to `Config.Smtp`
+    Smtp,
+    /// pipe into a `sendmail -t` compatible binary
+    Sendmail(String),
+    /// write into a maildir
+    Maildir(String),
+}
+
+impl Backend {
+    pub fn parse(
+        kind: &str,
+        sendmail: Option<&str>,
+        maildir: Option<&str>,
+    ) -> Result<Self, String> {
+        match kind.to_lowercase().as_str() {
+            "smtp" => Ok(Backend::Smtp),
+            "sendmail" => Ok(Backend::Sendmail(
+                sendmail.unwrap_or("/usr/sbin/sendmail").to_string(),
+            )),
+            "maildir" => match maildir {
+                Some(dir) => Ok(Backend::Maildir(dir.to_string())),
+                None => Err(String::from("maildir transport needs a directory")),
+            },
+            _ => Err(format!("unknown transport '{}'", kind)),
+        }
+    }
+
+    /// open a session, `smtp` is only used by the smtp backend
+    pub fn connect(&self, smtp: &ServerConfig) -> io::Result<Box<dyn Transport>> {
+        match self {
+            Backend::Smtp => Ok(Box::new(smtp::Client::connect(smtp)?)),
+            Backend::Sendmail(command) => Ok(Box::new(Sendmail {
+                command: command.clone(),
+            })),
+            Backend::Maildir(dir) => Ok(Box::new(Maildir::new(dir)?)),
+        }
+    }
+}
+
+impl Transport for smtp::Client {
+    fn send(&mut self, from: &str, to: &[&str], message: &[u8]) -> io::Result<()> {
+        smtp::Client::send(self, from, to, message)
+    }
+
+    fn close(self: Box<Self>) -> io::Result<()> {
+        self.quit()
+    }
+}
+
+/// pipes messages into sendmail
+pub struct Sendmail {
+    command: String,
+}
+
+impl Transport for Sendmail {
+    fn send(&mut self, from: &str, _to: &[&str], message: &[u8]) -> io::Result<()> {
+        let mut child = Command::new(&self.command)
+            .arg("-t")
+            .arg("-i")
+            .arg("-f")
+            .arg(from)
+            .stdin(Stdio::piped())
+            .spawn()?;
+        // closed before waiting, sendmail reads until the end of input
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(message)?;
+        }
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(other(format!("{} exited with {}", self.command, status)));
+        }
+        Ok(())
+    }
+}
+
+/// stores messages in a maildir for review or tests
+pub struct Maildir {
+    dir: PathBuf,
+}
+
+static MAILDIR_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+impl Maildir {
+    pub fn new(dir: &str) -> io::Result<Self> {
+        let dir = PathBuf::from(dir);
+        for sub in &["tmp", "new", "cur"] {
+            fs::create_dir_all(dir.join(sub))?;
+        }
+        Ok(Self { dir })
+    }
+}
+
+impl Transport for Maildir {
+    fn send(&mut self, _from: &str, _to: &[&str], message: &[u8]) -> io::Result<()> {
+        let name = format!(
+            "{}.{}_{}.datenbriefd",
+            chrono::Utc::now().timestamp(),
+            std::process::id(),
+            MAILDIR_COUNTER.fetch_add(1, Ordering::SeqCst)
+        );
+        let tmp = self.dir.join("tmp").join(&name);
+        fs::write(&tmp, message)?;
+        fs::rename(tmp, self.dir.join("new").join(&name))?;
+        debug!("stored message as {}", name);
+        Ok(())
+    }
+}
@@ -0,0 +1,220 @@
+use super::net::other;
+use flate2::read::GzDecoder;
+use std::{
+    fs,
+    io::{self, Cursor, Read},
+    path::{Component, Path, PathBuf},
+};
+use zip::{result::ZipError, ZipArchive};
+
+/// upper limit for the unpacked size of one archive
+pub const MAX_SIZE: u64 = 512 * 1024 * 1024;
+
+/// words introducing a password in a mail
+const KEYWORDS: &[&str] = &["passwort", "kennwort", "password", "passphrase"];
+
+/// extensions of office documents which are zip files inside
+const DOCUMENTS: &[&str] = &[
+    "docx", "docm", "xlsx", "xlsm", "pptx", "pptm", "odt", "ods", "odp", "odg",
+];
+
+/// files unpacked from an archive
+#[derive(Debug, Clone, Default)]
+pub struct Unpacked {
+    /// paths relative to the destination directory
+    pub files: Vec<String>,
+    /// some entries could not be unpacked without (the right) password
+    pub encrypted: bool,
+}
+
+/// unpack zip, tar, tar.gz and gz `data` into `dest`, `None` if it is no archive
+pub fn unpack(data: &[u8], dest: &Path, password: Option<&str>) -> io::Result<Option<Unpacked>> {
+    let mut unpacked = Unpacked::default();
+    if data.starts_with(b"PK\x03\x04") {
+        if is_office_zip(data) {
+            return Ok(None);
+        }
+        unzip(data, dest, password, &mut unpacked)?;
+    } else if is_tar(data) {
+        untar(data, dest, &mut unpacked)?;
+    } else if data.starts_with(&[0x1f, 0x8b]) {
+        let mut inner = Vec::new();
+        GzDecoder::new(data)
+            .take(MAX_SIZE + 1)
+            .read_to_end(&mut inner)?;
+        if inner.len() as u64 > MAX_SIZE {
+            return Err(other("archive is too large"));
+        }
+        if is_tar(&inner) {
+            untar(&inner, dest, &mut unpacked)?;
+        } else {
+            let name = dest
+                .file_name()
+                .map(|v| v.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let name = name.trim_end_matches("-unpacked").trim_end_matches(".gz");
+            let name = if name.is_empty() { "content" } else { name };
+            write(dest, Path::new(name), &inner, &mut unpacked)?;
+        }
+    } else {
+        return Ok(None);
+    }
+    Ok(Some(unpacked))
+}
+
+/// whether the attachment `name` of `content_type` is an office document kept as it is
+pub fn is_document(name: &str, content_type: &str) -> bool {
+    let extension = name
+        .rsplit_once('.')
+        .map(|v| v.1.to_lowercase())
+        .unwrap_or_default();
+    DOCUMENTS.contains(&extension.as_str())
+        || content_type.starts_with("application/vnd.openxmlformats-officedocument.")
+        || content_type.starts_with("application/vnd.oasis.opendocument.")
+}
+
+/// whether the zip `data` is an ooxml or odf document, whatever its name
+fn is_office_zip(data: &[u8]) -> bool {
+    ZipArchive::new(Cursor::new(data)).is_ok_and(|archive| {
+        archive
+            .file_names()
+            .any(|v| v == "[Content_Types].xml" || v == "mimetype")
+    })
+}
+
+/// whether `data` is a pdf with a password
+pub fn is_encrypted_pdf(data: &[u8]) -> bool {
+    data.starts_with(b"%PDF") && data.windows(8).any(|v| v == b"/Encrypt")
+}
+
+/// passwords mentioned in `text`, like `Das Passwort lautet: abc123`
+pub fn passwords(text: &str) -> Vec<String> {
+    let mut passwords = Vec::new();
+    let mut lines = text.lines().map(|v| v.trim());
+    while let Some(line) = lines.next() {
+        let lower = line.to_lowercase();
+        let start = match KEYWORDS.iter().filter_map(|v| lower.find(v)).min() {
+            Some(start) => start,
+            None => continue,
+        };
+        // keep the case of the password, the lower cased line may have a different length
+        let rest = match line.get(start..) {
+            Some(rest) => rest,
+            None => continue,
+        };
+        let value = match rest.find(':') {
+            Some(pos) => rest[pos + 1..].trim().to_string(),
+            None => {
+                let words: Vec<&str> = rest.split_whitespace().collect();
+                match words.iter().position(|v| v.eq_ignore_ascii_case("lautet")) {
+                    Some(pos) => words[pos + 1..].join(" "),
+                    None => continue,
+                }
+            }
+        };
+        // the password may follow on its own line
+        let value = if value.is_empty() {
+            lines.next().unwrap_or_default().to_string()
+        } else {
+            value
+        };
+        let value = value
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .trim_end_matches(['.', ',', ';'])
+            .trim_matches(|v| "\"'„“”‚‘’«»".contains(v));
+        if !value.is_empty() && !passwords.iter().any(|v| v == value) {
+            passwords.push(value.to_string());
+        }
+    }
+    passwords
+}
+
+fn is_tar(data: &[u8]) -> bool {
+    data.get(257..262) == Some(b"ustar")
+}
+
+fn unzip(data: &[u8], dest: &Path, password: Option<&str>, out: &mut Unpacked) -> io::Result<()> {
+    let mut archive = ZipArchive::new(Cursor::new(data)).map_err(other)?;
+    let mut size = 0;
+    for i in 0..archive.len() {
+        let file = match password {
+            Some(password) => match archive.by_index_decrypt(i, password.as_bytes()) {
+                Ok(Ok(file)) => file,
+                Ok(Err(_)) => {
+                    out.encrypted = true;
+                    continue;
+                }
+                Err(err) => return Err(other(err)),
+            },
+            None => match archive.by_index(i) {
+                Ok(file) => file,
+                Err(ZipError::UnsupportedArchive(msg)) if msg == ZipError::PASSWORD_REQUIRED => {
+                    out.encrypted = true;
+                    continue;
+                }
+                Err(err) => return Err(other(err)),
+            },
+        };
+        if file.is_dir() {
+            continue;
+        }
+        let path = match file.enclosed_name() {
+            Some(path) => path.to_path_buf(),
+            None => {
+                warn!("skipping unsafe path {} in zip archive", file.name());
+                continue;
+            }
+        };
+        let mut content = Vec::new();
+        if let Err(err) = file.take(MAX_SIZE + 1 - size).read_to_end(&mut content) {
+            // a wrong password is only detected by the checksum in some cases
+            if password.is_some() {
+                out.encrypted = true;
+                continue;
+            }
+            return Err(err);
+        }
+        size += content.len() as u64;
+        if size > MAX_SIZE {
+            return Err(other("archive is too large"));
+        }
+        write(dest, &path, &content, out)?;
+    }
+    Ok(())
+}
+
+fn untar(data: &[u8], dest: &Path, out: &mut Unpacked) -> io::Result<()> {
+    let mut archive = tar::Archive::new(data);
+    let mut size = 0;
+    for entry in archive.entries()? {
+        let entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry.path()?.to_path_buf();
+        if !path.components().all(|v| matches!(v, Component::Normal(_))) {
+            warn!("skipping unsafe path {} in tar archive", path.display());
+            continue;
+        }
+        let mut content = Vec::new();
+        entry.take(MAX_SIZE + 1 - size).read_to_end(&mut content)?;
+        size += content.len() as u64;
+        if size > MAX_SIZE {
+            return Err(other("archive is too large"));
+        }
+        write(dest, &path, &content, out)?;
+    }
+    Ok(())
+}
+
+fn write(dest: &Path, path: &Path, content: &[u8], out: &mut Unpacked) -> io::Result<()> {
+    let target: PathBuf = dest.join(path);
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&target, content)?;
+    out.files.push(path.to_string_lossy().to_string());
+    Ok(())
+}
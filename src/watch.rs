@@ -0,0 +1,506 @@
+use super::{
+    api,
+    bounce::{self, Bounce},
+    classify::{Action, Outcome},
+    imap,
+    mime::{self, Part},
+    right::Right,
+    sha256, Company, Config, ServerConfig,
+};
+use chrono::{prelude::*, Duration};
+use serde_json::Value;
+use std::{io, sync::mpsc::Sender, thread};
+
+/// mailbox with new mail or a request on the api socket
+#[derive(Debug, Clone)]
+pub enum Event {
+    Replies,
+    Control,
+    /// api request and where to send the response
+    Request(api::Request, Sender<Value>),
+}
+
+/// message ids of handled replies kept to detect duplicates
+const MAX_PROCESSED: usize = 10_000;
+
+/// uids fetched with one command, keeps large mailboxes out of memory
+const FETCH_BATCH: u32 = 50;
+
+/// position of the reply watcher in a folder
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct Checkpoint {
+    pub uid_validity: u32,
+    /// highest uid already handled
+    pub uid: u32,
+}
+
+/// what a handled reply turned out to be
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Reply {
+    Bounce,
+    /// acknowledgement or other automatic reply
+    Auto,
+    Answer(Option<Outcome>),
+}
+
+impl Reply {
+    /// imap keyword showing the classification in mail clients
+    fn keyword(&self) -> String {
+        match self {
+            Reply::Bounce => String::from("$bounce"),
+            Reply::Auto => String::from("$autoreply"),
+            Reply::Answer(Some(outcome)) => format!("${}", outcome),
+            Reply::Answer(None) => String::from("$answered"),
+        }
+    }
+}
+
+/// watch `mailbox` on `server` in the background and send `event` when new mail arrives
+///
+/// Uses IMAP IDLE where the server supports it and falls back to polling every `poll`.
+pub(crate) fn spawn_watcher(
+    server: ServerConfig,
+    mailbox: String,
+    poll: Duration,
+    event: Event,
+    tx: Sender<Event>,
+) {
+    let poll = poll.to_std().unwrap_or(std::time::Duration::from_secs(300));
+    thread::spawn(move || loop {
+        match watch(&server, &mailbox, poll, &event, &tx) {
+            // the daemon stopped listening
+            Ok(()) => return,
+            Err(err) => {
+                warn!("watching {} on {} failed: {}", mailbox, server.host, err);
+                thread::sleep(poll);
+                if tx.send(event.clone()).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+}
+
+fn watch(
+    server: &ServerConfig,
+    mailbox: &str,
+    poll: std::time::Duration,
+    event: &Event,
+    tx: &Sender<Event>,
+) -> io::Result<()> {
+    let mut client = imap::Client::connect(server)?;
+    if !client.has_capability("IDLE")? {
+        info!(
+            "{} does not support IDLE, polling every {} seconds",
+            server.host,
+            poll.as_secs()
+        );
+        client.logout()?;
+        loop {
+            thread::sleep(poll);
+            if tx.send(event.clone()).is_err() {
+                return Ok(());
+            }
+        }
+    }
+
+    client.select(mailbox)?;
+    debug!("waiting for mail in {} on {}", mailbox, server.host);
+    loop {
+        // servers may drop idle connections after 30 minutes
+        if client.idle(std::time::Duration::from_secs(25 * 60))? {
+            debug!("new mail in {} on {}", mailbox, server.host);
+            if tx.send(event.clone()).is_err() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+impl Config {
+    /// fetch new mails from `Imap` and process them
+    pub(crate) fn watch_replies(&mut self) {
+        if self.Imap.host.is_empty() {
+            return;
+        }
+        let mut client = match imap::Client::connect(&self.Imap) {
+            Ok(client) => client,
+            Err(err) => {
+                warn!(
+                    "could not connect to imap server {}: {}",
+                    self.Imap.host, err
+                );
+                return;
+            }
+        };
+        if let Err(err) = self.fetch_replies(&mut client) {
+            warn!("could not fetch replies from {}: {}", self.Imap.host, err);
+        }
+        if let Err(err) = client.logout() {
+            debug!("error closing imap connection: {}", err);
+        }
+    }
+
+    fn fetch_replies(&mut self, client: &mut imap::Client) -> std::io::Result<()> {
+        let mailbox = client.select(&self.inbox)?;
+        let key = format!("{}@{}/{}", self.Imap.user, self.Imap.host, self.inbox);
+        let mut checkpoint = self.checkpoints.get(&key).cloned().unwrap_or_default();
+        if checkpoint.uid_validity != mailbox.uid_validity {
+            if checkpoint.uid_validity != 0 {
+                warn!("uid validity of {} changed, reading it again", key);
+            }
+            checkpoint = Checkpoint {
+                uid_validity: mailbox.uid_validity,
+                uid: 0,
+            };
+        }
+        if mailbox.exists == 0 || (mailbox.uid_next != 0 && mailbox.uid_next <= checkpoint.uid + 1)
+        {
+            return Ok(());
+        }
+
+        if checkpoint.uid == 0 && mailbox.uid_next != 0 {
+            checkpoint.uid = self.first_uid(client, mailbox.uid_next)?;
+            debug!("starting {} after uid {}", key, checkpoint.uid);
+            self.checkpoints.insert(key.clone(), checkpoint.clone());
+            self.save_time();
+        }
+
+        loop {
+            let first = checkpoint.uid + 1;
+            // servers without UIDNEXT get everything at once
+            let range = if mailbox.uid_next == 0 {
+                format!("{}:*", first)
+            } else if first < mailbox.uid_next {
+                format!(
+                    "{}:{}",
+                    first,
+                    (first + FETCH_BATCH - 1).min(mailbox.uid_next - 1)
+                )
+            } else {
+                return Ok(());
+            };
+            for (uid, raw) in client.fetch(&range)? {
+                // `n:*` always matches the last message, even if it is older than `n`
+                if uid <= checkpoint.uid {
+                    continue;
+                }
+                self.fetched_reply(client, &key, uid, &raw);
+                checkpoint.uid = uid;
+                self.checkpoints.insert(key.clone(), checkpoint.clone());
+                self.save_time();
+            }
+            if mailbox.uid_next == 0 {
+                return Ok(());
+            }
+            // uids of deleted mails leave gaps
+            checkpoint.uid = checkpoint
+                .uid
+                .max((first + FETCH_BATCH - 1).min(mailbox.uid_next - 1));
+            self.checkpoints.insert(key.clone(), checkpoint.clone());
+            self.save_time();
+        }
+    }
+
+    /// uid after which a mailbox is read the first time
+    ///
+    /// Older mails can only answer the open requests, so everything sent before the oldest
+    /// one is skipped, and the whole mailbox if there is none.
+    fn first_uid(&self, client: &mut imap::Client, uid_next: u32) -> io::Result<u32> {
+        let since = self
+            .companies
+            .iter()
+            .filter(|v| v.message_id.is_some() && !v.is_fulfilled())
+            .filter_map(|v| v.last_hit)
+            .min();
+        let uids = match since {
+            Some(since) => client.search(&format!("SINCE {}", since.format("%-d-%b-%Y")))?,
+            None => Vec::new(),
+        };
+        Ok(uids.into_iter().min().unwrap_or(uid_next).saturating_sub(1))
+    }
+
+    /// handle the mail `uid` of the mailbox `key` unless it was seen before
+    fn fetched_reply(&mut self, client: &mut imap::Client, key: &str, uid: u32, raw: &[u8]) {
+        let id = message_id(raw);
+        if self.is_processed(&id) {
+            debug!("reply {} in {} was already handled", id, key);
+            return;
+        }
+        let reply = self.process_reply(raw, Utc::now());
+        if let Some((index, reply)) = reply {
+            if let Err(err) = self.file_reply(client, uid, index, &reply) {
+                warn!(
+                    "could not file reply {} of {}: {}",
+                    uid, self.companies[index].name, err
+                );
+            }
+        }
+        self.remember(id);
+    }
+
+    /// note the mail `id` as handled, forgetting the oldest ones
+    pub(crate) fn remember(&mut self, id: String) {
+        self.processed.push(id);
+        if self.processed.len() > MAX_PROCESSED {
+            self.processed.remove(0);
+        }
+    }
+
+    /// whether the mail `id` was handled before
+    pub(crate) fn is_processed(&self, id: &str) -> bool {
+        self.processed.iter().any(|v| v == id)
+    }
+
+    /// handle one mail received on `Imap`, returns the company it belongs to and what it is
+    ///
+    /// The mail counts from its date header if that is earlier than `now`.
+    pub(crate) fn process_reply(
+        &mut self,
+        raw: &[u8],
+        now: DateTime<Utc>,
+    ) -> Option<(usize, Reply)> {
+        let message = Part::parse(raw);
+        // mails fetched on the first sync can be years old, they count from their own date
+        let now = message.date().map_or(now, |v| v.min(now));
+        if let Some(bounce) = bounce::parse(&message) {
+            return self.bounced(&bounce, now).map(|i| (i, Reply::Bounce));
+        }
+        let i = match self.find_company(&message) {
+            Some(i) => i,
+            None => {
+                trace!(
+                    "mail from {} does not belong to any company",
+                    message.header("From").unwrap_or_default()
+                );
+                return None;
+            }
+        };
+        let v: &mut Company = &mut self.companies[i];
+        if let Err(err) = self.archive.store(v, raw, &message, now) {
+            error!("could not archive reply of {}: {}", v.name, err);
+        }
+        if v.last_hit.is_some_and(|sent| now < sent) {
+            debug!("mail from {} is older than the open request", v.name);
+            return Some((i, Reply::Answer(None)));
+        }
+        v.last_reply = Some(now);
+        // acknowledgements do not stop the reminders, the deadline keeps running
+        if self.autoreply.is_auto(&message) {
+            let ticket = self.autoreply.ticket(&message, v.reference.as_deref());
+            info!(
+                "automatic reply from {}, ticket {}",
+                v.name,
+                ticket.as_deref().unwrap_or("-")
+            );
+            v.record(
+                now,
+                format!(
+                    "automatic reply, ticket {}",
+                    ticket.as_deref().unwrap_or("-")
+                ),
+            );
+            if ticket.is_some() {
+                v.ticket = ticket;
+            }
+            self.save_time();
+            return Some((i, Reply::Auto));
+        }
+
+        info!(
+            "reply from {}: {}",
+            v.name,
+            message.decoded("Subject").unwrap_or_default()
+        );
+        let outcome = self.classifier.classify(&message);
+        match outcome {
+            Some(outcome) => self.set_outcome(i, outcome, now),
+            // newsletters and other mail to the alias end up here as well
+            None if v.last_hit.is_some() && !v.is_fulfilled() => {
+                info!("could not classify reply of {}, stopping reminders", v.name);
+                v.answered = Some(now);
+                v.record(now, String::from("reply, not classified, needs review"));
+                self.save_time();
+            }
+            None => {
+                info!("mail from {} without open request, needs review", v.name);
+                v.record(now, String::from("mail, no open request, needs review"));
+                self.save_time();
+            }
+        }
+        Some((i, Reply::Answer(outcome)))
+    }
+
+    /// move a handled reply to the folder of its company and tag it
+    fn file_reply(
+        &self,
+        client: &mut imap::Client,
+        uid: u32,
+        index: usize,
+        reply: &Reply,
+    ) -> io::Result<()> {
+        if self.reply_keywords {
+            client.add_flags(&uid.to_string(), &reply.keyword())?;
+        }
+        let prefix = match &self.reply_folder {
+            Some(prefix) if !prefix.is_empty() => prefix,
+            _ => return Ok(()),
+        };
+        let delimiter = client.delimiter()?;
+        let name = self.companies[index].name.replace(delimiter, "-");
+        let folder = format!("{}{}{}", prefix, delimiter, name);
+        debug!("moving reply {} to {}", uid, folder);
+        client.move_to(&uid.to_string(), &folder)
+    }
+
+    /// record the `outcome` of the open request of the company at `index` and act on it
+    pub(crate) fn set_outcome(&mut self, index: usize, outcome: Outcome, now: DateTime<Utc>) {
+        let v: &mut Company = &mut self.companies[index];
+        info!("answer of {} classified as {}", v.name, outcome);
+        v.outcome = Some((outcome, now));
+        v.record(now, format!("answer classified as {}", outcome));
+        match outcome {
+            Outcome::Verification => v.stop_clock(now),
+            Outcome::Extension => {
+                v.resume_clock(now);
+                v.extend(now);
+            }
+            _ => v.resume_clock(now),
+        }
+        match outcome.action() {
+            Action::Done => {
+                v.answered = Some(now);
+                // a second mail with data does not start the next cycle again
+                if let (Some(sent), false) = (v.last_hit, v.is_fulfilled()) {
+                    let days = (now - sent).num_days().max(0) as u32;
+                    v.response_days.push(days);
+                    v.fulfilled = Some(now);
+                    v.reminder = 0;
+                    // requests for other rights leave the periodic schedule alone
+                    if v.right == Right::Access {
+                        v.next_hit = now + Duration::days(v.interval as i64);
+                    }
+                    v.record(now, format!("request fulfilled after {} days", days));
+                    info!(
+                        "request to {} fulfilled after {} days, next request on {}",
+                        v.name,
+                        days,
+                        v.next_hit.format("%Y-%m-%d")
+                    );
+                }
+            }
+            Action::Pause => {
+                // only the clock stops, the next answer or `resume` starts it again
+                info!("stopped the deadline of {} for the identity check", v.name);
+            }
+            Action::Remind => v.answered = None,
+            Action::Escalate => {
+                // the complaint is drafted on the next tick, see `Scheduler::escalation_at`
+                warn!("{} refused the request, drafting a complaint", v.name);
+                v.answered = Some(now);
+            }
+        }
+        self.save_time();
+    }
+
+    /// mark the company a bounce belongs to as unreachable, returns its index
+    ///
+    /// Only the failed recipients are invalid, the thread just picks the company if several
+    /// share the address.
+    fn bounced(&mut self, bounce: &Bounce, now: DateTime<Utc>) -> Option<usize> {
+        let failed = |v: &Company| bounce.recipients.contains(&v.mail.to_lowercase());
+        let index = self
+            .companies
+            .iter()
+            .position(|v| failed(v) && bounce.message_ids.iter().any(|id| is_ours(v, id)))
+            .or_else(|| self.companies.iter().position(failed));
+        let v: &mut Company = match index {
+            Some(i) => &mut self.companies[i],
+            None => {
+                info!(
+                    "bounce for {:?} does not belong to any company",
+                    bounce.recipients
+                );
+                return None;
+            }
+        };
+        let reason = match &bounce.status {
+            Some(status) if !bounce.reason.contains(status.as_str()) => {
+                format!("{} ({})", bounce.reason, status)
+            }
+            _ => bounce.reason.clone(),
+        };
+        warn!(
+            "address {} of {} is invalid, stopping requests: {}",
+            v.mail, v.name, reason
+        );
+        v.record(now, format!("{} bounced: {}", v.mail, reason));
+        v.invalid = Some((v.mail.clone(), reason, now));
+        self.save_time();
+        index
+    }
+
+    /// company a reply belongs to, by thread, reference number or address
+    pub fn find_company(&self, message: &Part) -> Option<usize> {
+        let mut ids = message.message_ids("In-Reply-To");
+        ids.extend(message.message_ids("References"));
+        if let Some(i) = self
+            .companies
+            .iter()
+            .position(|v| ids.iter().any(|id| is_ours(v, id)))
+        {
+            return Some(i);
+        }
+
+        let subject = message.decoded("Subject").unwrap_or_default();
+        if let Some(i) = self.companies.iter().position(|v| {
+            v.reference
+                .as_ref()
+                .is_some_and(|reference| subject.contains(reference.as_str()))
+        }) {
+            return Some(i);
+        }
+
+        let mut to: Vec<String> = Vec::new();
+        for name in &["To", "Cc", "Delivered-To", "X-Original-To"] {
+            for value in message.headers(name) {
+                to.extend(value.split(',').map(mime::address));
+            }
+        }
+        if let Some(i) = self
+            .companies
+            .iter()
+            .position(|v| !v.alias.is_empty() && to.contains(&v.alias.to_lowercase()))
+        {
+            return Some(i);
+        }
+
+        let from = message.header("From").map(mime::address)?;
+        self.companies
+            .iter()
+            .position(|v| v.mail.to_lowercase() == from)
+    }
+}
+
+/// message id of the raw mail `raw`, a hash of the mail if it has none
+pub(crate) fn message_id(raw: &[u8]) -> String {
+    let message = Part::parse(raw);
+    match message.message_ids("Message-ID").into_iter().next() {
+        Some(id) => id,
+        None => format!("sha256:{}", sha256(raw)),
+    }
+}
+
+/// whether the message id `id` was sent to `company`, reminders carry the reference in their id
+fn is_ours(company: &Company, id: &str) -> bool {
+    let id = id.trim_matches(|v| v == '<' || v == '>');
+    if let Some(own) = &company.message_id {
+        if own.trim_matches(|v| v == '<' || v == '>') == id {
+            return true;
+        }
+    }
+    match (&company.reference, id.split('.').next()) {
+        (Some(reference), Some(prefix)) => reference.eq_ignore_ascii_case(prefix),
+        _ => false,
+    }
+}